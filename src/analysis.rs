@@ -0,0 +1,389 @@
+use serde_json::value::Value as Json;
+
+use crate::template::{
+    sorted_hash, BlockParam, DirectiveTemplate, HelperTemplate, Parameter, Template,
+    TemplateElement, TemplateMapping,
+};
+
+/// What a name in a template refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReferenceKind {
+    /// A data path, like `title`, `../user.name` or `@index`
+    Variable,
+    /// A path starting with a block param, like `item.name` in
+    /// `{{#each items as |item|}}`
+    BlockParam,
+    Helper,
+    Decorator,
+    Partial,
+}
+
+/// A name used by a template
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub kind: ReferenceKind,
+    pub name: String,
+    /// Line and column of the template element or subexpression using the
+    /// name, when the template was compiled with source map
+    pub position: Option<(usize, usize)>,
+    /// `true` when the name opens a block, like `{{#each}}` or
+    /// `{{#> layout}}`
+    pub block: bool,
+}
+
+/// The names used by a template, in the order they appear, hash params
+/// sorted by key
+///
+/// Expressions without parameters, like `{{title}}`, are reported as
+/// variables, even when a helper with that name exists at render time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TemplateReport {
+    pub references: Vec<Reference>,
+    /// Names of the partials defined in the template with `{{#*inline}}`
+    pub inline_partials: Vec<String>,
+}
+
+impl TemplateReport {
+    /// References of a given kind
+    pub fn of_kind(&self, kind: ReferenceKind) -> impl Iterator<Item = &Reference> {
+        self.references.iter().filter(move |r| r.kind == kind)
+    }
+
+    pub fn variables(&self) -> impl Iterator<Item = &Reference> {
+        self.of_kind(ReferenceKind::Variable)
+    }
+
+    pub fn helpers(&self) -> impl Iterator<Item = &Reference> {
+        self.of_kind(ReferenceKind::Helper)
+    }
+
+    pub fn decorators(&self) -> impl Iterator<Item = &Reference> {
+        self.of_kind(ReferenceKind::Decorator)
+    }
+
+    pub fn partials(&self) -> impl Iterator<Item = &Reference> {
+        self.of_kind(ReferenceKind::Partial)
+    }
+}
+
+/// A helper, decorator or partial that can't be found in a registry
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnresolvedReference {
+    pub template: String,
+    pub reference: Reference,
+}
+
+impl Template {
+    /// List the variables, helpers, decorators and partials the template uses
+    pub fn analyze(&self) -> TemplateReport {
+        let mut analyzer = Analyzer {
+            report: TemplateReport::default(),
+            block_params: Vec::new(),
+        };
+        analyzer.template(self);
+        analyzer.report
+    }
+}
+
+struct Analyzer {
+    report: TemplateReport,
+    // block params of the enclosing blocks
+    block_params: Vec<String>,
+}
+
+impl Analyzer {
+    fn add(&mut self, kind: ReferenceKind, name: &str, position: Option<(usize, usize)>) {
+        self.report.references.push(Reference {
+            kind,
+            name: name.to_owned(),
+            position,
+            block: false,
+        });
+    }
+
+    fn add_block(&mut self, kind: ReferenceKind, name: &str, position: Option<(usize, usize)>) {
+        self.add(kind, name, position);
+        if let Some(r) = self.report.references.last_mut() {
+            r.block = true;
+        }
+    }
+
+    fn is_block_param(&self, name: &str) -> bool {
+        let first = name.split(&['.', '/'][..]).next().unwrap_or(name);
+        self.block_params.iter().any(|p| p == first)
+    }
+
+    fn template(&mut self, t: &Template) {
+        for (idx, e) in t.elements.iter().enumerate() {
+            let position = t
+                .mapping
+                .as_ref()
+                .and_then(|m| m.get(idx))
+                .map(|&TemplateMapping(line, col)| (line, col));
+            self.element(e, position);
+        }
+    }
+
+    fn element(&mut self, e: &TemplateElement, position: Option<(usize, usize)>) {
+        match *e {
+            TemplateElement::HTMLExpression(ref p) => self.param(p, position),
+            TemplateElement::Expression(ref ht) | TemplateElement::HelperBlock(ref ht) => {
+                self.helper(ht, position)
+            }
+            TemplateElement::DirectiveExpression(ref dt)
+            | TemplateElement::DirectiveBlock(ref dt) => {
+                self.directive(dt, ReferenceKind::Decorator, position)
+            }
+            TemplateElement::PartialExpression(ref dt) | TemplateElement::PartialBlock(ref dt) => {
+                self.directive(dt, ReferenceKind::Partial, position)
+            }
+            TemplateElement::RawString(_) | TemplateElement::Comment(_) => {}
+        }
+    }
+
+    fn param(&mut self, p: &Parameter, position: Option<(usize, usize)>) {
+        match *p {
+            Parameter::Name(ref n) => {
+                let kind = if self.is_block_param(n) {
+                    ReferenceKind::BlockParam
+                } else {
+                    ReferenceKind::Variable
+                };
+                self.add(kind, n, position);
+            }
+            Parameter::Subexpression(ref s) => {
+                let position = s
+                    .mapping()
+                    .map(|&TemplateMapping(line, col)| (line, col))
+                    .or(position);
+                self.element(s.as_element(), position)
+            }
+            Parameter::Literal(_) => {}
+        }
+    }
+
+    fn helper(&mut self, ht: &HelperTemplate, position: Option<(usize, usize)>) {
+        if ht.is_name_only() {
+            self.param(&ht.name, position);
+            return;
+        }
+
+        match ht.name {
+            Parameter::Name(ref n) if ht.block => {
+                self.add_block(ReferenceKind::Helper, n, position)
+            }
+            Parameter::Name(ref n) => self.add(ReferenceKind::Helper, n, position),
+            ref p => self.param(p, position),
+        }
+        // the layout of an `extend` block is a template reference
+        if ht.name.as_name() == Some("extend") {
+            if let Some(Parameter::Literal(Json::String(ref layout))) = ht.params.first() {
+                self.add(ReferenceKind::Partial, layout, position);
+            }
+        }
+        for p in &ht.params {
+            self.param(p, position);
+        }
+        for (_, p) in sorted_hash(&ht.hash) {
+            self.param(p, position);
+        }
+
+        let scope = self.block_params.len();
+        match ht.block_param {
+            Some(BlockParam::Single(ref p)) => {
+                self.block_params.extend(p.as_name().map(|s| s.to_owned()))
+            }
+            Some(BlockParam::Pair((ref p1, ref p2))) => {
+                self.block_params.extend(p1.as_name().map(|s| s.to_owned()));
+                self.block_params.extend(p2.as_name().map(|s| s.to_owned()));
+            }
+            None => {}
+        }
+        if let Some(ref t) = ht.template {
+            self.template(t);
+        }
+        self.block_params.truncate(scope);
+        if let Some(ref t) = ht.inverse {
+            self.template(t);
+        }
+    }
+
+    fn directive(
+        &mut self,
+        dt: &DirectiveTemplate,
+        kind: ReferenceKind,
+        position: Option<(usize, usize)>,
+    ) {
+        match dt.name {
+            Parameter::Name(ref n) if dt.template.is_some() => self.add_block(kind, n, position),
+            Parameter::Name(ref n) => self.add(kind, n, position),
+            ref p => self.param(p, position),
+        }
+        if kind == ReferenceKind::Decorator && dt.name.as_name() == Some("inline") {
+            if let Some(Parameter::Literal(Json::String(ref name))) = dt.params.first() {
+                self.report.inline_partials.push(name.clone());
+            }
+        }
+        for p in &dt.params {
+            self.param(p, position);
+        }
+        for (_, p) in sorted_hash(&dt.hash) {
+            self.param(p, position);
+        }
+        if let Some(ref t) = dt.template {
+            self.template(t);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analysis::{Reference, ReferenceKind};
+    use crate::registry::Registry;
+    use crate::template::Template;
+
+    fn names<'a, I: Iterator<Item = &'a Reference>>(refs: I) -> Vec<&'a str> {
+        refs.map(|r| r.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_analyze() {
+        let t = Template::compile2(
+            "<h1>{{title}}</h1>\n\
+             {{#each items as |item i|}}{{i}}: {{format item.price currency=(lookup ../settings \"cur\")}}\
+             {{else}}{{{empty}}}{{/each}}\n\
+             {{#*inline \"row\"}}{{@index}}{{/inline}}{{> row}}{{> footer year=now}}\
+             {{> (which a)}}",
+            true,
+        )
+        .unwrap();
+        let report = t.analyze();
+
+        assert_eq!(
+            names(report.variables()),
+            vec![
+                "title",
+                "items",
+                "../settings",
+                "empty",
+                "@index",
+                "now",
+                "a"
+            ]
+        );
+        assert_eq!(
+            names(report.of_kind(ReferenceKind::BlockParam)),
+            vec!["i", "item.price"]
+        );
+        assert_eq!(
+            names(report.helpers()),
+            vec!["each", "format", "lookup", "which"]
+        );
+        assert_eq!(names(report.decorators()), vec!["inline"]);
+        assert_eq!(names(report.partials()), vec!["row", "footer"]);
+        assert_eq!(report.inline_partials, vec!["row"]);
+
+        let title = report.variables().next().unwrap();
+        assert_eq!(title.position, Some((1, 5)));
+        let price = report.of_kind(ReferenceKind::BlockParam).nth(1).unwrap();
+        assert_eq!(price.position, Some((2, 35)));
+        let settings = report.variables().nth(2).unwrap();
+        assert_eq!(settings.position, Some((2, 64)));
+        let each = report.helpers().next().unwrap();
+        assert!(each.block);
+        assert!(!report.helpers().nth(1).unwrap().block);
+    }
+
+    #[test]
+    fn test_analyze_hash_order() {
+        let t = Template::compile("{{f z=z y=(g (h b) c=c) x=x a=a}}{{> p q=q b=b}}").unwrap();
+        let report = t.analyze();
+        assert_eq!(
+            names(report.references.iter()),
+            vec!["f", "a", "x", "g", "h", "b", "c", "z", "p", "b", "q"]
+        );
+    }
+
+    #[test]
+    fn test_analyze_subexpression_position() {
+        let t = Template::compile2("{{f a\n  (g b (h c))}}", true).unwrap();
+        let report = t.analyze();
+        let positions: Vec<_> = report
+            .references
+            .iter()
+            .map(|r| (r.name.as_str(), r.position))
+            .collect();
+        assert_eq!(
+            positions,
+            vec![
+                ("f", Some((1, 1))),
+                ("a", Some((1, 1))),
+                ("g", Some((2, 3))),
+                ("b", Some((2, 3))),
+                ("h", Some((2, 8))),
+                ("c", Some((2, 8)))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unresolved_references() {
+        let mut r = Registry::new();
+        r.register_layout_helpers();
+        r.register_template_string("base", "{{#block \"body\"}}{{/block}}")
+            .unwrap();
+        r.register_template_string(
+            "page",
+            "{{#extend \"base\"}}{{/extend}}{{#extend \"missing\"}}{{/extend}}",
+        )
+        .unwrap();
+        r.register_template_string(
+            "t0",
+            "{{#*inline \"p\"}}{{/inline}}{{> p}}{{> base}}{{> nope}}\n{{#if (upper x)}}{{/if}}{{*deco}}\
+             {{#> fallback}}none{{/fallback}}",
+        )
+        .unwrap();
+
+        let unresolved: Vec<_> = r
+            .unresolved_references()
+            .into_iter()
+            .map(|u| {
+                (
+                    u.template,
+                    u.reference.kind,
+                    u.reference.name,
+                    u.reference.position,
+                )
+            })
+            .collect();
+        assert_eq!(
+            unresolved,
+            vec![
+                (
+                    "page".to_owned(),
+                    ReferenceKind::Partial,
+                    "missing".to_owned(),
+                    Some((1, 30))
+                ),
+                (
+                    "t0".to_owned(),
+                    ReferenceKind::Partial,
+                    "nope".to_owned(),
+                    Some((1, 45))
+                ),
+                (
+                    "t0".to_owned(),
+                    ReferenceKind::Helper,
+                    "upper".to_owned(),
+                    Some((2, 7))
+                ),
+                (
+                    "t0".to_owned(),
+                    ReferenceKind::Decorator,
+                    "deco".to_owned(),
+                    Some((2, 25))
+                ),
+            ]
+        );
+    }
+}
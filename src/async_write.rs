@@ -0,0 +1,335 @@
+//! Rendering to a `futures::io::AsyncWrite`, with the `async_write` feature
+//!
+//! Rendering itself is synchronous, so it runs on a thread of its own. The
+//! output is sent in chunks of at most `CHUNK_SIZE` bytes, written to the
+//! writer as they are produced without blocking the current thread. The
+//! render waits while the writer is behind, so only a few chunks are kept
+//! in memory.
+
+use std::io::{Error as IOError, ErrorKind};
+use std::mem;
+use std::sync::Arc;
+use std::thread;
+
+use futures::channel::mpsc::{self, Sender};
+use futures::executor::block_on;
+use futures::io::{AsyncWrite, AsyncWriteExt};
+use futures::sink::SinkExt;
+use futures::stream::StreamExt;
+use serde::Serialize;
+
+use crate::context::Context;
+use crate::error::{RenderError, TemplateRenderError};
+use crate::output::Output;
+use crate::registry::Registry;
+use crate::render::{RenderContext, Renderable};
+use crate::shared::SharedRegistry;
+use crate::template::Template;
+
+/// The size of the chunks written to the writer
+const CHUNK_SIZE: usize = 8 * 1024;
+
+enum Message {
+    Chunk(Vec<u8>),
+    Done(Result<(), RenderError>),
+}
+
+// Sends the rendered output in chunks, waiting while the channel is full
+struct ChunkOutput {
+    buffer: Vec<u8>,
+    sender: Sender<Message>,
+}
+
+impl ChunkOutput {
+    fn send(&mut self, message: Message) -> Result<(), IOError> {
+        // the receiver is gone when the future writing the chunks is dropped
+        block_on(self.sender.send(message))
+            .map_err(|_| IOError::new(ErrorKind::BrokenPipe, "Async write cancelled"))
+    }
+}
+
+impl Output for ChunkOutput {
+    fn write(&mut self, seg: &str) -> Result<(), IOError> {
+        self.buffer.extend_from_slice(seg.as_bytes());
+        while self.buffer.len() >= CHUNK_SIZE {
+            let rest = self.buffer.split_off(CHUNK_SIZE);
+            let chunk = mem::replace(&mut self.buffer, rest);
+            self.send(Message::Chunk(chunk))?;
+        }
+        Ok(())
+    }
+}
+
+// Runs `render` on a new thread and writes its output to `writer`
+async fn render_to_chunks<F, W>(render: F, mut writer: W) -> Result<(), RenderError>
+where
+    F: FnOnce(&mut ChunkOutput) -> Result<(), RenderError> + Send + 'static,
+    W: AsyncWrite + Unpin,
+{
+    let (sender, mut receiver) = mpsc::channel(1);
+    thread::spawn(move || {
+        let mut output = ChunkOutput {
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+            sender,
+        };
+        let result = render(&mut output);
+        if result.is_ok() && !output.buffer.is_empty() {
+            let chunk = mem::replace(&mut output.buffer, Vec::new());
+            if output.send(Message::Chunk(chunk)).is_err() {
+                return;
+            }
+        }
+        let _ = output.send(Message::Done(result));
+    });
+
+    while let Some(message) = receiver.next().await {
+        match message {
+            Message::Chunk(chunk) => writer.write_all(&chunk).await?,
+            Message::Done(result) => {
+                result?;
+                writer.flush().await?;
+                return Ok(());
+            }
+        }
+    }
+    Err(RenderError::new("Render thread panicked"))
+}
+
+async fn render_template_to_chunks<T, W>(
+    registry: Arc<Registry>,
+    template_string: &str,
+    data: &T,
+    writer: W,
+) -> Result<(), TemplateRenderError>
+where
+    T: Serialize,
+    W: AsyncWrite + Unpin,
+{
+    let tpl = Template::compile_with_options(template_string, &registry.template_options())?;
+    let ctx = Context::wraps(data)?;
+    let render = move |output: &mut ChunkOutput| {
+        let mut render_context = RenderContext::new(None);
+        tpl.render(&registry, &ctx, &mut render_context, output)
+    };
+    render_to_chunks(render, writer).await?;
+    Ok(())
+}
+
+async fn render_name_to_chunks<T, W>(
+    registry: Arc<Registry>,
+    name: &str,
+    data: &T,
+    writer: W,
+) -> Result<(), RenderError>
+where
+    T: Serialize,
+    W: AsyncWrite + Unpin,
+{
+    let name = name.to_owned();
+    let ctx = Context::wraps(data)?;
+    let render = move |output: &mut ChunkOutput| {
+        registry.render_context_to_output(&name, &ctx, None, output)
+    };
+    render_to_chunks(render, writer).await
+}
+
+impl Registry {
+    /// Render a registered template and write it to a `futures::io::AsyncWrite`
+    ///
+    /// The template is rendered on a new thread, with a clone of the
+    /// registry sharing its templates and helpers. The output is written to
+    /// `writer` in chunks while rendering, and `writer` is flushed at the
+    /// end.
+    pub async fn render_to_async_write<T, W>(
+        &self,
+        name: &str,
+        data: &T,
+        writer: W,
+    ) -> Result<(), RenderError>
+    where
+        T: Serialize,
+        W: AsyncWrite + Unpin,
+    {
+        render_name_to_chunks(Arc::new(self.clone()), name, data, writer).await
+    }
+
+    /// render a template string to a `futures::io::AsyncWrite` using current
+    /// registry without register it
+    pub async fn render_template_to_async_write<T, W>(
+        &self,
+        template_string: &str,
+        data: &T,
+        writer: W,
+    ) -> Result<(), TemplateRenderError>
+    where
+        T: Serialize,
+        W: AsyncWrite + Unpin,
+    {
+        render_template_to_chunks(Arc::new(self.clone()), template_string, data, writer).await
+    }
+}
+
+impl SharedRegistry {
+    /// Render a registered template to a `futures::io::AsyncWrite`,
+    /// see `Registry::render_to_async_write`
+    pub async fn render_to_async_write<T, W>(
+        &self,
+        name: &str,
+        data: &T,
+        writer: W,
+    ) -> Result<(), RenderError>
+    where
+        T: Serialize,
+        W: AsyncWrite + Unpin,
+    {
+        render_name_to_chunks(self.snapshot(), name, data, writer).await
+    }
+
+    /// Render a template string to a `futures::io::AsyncWrite`,
+    /// see `Registry::render_template_to_async_write`
+    pub async fn render_template_to_async_write<T, W>(
+        &self,
+        template_string: &str,
+        data: &T,
+        writer: W,
+    ) -> Result<(), TemplateRenderError>
+    where
+        T: Serialize,
+        W: AsyncWrite + Unpin,
+    {
+        render_template_to_chunks(self.snapshot(), template_string, data, writer).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Error as IOError;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use futures::executor::block_on;
+    use futures::io::AsyncWrite;
+
+    use super::CHUNK_SIZE;
+    use crate::registry::Registry;
+    use crate::shared::SharedRegistry;
+
+    // records each write, and is pending every other poll
+    #[derive(Default)]
+    struct RecordingWriter {
+        writes: Vec<Vec<u8>>,
+        pending: bool,
+        flushed: bool,
+    }
+
+    impl RecordingWriter {
+        fn output(&self) -> String {
+            String::from_utf8(self.writes.concat()).unwrap()
+        }
+    }
+
+    impl AsyncWrite for RecordingWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<Result<usize, IOError>> {
+            self.pending = !self.pending;
+            if self.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            self.writes.push(buf.to_vec());
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), IOError>> {
+            self.flushed = true;
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), IOError>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn test_render_to_async_write() {
+        let mut r = Registry::new();
+        r.register_template_string("index", "{{#each this}}<li>{{this}}</li>{{/each}}")
+            .unwrap();
+        let data: Vec<usize> = (0..5000).collect();
+
+        let mut writer = RecordingWriter::default();
+        block_on(r.render_to_async_write("index", &data, &mut writer)).unwrap();
+        assert_eq!(writer.output(), r.render("index", &data).unwrap());
+        assert!(writer.flushed);
+    }
+
+    #[test]
+    fn test_render_to_async_write_in_chunks() {
+        let mut r = Registry::new();
+        r.register_template_string("index", "{{#each this}}<li>{{this}}</li>{{/each}}")
+            .unwrap();
+        let data: Vec<usize> = (0..5000).collect();
+        let expected = r.render("index", &data).unwrap();
+
+        let mut writer = RecordingWriter::default();
+        block_on(r.render_to_async_write("index", &data, &mut writer)).unwrap();
+        // written while rendering, in full chunks but the last one
+        assert_eq!(
+            writer.writes.len(),
+            (expected.len() + CHUNK_SIZE - 1) / CHUNK_SIZE
+        );
+        let (last, chunks) = writer.writes.split_last().unwrap();
+        assert!(chunks.iter().all(|w| w.len() == CHUNK_SIZE));
+        assert!(!last.is_empty() && last.len() <= CHUNK_SIZE);
+        assert_eq!(writer.output(), expected);
+    }
+
+    #[test]
+    fn test_render_to_async_write_small() {
+        let r = Registry::new();
+        let mut writer = RecordingWriter::default();
+        block_on(r.render_template_to_async_write("<h1>{{this}}</h1>", &"hi", &mut writer))
+            .unwrap();
+        assert_eq!(writer.writes, vec![b"<h1>hi</h1>".to_vec()]);
+        assert!(writer.flushed);
+    }
+
+    #[test]
+    fn test_render_to_async_write_errors() {
+        let mut r = Registry::new();
+        r.register_template_string("t0", "{{#each this}}<li>{{this}}</li>{{/each}}{{missing}}")
+            .unwrap();
+        r.set_strict_mode(true);
+        let data: Vec<usize> = (0..5000).collect();
+
+        let mut writer = RecordingWriter::default();
+        assert!(block_on(r.render_to_async_write("t0", &data, &mut writer)).is_err());
+        // chunks rendered before the error are written, not the rest
+        assert!(!writer.writes.is_empty());
+        assert!(writer.writes.iter().all(|w| w.len() == CHUNK_SIZE));
+        assert!(!writer.flushed);
+
+        let mut writer = RecordingWriter::default();
+        assert!(block_on(r.render_to_async_write("missing", &data, &mut writer)).is_err());
+        assert!(block_on(r.render_template_to_async_write("{{#if}}", &data, &mut writer)).is_err());
+        assert!(writer.writes.is_empty());
+    }
+
+    #[test]
+    fn test_shared_render_to_async_write() {
+        let hbs = SharedRegistry::new();
+        hbs.register_template_string("t0", "<p>{{this}}</p>")
+            .unwrap();
+
+        let mut writer = RecordingWriter::default();
+        block_on(hbs.render_to_async_write("t0", &"hi", &mut writer)).unwrap();
+        assert_eq!(writer.output(), "<p>hi</p>");
+
+        let mut writer = RecordingWriter::default();
+        block_on(hbs.render_template_to_async_write("{{> t0}}!", &"hi", &mut writer)).unwrap();
+        assert_eq!(writer.output(), "<p>hi</p>!");
+    }
+}
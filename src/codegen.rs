@@ -0,0 +1,543 @@
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::{Error as IOError, Write};
+use std::path::Path;
+
+use serde_json::value::Value as Json;
+
+use crate::error::{TemplateError, TemplateFileError};
+use crate::registry::Registry;
+use crate::template::{
+    BlockParam, DirectiveTemplate, HelperTemplate, Parameter, Template, TemplateElement,
+    TemplateMapping,
+};
+
+/// Turn templates into Rust code at build time
+///
+/// Templates added to the precompiler are parsed right away, so a syntax
+/// error is reported by the build script and fails `cargo build`. The
+/// generated code rebuilds the compiled templates without running the
+/// parser, and registers them with `register_templates`.
+///
+/// In `build.rs`:
+///
+#[cfg_attr(not(feature = "no_dir_source"), doc = "```no_run")]
+#[cfg_attr(feature = "no_dir_source", doc = "```ignore")]
+/// use std::env;
+/// use std::path::Path;
+/// use handlebars::Precompiler;
+///
+/// println!("cargo:rerun-if-changed=templates");
+///
+/// let mut precompiler = Precompiler::new();
+/// precompiler
+///     .register_templates_directory(".hbs", "templates")
+///     .unwrap();
+/// precompiler
+///     .write_to(Path::new(&env::var("OUT_DIR").unwrap()).join("templates.rs"))
+///     .unwrap();
+/// ```
+///
+/// And in the crate, include the generated code in a module of its own:
+///
+/// ```ignore
+/// mod templates {
+///     include!(concat!(env!("OUT_DIR"), "/templates.rs"));
+/// }
+///
+/// let mut handlebars = Handlebars::new();
+/// templates::register_templates(&mut handlebars);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Precompiler {
+    registry: Registry,
+}
+
+impl Precompiler {
+    pub fn new() -> Precompiler {
+        Precompiler {
+            registry: Registry::new(),
+        }
+    }
+
+    /// Keep line and column of template elements for error reporting,
+    /// enabled by default
+    pub fn source_map_enabled(&mut self, enable: bool) {
+        self.registry.source_map_enabled(enable);
+    }
+
+    /// Open and close delimiters of templates added after the call, see
+    /// `Registry::set_delimiters`
+    pub fn set_delimiters(&mut self, open: &str, close: &str) {
+        self.registry.set_delimiters(open, close);
+    }
+
+    /// Parse templates added after the call following the Mustache spec,
+    /// see `Registry::set_mustache_mode`
+    ///
+    /// The registry rendering the generated templates needs Mustache mode
+    /// as well.
+    pub fn set_mustache_mode(&mut self, enable: bool) {
+        self.registry.set_mustache_mode(enable);
+    }
+
+    /// Keep the lines of standalone tags in templates added after the call,
+    /// see `Registry::set_ignore_standalone`
+    pub fn set_ignore_standalone(&mut self, enable: bool) {
+        self.registry.set_ignore_standalone(enable);
+    }
+
+    /// Add a template string, see `Registry::register_template_string`
+    pub fn register_template_string<S>(
+        &mut self,
+        name: &str,
+        tpl_str: S,
+    ) -> Result<(), TemplateError>
+    where
+        S: AsRef<str>,
+    {
+        self.registry.register_template_string(name, tpl_str)
+    }
+
+    /// Add a template from a path, see `Registry::register_template_file`
+    pub fn register_template_file<P>(
+        &mut self,
+        name: &str,
+        tpl_path: P,
+    ) -> Result<(), TemplateFileError>
+    where
+        P: AsRef<Path>,
+    {
+        self.registry.register_template_file(name, tpl_path)
+    }
+
+    /// Add all templates in a directory, see
+    /// `Registry::register_templates_directory`
+    #[cfg(not(feature = "no_dir_source"))]
+    pub fn register_templates_directory<P>(
+        &mut self,
+        tpl_extension: &'static str,
+        dir_path: P,
+    ) -> Result<(), TemplateFileError>
+    where
+        P: AsRef<Path>,
+    {
+        self.registry
+            .register_templates_directory(tpl_extension, dir_path)
+    }
+
+    /// Generate the Rust code for all added templates
+    pub fn generate(&self) -> String {
+        let mut templates: Vec<(&String, &Template)> = self
+            .registry
+            .get_templates()
+            .iter()
+            .map(|(n, t)| (n, t.as_ref()))
+            .collect();
+        templates.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut code = String::new();
+        code.push_str("// Generated by handlebars::Precompiler, do not edit.\n\n");
+        code.push_str("#[allow(unused_imports)]\n");
+        code.push_str(
+            "use ::handlebars::template::{BlockParam, DirectiveTemplate, HelperTemplate, \
+             Parameter, Subexpression, TemplateElement, TemplateMapping};\n",
+        );
+        code.push_str("#[allow(unused_imports)]\n");
+        code.push_str("use ::handlebars::{Handlebars, JsonValue, Template};\n\n");
+
+        code.push_str("/// Register all precompiled templates\n");
+        code.push_str("pub fn register_templates(registry: &mut Handlebars) {\n");
+        for (i, (name, _)) in templates.iter().enumerate() {
+            writeln!(
+                code,
+                "    registry.register_template({:?}, template_{}());",
+                name, i
+            )
+            .unwrap();
+        }
+        code.push_str("}\n");
+
+        for (i, (_, t)) in templates.iter().enumerate() {
+            write!(code, "\nfn template_{}() -> Template {{\n    ", i).unwrap();
+            write_template(&mut code, t, 1);
+            code.push_str("\n}\n");
+        }
+        code
+    }
+
+    /// Write the generated code to a file
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> Result<(), IOError> {
+        let mut file = File::create(path)?;
+        file.write_all(self.generate().as_bytes())
+    }
+}
+
+fn newline(code: &mut String, depth: usize) {
+    code.push('\n');
+    for _ in 0..depth {
+        code.push_str("    ");
+    }
+}
+
+fn write_string(code: &mut String, s: &str) {
+    write!(code, "{:?}.to_owned()", s).unwrap();
+}
+
+fn write_template(code: &mut String, t: &Template, depth: usize) {
+    code.push_str("Template::from_elements(");
+    newline(code, depth + 1);
+    code.push_str("vec![");
+    for e in &t.elements {
+        newline(code, depth + 2);
+        write_element(code, e, depth + 2);
+        code.push(',');
+    }
+    if !t.elements.is_empty() {
+        newline(code, depth + 1);
+    }
+    code.push_str("],");
+
+    newline(code, depth + 1);
+    match t.mapping {
+        Some(ref mapping) => {
+            code.push_str("Some(vec![");
+            for (i, m) in mapping.iter().enumerate() {
+                if i > 0 {
+                    code.push_str(", ");
+                }
+                write!(code, "TemplateMapping({}, {})", m.0, m.1).unwrap();
+            }
+            code.push_str("])");
+        }
+        None => code.push_str("None"),
+    }
+    code.push(',');
+    newline(code, depth);
+    code.push(')');
+}
+
+fn write_element(code: &mut String, e: &TemplateElement, depth: usize) {
+    match *e {
+        TemplateElement::RawString(ref s) => {
+            code.push_str("TemplateElement::RawString(");
+            write_string(code, s);
+            code.push(')');
+        }
+        TemplateElement::Comment(ref s) => {
+            code.push_str("TemplateElement::Comment(");
+            write_string(code, s);
+            code.push(')');
+        }
+        TemplateElement::HTMLExpression(ref p) => {
+            code.push_str("TemplateElement::HTMLExpression(");
+            write_param(code, p, depth);
+            code.push(')');
+        }
+        TemplateElement::Expression(ref ht) => {
+            code.push_str("TemplateElement::Expression(Box::new(");
+            write_helper(code, ht, depth);
+            code.push_str("))");
+        }
+        TemplateElement::HelperBlock(ref ht) => {
+            code.push_str("TemplateElement::HelperBlock(Box::new(");
+            write_helper(code, ht, depth);
+            code.push_str("))");
+        }
+        TemplateElement::DirectiveExpression(ref dt) => {
+            code.push_str("TemplateElement::DirectiveExpression(");
+            write_directive(code, dt, depth);
+            code.push(')');
+        }
+        TemplateElement::DirectiveBlock(ref dt) => {
+            code.push_str("TemplateElement::DirectiveBlock(");
+            write_directive(code, dt, depth);
+            code.push(')');
+        }
+        TemplateElement::PartialExpression(ref dt) => {
+            code.push_str("TemplateElement::PartialExpression(");
+            write_directive(code, dt, depth);
+            code.push(')');
+        }
+        TemplateElement::PartialBlock(ref dt) => {
+            code.push_str("TemplateElement::PartialBlock(");
+            write_directive(code, dt, depth);
+            code.push(')');
+        }
+    }
+}
+
+fn write_optional_template(code: &mut String, t: &Option<Template>, depth: usize) {
+    match *t {
+        Some(ref t) => {
+            code.push_str("Some(");
+            write_template(code, t, depth);
+            code.push(')');
+        }
+        None => code.push_str("None"),
+    }
+}
+
+fn write_params<'a, I>(code: &mut String, params: I, depth: usize)
+where
+    I: Iterator<Item = &'a Parameter>,
+{
+    code.push_str("vec![");
+    for (i, p) in params.enumerate() {
+        if i > 0 {
+            code.push_str(", ");
+        }
+        write_param(code, p, depth);
+    }
+    code.push(']');
+}
+
+fn write_hash<'a, I>(code: &mut String, hash: I, depth: usize)
+where
+    I: Iterator<Item = (&'a String, &'a Parameter)>,
+{
+    // sorted to keep the generated code stable between builds
+    let mut hash: Vec<_> = hash.collect();
+    hash.sort_by(|a, b| a.0.cmp(b.0));
+
+    code.push_str("vec![");
+    for (i, (k, v)) in hash.into_iter().enumerate() {
+        if i > 0 {
+            code.push_str(", ");
+        }
+        code.push('(');
+        write_string(code, k);
+        code.push_str(", ");
+        write_param(code, v, depth);
+        code.push(')');
+    }
+    code.push_str("].into_iter().collect()");
+}
+
+fn write_helper(code: &mut String, ht: &HelperTemplate, depth: usize) {
+    if ht.block {
+        code.push_str("HelperTemplate::new_block(");
+    } else {
+        code.push_str("HelperTemplate::new(");
+    }
+    newline(code, depth + 1);
+    write_param(code, &ht.name, depth + 1);
+    code.push(',');
+
+    newline(code, depth + 1);
+    write_params(code, ht.params.iter(), depth + 1);
+    code.push(',');
+
+    newline(code, depth + 1);
+    write_hash(code, ht.hash.iter(), depth + 1);
+    code.push(',');
+
+    if ht.block {
+        newline(code, depth + 1);
+        match ht.block_param {
+            Some(BlockParam::Single(ref p)) => {
+                code.push_str("Some(BlockParam::Single(");
+                write_param(code, p, depth + 1);
+                code.push_str("))");
+            }
+            Some(BlockParam::Pair((ref p1, ref p2))) => {
+                code.push_str("Some(BlockParam::Pair((");
+                write_param(code, p1, depth + 1);
+                code.push_str(", ");
+                write_param(code, p2, depth + 1);
+                code.push_str(")))");
+            }
+            None => code.push_str("None"),
+        }
+        code.push(',');
+
+        newline(code, depth + 1);
+        write_optional_template(code, &ht.template, depth + 1);
+        code.push(',');
+
+        newline(code, depth + 1);
+        write_optional_template(code, &ht.inverse, depth + 1);
+        code.push(',');
+    }
+    newline(code, depth);
+    code.push(')');
+}
+
+fn write_directive(code: &mut String, dt: &DirectiveTemplate, depth: usize) {
+    code.push_str("DirectiveTemplate::new(");
+    newline(code, depth + 1);
+    write_param(code, &dt.name, depth + 1);
+    code.push(',');
+
+    newline(code, depth + 1);
+    write_params(code, dt.params.iter(), depth + 1);
+    code.push(',');
+
+    newline(code, depth + 1);
+    write_hash(code, dt.hash.iter(), depth + 1);
+    code.push(',');
+
+    newline(code, depth + 1);
+    write_optional_template(code, &dt.template, depth + 1);
+    code.push(',');
+    newline(code, depth);
+    code.push(')');
+
+    if let Some(ref indent) = dt.indent {
+        code.push_str(".with_indent(");
+        write_string(code, indent);
+        code.push(')');
+    }
+}
+
+fn write_param(code: &mut String, p: &Parameter, depth: usize) {
+    match *p {
+        Parameter::Name(ref n) => {
+            code.push_str("Parameter::Name(");
+            write_string(code, n);
+            code.push(')');
+        }
+        Parameter::Literal(ref j) => {
+            code.push_str("Parameter::Literal(");
+            write_json(code, j);
+            code.push(')');
+        }
+        Parameter::Subexpression(ref s) => {
+            code.push_str("Parameter::Subexpression(Subexpression::from_helper(");
+            match *s.as_element() {
+                TemplateElement::Expression(ref ht) => write_helper(code, ht, depth),
+                _ => unreachable!("a subexpression is a helper expression"),
+            }
+            code.push_str(", ");
+            match s.mapping() {
+                Some(&TemplateMapping(line, col)) => {
+                    write!(code, "Some(TemplateMapping({}, {}))", line, col).unwrap()
+                }
+                None => code.push_str("None"),
+            }
+            code.push_str("))");
+        }
+    }
+}
+
+fn write_json(code: &mut String, j: &Json) {
+    match *j {
+        Json::Null => code.push_str("JsonValue::Null"),
+        Json::Bool(b) => write!(code, "JsonValue::Bool({})", b).unwrap(),
+        Json::Number(ref n) => {
+            if let Some(u) = n.as_u64() {
+                write!(code, "JsonValue::from({}u64)", u).unwrap();
+            } else if let Some(i) = n.as_i64() {
+                write!(code, "JsonValue::from({}i64)", i).unwrap();
+            } else {
+                write!(code, "JsonValue::from({:?}f64)", n.as_f64().unwrap()).unwrap();
+            }
+        }
+        Json::String(ref s) => {
+            code.push_str("JsonValue::String(");
+            write_string(code, s);
+            code.push(')');
+        }
+        Json::Array(ref a) => {
+            code.push_str("JsonValue::Array(vec![");
+            for (i, v) in a.iter().enumerate() {
+                if i > 0 {
+                    code.push_str(", ");
+                }
+                write_json(code, v);
+            }
+            code.push_str("])");
+        }
+        Json::Object(ref m) => {
+            code.push_str("JsonValue::Object(vec![");
+            for (i, (k, v)) in m.iter().enumerate() {
+                if i > 0 {
+                    code.push_str(", ");
+                }
+                code.push('(');
+                write_string(code, k);
+                code.push_str(", ");
+                write_json(code, v);
+                code.push(')');
+            }
+            code.push_str("].into_iter().collect())");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::codegen::Precompiler;
+
+    #[test]
+    fn test_generate() {
+        let mut precompiler = Precompiler::new();
+        precompiler
+            .register_template_string("b", "Hello {{name}}!")
+            .unwrap();
+        precompiler
+            .register_template_string("a", "{{#if (eq x 1.5)}}\"yes\"{{/if}}")
+            .unwrap();
+
+        let code = precompiler.generate();
+        assert!(code.contains("pub fn register_templates(registry: &mut Handlebars) {"));
+        assert!(code
+            .find("register_template(\"a\", template_0())")
+            .is_some());
+        assert!(code
+            .find("register_template(\"b\", template_1())")
+            .is_some());
+        assert!(code.contains("TemplateElement::RawString(\"\\\"yes\\\"\".to_owned())"));
+        assert!(code.contains("Parameter::Literal(JsonValue::from(1.5f64))"));
+        assert!(code.contains("Parameter::Name(\"name\".to_owned())"));
+        assert!(code.contains("TemplateMapping(1, 7)"));
+        assert!(code.contains("Subexpression::from_helper("));
+        assert!(code.contains(", Some(TemplateMapping(1, 7))))"));
+        assert!(!code.contains(" {\n        name: "));
+
+        precompiler.source_map_enabled(false);
+        precompiler
+            .register_template_string("b", "Hello {{name}}!")
+            .unwrap();
+        precompiler
+            .register_template_string("a", "{{#if (eq x 1.5)}}\"yes\"{{/if}}")
+            .unwrap();
+        assert!(!precompiler.generate().contains("TemplateMapping("));
+    }
+
+    #[test]
+    fn test_template_options() {
+        let mut precompiler = Precompiler::new();
+        precompiler
+            .register_template_string("a", "a\n  {{> p}}\n")
+            .unwrap();
+        assert!(precompiler
+            .generate()
+            .contains(".with_indent(\"  \".to_owned())"));
+
+        precompiler.set_ignore_standalone(true);
+        precompiler.set_delimiters("<%", "%>");
+        precompiler
+            .register_template_string("a", "a\n  <%> p%>\n")
+            .unwrap();
+        let code = precompiler.generate();
+        assert!(!code.contains(".with_indent("));
+        assert!(code.contains("TemplateElement::PartialExpression("));
+
+        precompiler.set_mustache_mode(true);
+        precompiler
+            .register_template_string("a", "<%=| |=%>|name|")
+            .unwrap();
+        assert!(precompiler
+            .generate()
+            .contains("Parameter::Name(\"name\".to_owned())"));
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let mut precompiler = Precompiler::new();
+        assert!(precompiler
+            .register_template_string("a", "{{#if}}")
+            .is_err());
+    }
+}
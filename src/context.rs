@@ -176,11 +176,45 @@ fn parse_json_visitor<'a, 'b: 'a>(
     }
 }
 
-fn get_data<'a>(d: Option<&'a Json>, p: &str) -> Result<Option<&'a Json>, RenderError> {
-    if p == "this" {
-        return Ok(d);
+/// Returns the keys of `relative_path` from the root of the data
+pub(crate) fn resolve_path(
+    base_path: &str,
+    path_context: &VecDeque<String>,
+    relative_path: &str,
+    block_params: &VecDeque<BlockParams>,
+) -> Result<Vec<String>, RenderError> {
+    let (paths, _) = parse_json_visitor(base_path, path_context, relative_path, block_params)?;
+    Ok(paths.iter().map(|p| (*p).to_owned()).collect())
+}
+
+/// The path of `keys` from the root of the data, escaping each key
+pub(crate) fn keys_path(keys: &[String]) -> String {
+    Some(".".to_owned())
+        .into_iter()
+        .chain(keys.iter().map(|k| format!("[{}]", k)))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Navigates `relative_path` in a value that is the context of a block
+/// without being in the data, like the result of a subexpression
+pub(crate) fn navigate_value<'reg, 'rc>(
+    value: &Json,
+    relative_path: &str,
+) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+    let mut path_stack = VecDeque::new();
+    parse_json_visitor_inner(&mut path_stack, relative_path)?;
+
+    let mut data = Some(value);
+    for p in path_stack.iter() {
+        data = get_data(data, p)?;
     }
+    Ok(data
+        .map(|v| ScopedJson::Derived(v.clone()))
+        .unwrap_or_else(|| ScopedJson::Missing))
+}
 
+fn get_data<'a>(d: Option<&'a Json>, p: &str) -> Result<Option<&'a Json>, RenderError> {
     let result = match d {
         Some(&Json::Array(ref l)) => p
             .parse::<usize>()
@@ -0,0 +1,106 @@
+use crate::context::Context;
+use crate::directives::{DirectiveDef, DirectiveResult};
+use crate::error::RenderError;
+use crate::helpers::helper_number::find_locale;
+use crate::registry::Registry;
+use crate::render::{Directive, RenderContext};
+use crate::value::JsonRender;
+
+/// `{{*locale "de-DE"}}` sets the locale of the formatting helpers for the
+/// rest of the render
+#[derive(Clone, Copy)]
+pub struct LocaleDirective;
+
+impl DirectiveDef for LocaleDirective {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        d: &Directive<'reg, 'rc>,
+        _: &'reg Registry,
+        _: &'rc Context,
+        rc: &mut RenderContext<'reg>,
+    ) -> DirectiveResult {
+        let locale = d
+            .param(0)
+            .ok_or_else(|| RenderError::new("Param required for directive \"locale\""))?
+            .value()
+            .render();
+        if find_locale(&locale).is_none() {
+            return Err(RenderError::new(format!("Unknown locale \"{}\"", locale)));
+        }
+
+        rc.set_locale(locale);
+        Ok(())
+    }
+}
+
+pub static LOCALE_DIRECTIVE: LocaleDirective = LocaleDirective;
+
+#[cfg(test)]
+mod test {
+    use crate::registry::Registry;
+
+    #[test]
+    fn test_locale() {
+        let mut handlebars = Registry::new();
+        handlebars.register_number_helpers();
+        handlebars
+            .register_template_string(
+                "t0",
+                "{{format_number n}} {{*locale lang}}{{#each list}}{{format_number this}} {{/each}}\
+                 {{format_number n locale=\"en-US\"}}",
+            )
+            .unwrap();
+        handlebars
+            .register_partial("p", "{{format_currency n}}")
+            .unwrap();
+        handlebars
+            .register_template_string("t1", "{{*locale \"fr-FR\"}}{{> p}}")
+            .unwrap();
+
+        let data = json!({"n": 1234.5, "lang": "de-DE", "list": [0.5, 1000]});
+        assert_eq!(
+            handlebars.render("t0", &data).unwrap(),
+            "1,234.5 0,5 1.000 1,234.5"
+        );
+        assert_eq!(
+            handlebars.render("t1", &data).unwrap(),
+            "1\u{202f}234,50\u{a0}€"
+        );
+        assert!(handlebars
+            .render_template("{{*locale \"xx\"}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown locale \"xx\""));
+    }
+
+    #[test]
+    fn test_render_with_locale() {
+        let mut handlebars = Registry::new();
+        handlebars.register_number_helpers();
+        handlebars
+            .register_template_string(
+                "t0",
+                "{{format_number n}} {{format_number n locale=\"en-US\"}}",
+            )
+            .unwrap();
+        handlebars
+            .register_template_string("t1", "{{*locale \"fr-FR\"}}{{format_number n}}")
+            .unwrap();
+
+        let data = json!({"n": 1234.5});
+        assert_eq!(
+            handlebars.render_with_locale("t0", &data, "de-DE").unwrap(),
+            "1.234,5 1,234.5"
+        );
+        assert_eq!(
+            handlebars.render_with_locale("t1", &data, "de-DE").unwrap(),
+            "1\u{202f}234,5"
+        );
+        assert_eq!(handlebars.render("t0", &data).unwrap(), "1,234.5 1,234.5");
+        assert!(handlebars
+            .render_with_locale("t0", &data, "xx")
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown locale \"xx\""));
+    }
+}
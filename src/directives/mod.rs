@@ -4,6 +4,8 @@ use crate::registry::Registry;
 use crate::render::{Directive, RenderContext};
 
 pub use self::inline::INLINE_DIRECTIVE;
+pub use self::locale::LOCALE_DIRECTIVE;
+pub use self::set::SET_DIRECTIVE;
 
 pub type DirectiveResult = Result<(), RenderError>;
 
@@ -90,6 +92,8 @@ impl<
 }
 
 mod inline;
+mod locale;
+mod set;
 
 #[cfg(test)]
 mod test {
@@ -0,0 +1,199 @@
+use crate::context::Context;
+use crate::directives::{DirectiveDef, DirectiveResult};
+use crate::error::RenderError;
+use crate::helpers::helper_let::hash_block_params;
+use crate::registry::Registry;
+use crate::render::{Directive, RenderContext};
+
+/// `{{*set total=(sum prices)}}` binds names to values, like `#let`, for
+/// the rest of the template or of the enclosing block
+///
+/// Built-in blocks rendering in the scope around them, like `#if`, end the
+/// names set in them. Custom block helpers rendering their block with the
+/// `RenderContext` they are given keep them bound after the block.
+#[derive(Clone, Copy)]
+pub struct SetDirective;
+
+impl DirectiveDef for SetDirective {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        d: &Directive<'reg, 'rc>,
+        _: &'reg Registry,
+        _: &'rc Context,
+        rc: &mut RenderContext<'reg>,
+    ) -> DirectiveResult {
+        if d.hash().is_empty() {
+            return Err(RenderError::new("Hash required for directive \"set\""));
+        }
+
+        let params = hash_block_params(d.hash(), rc)?;
+        rc.push_block_context(params)
+    }
+}
+
+pub static SET_DIRECTIVE: SetDirective = SetDirective;
+
+#[cfg(test)]
+mod test {
+    use crate::context::{BlockParams, Context};
+    use crate::helpers::{HelperDef, HelperResult};
+    use crate::output::Output;
+    use crate::registry::Registry;
+    use crate::render::{Helper, RenderContext, Renderable};
+
+    #[test]
+    fn test_set() {
+        let mut handlebars = Registry::new();
+        handlebars.register_variable_helpers();
+        handlebars.register_math_helpers();
+        handlebars.register_switch_helpers();
+        handlebars
+            .register_partial("p", "{{*set name=\"partial\"}}{{name}}")
+            .unwrap();
+
+        let data = json!({
+            "name": "data",
+            "prices": [1, 2, 3],
+            "posts": [{"author": {"name": "ann"}}, {"author": {"name": "bob"}}]
+        });
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{name}} {{*set name=\"set\" total=(sum prices)}}{{name}} {{total}}",
+                    &data
+                )
+                .unwrap(),
+            "data set 6"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{*set n=1}}{{n}}{{*set n=(add n 1)}}{{n}}", &data)
+                .unwrap(),
+            "12"
+        );
+        // names set in a block are bound until its end
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each posts}}{{*set a=author}}{{a.name}}{{/each}}[{{a.name}}]",
+                    &data
+                )
+                .unwrap(),
+            "annbob[]"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{*set first=posts.[0]}}{{#each posts}}{{author.name}}/{{first.author.name}} {{/each}}",
+                    &data
+                )
+                .unwrap(),
+            "ann/ann bob/ann "
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#with posts.[1]}}{{*set x=author.name}}{{x}}{{/with}}{{x}}",
+                    &data
+                )
+                .unwrap(),
+            "bob"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#if true}}{{*set x=1}}{{x}}{{/if}}[{{x}}]", &data)
+                .unwrap(),
+            "1[]"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#unless false}}{{*set x=1}}{{/unless}}[{{x}}]", &data)
+                .unwrap(),
+            "[]"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#switch 1}}{{#case 1}}{{*set x=1}}{{/case}}[{{x}}]{{/switch}}",
+                    &data
+                )
+                .unwrap(),
+            "[]"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each nothing}}{{else}}{{*set x=1}}{{/each}}[{{x}}]",
+                    &data
+                )
+                .unwrap(),
+            "[]"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{> p}} {{name}}", &data)
+                .unwrap(),
+            "partial data"
+        );
+
+        assert!(handlebars
+            .render_template("{{*set}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("Hash required for directive \"set\""));
+    }
+
+    // binds `name` in the rest of the enclosing block, like `*set`
+    #[derive(Clone, Copy)]
+    struct BindHelper;
+
+    impl HelperDef for BindHelper {
+        fn call<'reg: 'rc, 'rc>(
+            &self,
+            h: &Helper<'reg, 'rc>,
+            r: &'reg Registry,
+            ctx: &'rc Context,
+            rc: &mut RenderContext<'reg>,
+            out: &mut dyn Output,
+        ) -> HelperResult {
+            let mut params = BlockParams::new();
+            params.add_value("name", json!("bound"))?;
+            rc.push_block_context(params)?;
+            match h.template() {
+                Some(t) => t.render(r, ctx, rc, out),
+                None => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_in_custom_block() {
+        let mut handlebars = Registry::new();
+        handlebars.register_variable_helpers();
+        handlebars.register_helper("bind", Box::new(BindHelper));
+
+        let data = json!({"name": "data"});
+        // the block context of a custom block helper is left as it is
+        assert_eq!(
+            handlebars
+                .render_template("{{#bind}}{{name}}{{/bind}} {{name}}", &data)
+                .unwrap(),
+            "bound bound"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#bind}}{{*set x=1}}{{/bind}}[{{x}}]", &data)
+                .unwrap(),
+            "[1]"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#with this}}{{#bind}}{{/bind}}{{name}}{{/with}} {{name}}",
+                    &data
+                )
+                .unwrap(),
+            "bound data"
+        );
+    }
+}
@@ -65,6 +65,14 @@ impl From<FromUtf8Error> for RenderError {
     }
 }
 
+impl From<TemplateFileError> for RenderError {
+    fn from(e: TemplateFileError) -> RenderError {
+        let mut err = RenderError::new(e.to_string());
+        err.cause = Some(Box::new(e));
+        err
+    }
+}
+
 impl RenderError {
     pub fn new<T: AsRef<str>>(desc: T) -> RenderError {
         RenderError {
@@ -117,9 +125,13 @@ quick_error! {
             display("invalid parameter {:?}", param)
             description("invalid parameter")
         }
-        NestedSubexpression {
-            display("nested subexpression is not supported")
-            description("nested subexpression is not supported")
+        InvalidPath (path: String) {
+            display("invalid path {:?}", path)
+            description("invalid path")
+        }
+        InvalidDelimiters (open: String, close: String) {
+            display("invalid delimiters {:?} and {:?}", open, close)
+            description("invalid delimiters")
         }
     }
 }
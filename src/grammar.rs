@@ -193,7 +193,13 @@ fn test_identifier_with_dash() {
 
 #[test]
 fn test_html_expression() {
-    let s = vec!["{{{html}}}", "{{{(html)}}}", "{{{(html)}}}"];
+    let s = vec![
+        "{{{html}}}",
+        "{{{(html)}}}",
+        "{{{(html)}}}",
+        "{{&html}}",
+        "{{~& html~}}",
+    ];
     for i in s.iter() {
         assert_rule!(Rule::html_expression, i);
     }
@@ -240,12 +246,31 @@ fn test_helper_block() {
         "{{#if}}hello{{else~}}world{{/if}}",
         "{{#if}}hello{{~^~}}world{{/if}}",
         "{{#if}}{{/if}}",
+        "{{^list}}empty{{/list}}",
+        "{{#person.name}}{{.}}{{/person.name}}",
     ];
     for i in s.iter() {
         assert_rule!(Rule::helper_block, i);
     }
 }
 
+#[test]
+fn test_set_delimiters() {
+    let s = vec![
+        "{{=<% %>=}}<%name%><%={{ }}=%>{{name}}",
+        "{{= | | =}}|#list||.||/list|",
+        "{{=<% %>=}}<%! comment %><%{name}%><%&name%>",
+    ];
+    for i in s.iter() {
+        assert_rule!(Rule::handlebars, i);
+    }
+
+    let s = vec!["{{=<%%>=}}", "{{=<% %>}}", "{{=<% %>=}}<%name}}"];
+    for i in s.iter() {
+        assert_not_rule!(Rule::handlebars, i);
+    }
+}
+
 #[test]
 fn test_raw_block() {
     let s = vec![
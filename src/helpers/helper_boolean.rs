@@ -1,9 +1,23 @@
 //! Helpers for boolean operations
 
+use serde_json::value::Value as Json;
+
 use crate::value::JsonTruthy;
 
-handlebars_helper!(eq: |x: i64, y: i64| x == y);
-handlebars_helper!(ne: |x: i64, y: i64| x != y);
+/// Numbers are equal by value, so `1` equals `1.0`, other values when their
+/// JSON is equal
+pub(crate) fn json_eq(a: &Json, b: &Json) -> bool {
+    match (a, b) {
+        // integers compare exactly, as large ones lose precision in a float
+        (&Json::Number(ref x), &Json::Number(ref y)) if x.is_f64() || y.is_f64() => {
+            x.as_f64() == y.as_f64()
+        }
+        _ => a == b,
+    }
+}
+
+handlebars_helper!(eq: |x: Json, y: Json| json_eq(x, y));
+handlebars_helper!(ne: |x: Json, y: Json| !json_eq(x, y));
 handlebars_helper!(gt: |x: i64, y: i64| x > y);
 handlebars_helper!(gte: |x: i64, y: i64| x >= y);
 handlebars_helper!(lt: |x: i64, y: i64| x < y);
@@ -40,6 +54,10 @@ mod test_conditions {
         test_condition("(eq 5 6)", false);
         test_condition("(ne 5 6)", true);
         test_condition("(ne 5 5)", false);
+        test_condition("(eq 5 5.0)", true);
+        test_condition("(eq \"a\" \"a\")", true);
+        test_condition("(ne \"a\" \"b\")", true);
+        test_condition("(eq null false)", false);
     }
 
     #[test]
@@ -0,0 +1,601 @@
+//! Helpers for arrays and objects
+//!
+//! They return new values, so they are mostly used as subexpressions like
+//! `{{#each (sort_by posts "date")}}`. A missing collection is empty.
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use serde_json::value::{Map, Value as Json};
+
+use crate::context::Context;
+use crate::error::RenderError;
+use crate::helpers::helper_boolean::json_eq;
+use crate::helpers::HelperDef;
+use crate::registry::Registry;
+use crate::render::{Helper, RenderContext};
+use crate::value::{JsonRender, JsonTruthy, ScopedJson};
+
+fn param<'a>(h: &'a Helper, idx: usize) -> Result<&'a Json, RenderError> {
+    h.param(idx)
+        .map(|p| p.value())
+        .ok_or_else(|| RenderError::new(format!("Insufficient params for helper \"{}\"", h.name())))
+}
+
+fn not_a(h: &Helper, idx: usize, kind: &str, value: &Json) -> RenderError {
+    RenderError::new(format!(
+        "Param {} of helper \"{}\" is not {}: {}",
+        idx,
+        h.name(),
+        kind,
+        value
+    ))
+}
+
+fn array<'a>(h: &'a Helper, idx: usize) -> Result<&'a [Json], RenderError> {
+    match *param(h, idx)? {
+        Json::Array(ref items) => Ok(items),
+        Json::Null => Ok(&[]),
+        ref value => Err(not_a(h, idx, "an array", value)),
+    }
+}
+
+fn integer(h: &Helper, idx: usize) -> Result<i64, RenderError> {
+    let value = param(h, idx)?;
+    value
+        .as_i64()
+        .ok_or_else(|| not_a(h, idx, "an integer", value))
+}
+
+/// The field of an item, by a dotted path like `author.name`
+fn field<'a>(item: &'a Json, path: &str) -> Option<&'a Json> {
+    path.split('.').try_fold(item, |value, key| match *value {
+        Json::Object(ref map) => map.get(key),
+        Json::Array(ref items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    })
+}
+
+fn field_name(h: &Helper, idx: usize) -> Result<String, RenderError> {
+    param(h, idx).map(|f| f.render())
+}
+
+/// Numbers, then strings, then booleans, then anything else, and missing
+/// values last
+fn compare(a: Option<&Json>, b: Option<&Json>) -> Ordering {
+    fn rank(value: Option<&Json>) -> u8 {
+        match value {
+            Some(&Json::Number(_)) => 0,
+            Some(&Json::String(_)) => 1,
+            Some(&Json::Bool(_)) => 2,
+            None => 4,
+            Some(_) => 3,
+        }
+    }
+
+    let a = a.filter(|v| !v.is_null());
+    let b = b.filter(|v| !v.is_null());
+    if let (Some(x), Some(y)) = (a.and_then(Json::as_f64), b.and_then(Json::as_f64)) {
+        return x.partial_cmp(&y).unwrap_or(Ordering::Equal);
+    }
+    if let (Some(x), Some(y)) = (a.and_then(Json::as_str), b.and_then(Json::as_str)) {
+        return x.cmp(y);
+    }
+    if let (Some(x), Some(y)) = (a.and_then(Json::as_bool), b.and_then(Json::as_bool)) {
+        return x.cmp(&y);
+    }
+    rank(a).cmp(&rank(b))
+}
+
+fn len(h: &Helper) -> Result<Json, RenderError> {
+    let len = match *param(h, 0)? {
+        Json::Array(ref items) => items.len(),
+        Json::Object(ref map) => map.len(),
+        Json::String(ref s) => s.chars().count(),
+        Json::Null => 0,
+        ref value => return Err(not_a(h, 0, "a collection", value)),
+    };
+    Ok(Json::from(len))
+}
+
+/// `{{first list}}` is the first item, `{{first list 3}}` the first 3 items
+fn first(h: &Helper) -> Result<Json, RenderError> {
+    let items = array(h, 0)?;
+    match h.param(1) {
+        Some(_) => {
+            let n = integer(h, 1)?.max(0) as usize;
+            Ok(Json::Array(items.iter().take(n).cloned().collect()))
+        }
+        None => Ok(items.first().cloned().unwrap_or(Json::Null)),
+    }
+}
+
+/// `{{last list}}` is the last item, `{{last list 3}}` the last 3 items
+fn last(h: &Helper) -> Result<Json, RenderError> {
+    let items = array(h, 0)?;
+    match h.param(1) {
+        Some(_) => {
+            let n = integer(h, 1)?.max(0) as usize;
+            Ok(Json::Array(items[items.len().saturating_sub(n)..].to_vec()))
+        }
+        None => Ok(items.last().cloned().unwrap_or(Json::Null)),
+    }
+}
+
+/// The range of `{{slice list start end}}` in a collection of `len` items,
+/// where negative indices count from the end
+fn range(h: &Helper, len: usize) -> Result<(usize, usize), RenderError> {
+    let index = |i: i64| {
+        if i < 0 {
+            len.saturating_sub(i.wrapping_neg() as u64 as usize)
+        } else {
+            (i as usize).min(len)
+        }
+    };
+    let start = index(integer(h, 1)?);
+    let end = match h.param(2) {
+        Some(_) => index(integer(h, 2)?),
+        None => len,
+    };
+    Ok((start, end.max(start)))
+}
+
+fn slice(h: &Helper) -> Result<Json, RenderError> {
+    match *param(h, 0)? {
+        Json::String(ref s) => {
+            let chars = s.chars().collect::<Vec<_>>();
+            let (start, end) = range(h, chars.len())?;
+            Ok(Json::String(chars[start..end].iter().collect()))
+        }
+        _ => {
+            let items = array(h, 0)?;
+            let (start, end) = range(h, items.len())?;
+            Ok(Json::Array(items[start..end].to_vec()))
+        }
+    }
+}
+
+fn reverse(h: &Helper) -> Result<Json, RenderError> {
+    match *param(h, 0)? {
+        Json::String(ref s) => Ok(Json::String(s.chars().rev().collect())),
+        _ => Ok(Json::Array(array(h, 0)?.iter().rev().cloned().collect())),
+    }
+}
+
+/// `{{sort_by list "field"}}` sorts items by a field, `{{sort_by list}}` by
+/// themselves. The sort is stable.
+fn sort_by(h: &Helper) -> Result<Json, RenderError> {
+    let mut items = array(h, 0)?.to_vec();
+    match h.param(1) {
+        Some(_) => {
+            let name = field_name(h, 1)?;
+            items.sort_by(|a, b| compare(field(a, &name), field(b, &name)));
+        }
+        None => items.sort_by(|a, b| compare(Some(a), Some(b))),
+    }
+    Ok(Json::Array(items))
+}
+
+/// `{{filter_by list "field" value}}` keeps the items whose field equals the
+/// value, `{{filter_by list "field"}}` the items whose field is truthy
+fn filter_by(h: &Helper) -> Result<Json, RenderError> {
+    let items = array(h, 0)?;
+    let name = field_name(h, 1)?;
+    let expected = h.param(2).map(|p| p.value());
+
+    let filtered = items
+        .iter()
+        .filter(|item| match (field(item, &name), expected) {
+            (Some(value), Some(expected)) => json_eq(value, expected),
+            (Some(value), None) => value.is_truthy(false),
+            (None, Some(expected)) => expected.is_null(),
+            (None, None) => false,
+        })
+        .cloned()
+        .collect();
+    Ok(Json::Array(filtered))
+}
+
+/// `{{group_by list "field"}}` groups items by a field, in the order the
+/// values first appear, as objects with the `key` of the group and its
+/// `items`
+fn group_by(h: &Helper) -> Result<Json, RenderError> {
+    let items = array(h, 0)?;
+    let name = field_name(h, 1)?;
+
+    let mut groups: Vec<(Json, Vec<Json>)> = Vec::new();
+    for item in items {
+        let key = field(item, &name).cloned().unwrap_or(Json::Null);
+        match groups.iter_mut().find(|&&mut (ref k, _)| json_eq(k, &key)) {
+            Some(&mut (_, ref mut group)) => group.push(item.clone()),
+            None => groups.push((key, vec![item.clone()])),
+        }
+    }
+
+    let groups = groups
+        .into_iter()
+        .map(|(key, items)| {
+            let mut group = Map::new();
+            group.insert("key".to_owned(), key);
+            group.insert("items".to_owned(), Json::Array(items));
+            Json::Object(group)
+        })
+        .collect();
+    Ok(Json::Array(groups))
+}
+
+/// `{{uniq list}}` drops repeated items, `{{uniq list "field"}}` items with
+/// a repeated field, keeping the first ones
+fn uniq(h: &Helper) -> Result<Json, RenderError> {
+    let items = array(h, 0)?;
+    let name = match h.param(1) {
+        Some(_) => Some(field_name(h, 1)?),
+        None => None,
+    };
+
+    let mut seen = HashSet::new();
+    let unique = items
+        .iter()
+        .filter(|item| {
+            let key = match name {
+                Some(ref name) => field(item, name),
+                None => Some(*item),
+            };
+            seen.insert(key.map(|k| k.to_string()))
+        })
+        .cloned()
+        .collect();
+    Ok(Json::Array(unique))
+}
+
+/// Keys of an object, or indices of an array
+fn keys(h: &Helper) -> Result<Json, RenderError> {
+    match *param(h, 0)? {
+        Json::Object(ref map) => Ok(Json::Array(
+            map.keys().map(|k| Json::String(k.clone())).collect(),
+        )),
+        _ => Ok(Json::Array(
+            (0..array(h, 0)?.len()).map(Json::from).collect(),
+        )),
+    }
+}
+
+fn values(h: &Helper) -> Result<Json, RenderError> {
+    match *param(h, 0)? {
+        Json::Object(ref map) => Ok(Json::Array(map.values().cloned().collect())),
+        _ => Ok(Json::Array(array(h, 0)?.to_vec())),
+    }
+}
+
+/// `{{join list ", "}}` joins the rendered items of an array, with `,` when
+/// no separator is given
+fn join(h: &Helper) -> Result<Json, RenderError> {
+    let sep = match h.param(1) {
+        Some(sep) => sep.value().render(),
+        None => ",".to_owned(),
+    };
+
+    let joined = match *param(h, 0)? {
+        Json::Array(ref items) => items
+            .iter()
+            .map(|item| item.render())
+            .collect::<Vec<_>>()
+            .join(&sep),
+        ref value => value.render(),
+    };
+    Ok(Json::String(joined))
+}
+
+/// A collection helper, returning a value computed from its params
+#[derive(Clone, Copy)]
+pub struct CollectionHelper(fn(&Helper) -> Result<Json, RenderError>);
+
+impl HelperDef for CollectionHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Registry,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg>,
+    ) -> Result<Option<ScopedJson<'reg, 'rc>>, RenderError> {
+        Ok(Some(ScopedJson::Derived((self.0)(h)?)))
+    }
+}
+
+pub static LEN_HELPER: CollectionHelper = CollectionHelper(len);
+pub static FIRST_HELPER: CollectionHelper = CollectionHelper(first);
+pub static LAST_HELPER: CollectionHelper = CollectionHelper(last);
+pub static SLICE_HELPER: CollectionHelper = CollectionHelper(slice);
+pub static REVERSE_HELPER: CollectionHelper = CollectionHelper(reverse);
+pub static SORT_BY_HELPER: CollectionHelper = CollectionHelper(sort_by);
+pub static FILTER_BY_HELPER: CollectionHelper = CollectionHelper(filter_by);
+pub static GROUP_BY_HELPER: CollectionHelper = CollectionHelper(group_by);
+pub static UNIQ_HELPER: CollectionHelper = CollectionHelper(uniq);
+pub static KEYS_HELPER: CollectionHelper = CollectionHelper(keys);
+pub static VALUES_HELPER: CollectionHelper = CollectionHelper(values);
+pub static JOIN_HELPER: CollectionHelper = CollectionHelper(join);
+
+#[cfg(test)]
+mod test {
+    use crate::registry::Registry;
+
+    fn data() -> serde_json::Value {
+        json!({
+            "nums": [3, 1, 2, 3, 1],
+            "words": "hello",
+            "posts": [
+                {"name": "c", "year": 2019, "author": {"name": "ann"}, "draft": false},
+                {"name": "a", "year": 2020, "author": {"name": "bob"}, "draft": true},
+                {"name": "b", "year": 2019, "author": {"name": "ann"}},
+                {"name": "d", "author": {"name": "cid"}}
+            ],
+            "map": {"x": 1, "y": 2}
+        })
+    }
+
+    #[test]
+    fn test_collection_helpers() {
+        let mut handlebars = Registry::new();
+        handlebars.register_collection_helpers();
+
+        assert_eq!(
+            handlebars.render_template("{{len nums}}", &data()).unwrap(),
+            "5"
+        );
+        assert_eq!(
+            handlebars.render_template("{{len map}}", &data()).unwrap(),
+            "2"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{len words}}", &data())
+                .unwrap(),
+            "5"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{len missing}}", &data())
+                .unwrap(),
+            "0"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{first nums}}", &data())
+                .unwrap(),
+            "3"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{last nums}}", &data())
+                .unwrap(),
+            "1"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{join (first nums 2)}}", &data())
+                .unwrap(),
+            "3,1"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{join (last nums 2)}}", &data())
+                .unwrap(),
+            "3,1"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("[{{first missing}}]", &data())
+                .unwrap(),
+            "[]"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{join (slice nums 1 3)}}", &data())
+                .unwrap(),
+            "1,2"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{join (slice nums -2)}}", &data())
+                .unwrap(),
+            "3,1"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{join (slice nums 4 2)}}", &data())
+                .unwrap(),
+            ""
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{slice words 1 -1}}", &data())
+                .unwrap(),
+            "ell"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{join (reverse nums) \" \"}}", &data())
+                .unwrap(),
+            "1 3 2 1 3"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{reverse words}}", &data())
+                .unwrap(),
+            "olleh"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{join (sort_by nums)}}", &data())
+                .unwrap(),
+            "1,1,2,3,3"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{join (uniq nums)}}", &data())
+                .unwrap(),
+            "3,1,2"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{join (keys map)}}", &data())
+                .unwrap(),
+            "x,y"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{join (values map)}}", &data())
+                .unwrap(),
+            "1,2"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{join (keys nums)}}", &data())
+                .unwrap(),
+            "0,1,2,3,4"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each (sort_by posts \"name\")}}{{name}}{{/each}}",
+                    &data()
+                )
+                .unwrap(),
+            "abcd"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each (sort_by posts \"year\")}}{{name}}{{/each}}",
+                    &data()
+                )
+                .unwrap(),
+            "cbad"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each (reverse (sort_by posts \"author.name\"))}}{{name}}{{/each}}",
+                    &data()
+                )
+                .unwrap(),
+            "dabc"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each (filter_by posts \"year\" 2019)}}{{name}}:{{@index}} {{/each}}",
+                    &data()
+                )
+                .unwrap(),
+            "c:0 b:1 "
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each (filter_by posts \"draft\")}}{{name}}{{/each}}",
+                    &data()
+                )
+                .unwrap(),
+            "a"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{len (filter_by posts \"author.name\" \"ann\")}}", &data())
+                .unwrap(),
+            "2"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each (group_by posts \"year\")}}{{key}}:{{#each items}}{{name}}{{/each}} {{/each}}",
+                    &data()
+                )
+                .unwrap(),
+            "2019:cb 2020:a :d "
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each (uniq posts \"author.name\") as |post|}}{{post.name}}{{/each}}",
+                    &data()
+                )
+                .unwrap(),
+            "cad"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#each (keys map)}}{{this}}:{{@index}} {{/each}}", &data())
+                .unwrap(),
+            "x:0 y:1 "
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each (first posts 2) as |post i|}}{{i}}.{{post.author.name}}/{{year}} {{/each}}",
+                    &data()
+                )
+                .unwrap(),
+            "0.ann/2019 1.bob/2020 "
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each (filter_by posts \"year\" 1999)}}{{name}}{{else}}none{{/each}}",
+                    &data()
+                )
+                .unwrap(),
+            "none"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#with (first posts)}}{{name}}/{{author.name}}{{/with}}",
+                    &data()
+                )
+                .unwrap(),
+            "c/ann"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#with (last posts) as |post|}}{{post.name}}/{{author.name}}{{/with}}",
+                    &data()
+                )
+                .unwrap(),
+            "d/cid"
+        );
+    }
+
+    #[test]
+    fn test_collection_helper_errors() {
+        let mut handlebars = Registry::new();
+        handlebars.register_collection_helpers();
+
+        assert!(handlebars
+            .render_template("{{len 1}}", &data())
+            .unwrap_err()
+            .to_string()
+            .contains("Param 0 of helper \"len\" is not a collection: 1"));
+        assert!(handlebars
+            .render_template("{{first words}}", &data())
+            .unwrap_err()
+            .to_string()
+            .contains("Param 0 of helper \"first\" is not an array: \"hello\""));
+        assert!(handlebars
+            .render_template("{{slice nums \"a\"}}", &data())
+            .unwrap_err()
+            .to_string()
+            .contains("Param 1 of helper \"slice\" is not an integer: \"a\""));
+        assert!(handlebars
+            .render_template("{{sort_by}}", &data())
+            .unwrap_err()
+            .to_string()
+            .contains("Insufficient params for helper \"sort_by\""));
+        assert!(handlebars
+            .render_template("{{group_by posts}}", &data())
+            .unwrap_err()
+            .to_string()
+            .contains("Insufficient params for helper \"group_by\""));
+    }
+}
@@ -0,0 +1,364 @@
+//! Date helpers, registered by `Registry::register_date_helpers`
+//!
+//! Dates are RFC 3339 strings like `2019-10-12T07:20:50Z`, dates like
+//! `2019-10-12` at midnight UTC, or unix epochs in seconds.
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde_json::value::Value as Json;
+
+use crate::context::Context;
+use crate::error::RenderError;
+use crate::helpers::HelperDef;
+use crate::registry::Registry;
+use crate::render::{Helper, RenderContext};
+use crate::value::{JsonRender, ScopedJson};
+
+const DEFAULT_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+fn parse_date(value: &Json) -> Option<DateTime<FixedOffset>> {
+    let utc = match *value {
+        Json::Number(ref n) => match n.as_i64() {
+            Some(secs) => Utc.timestamp_opt(secs, 0).single(),
+            None => n.as_f64().and_then(|secs| {
+                let nanos = (secs.fract().abs() * 1e9) as u32;
+                Utc.timestamp_opt(secs.floor() as i64, nanos).single()
+            }),
+        },
+        Json::String(ref s) => {
+            if let Ok(date) = DateTime::parse_from_rfc3339(s) {
+                return Some(date);
+            }
+            NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S")
+                .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S"))
+                .or_else(|_| {
+                    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                        .map(|d| d.and_hms_opt(0, 0, 0).unwrap_or_default())
+                })
+                .ok()
+                .map(|d| Utc.from_utc_datetime(&d))
+        }
+        _ => None,
+    };
+    utc.map(|d| d.fixed_offset())
+}
+
+fn date_param(h: &Helper, idx: usize) -> Result<DateTime<FixedOffset>, RenderError> {
+    let value = h
+        .param(idx)
+        .ok_or_else(|| RenderError::new(format!("Param not found for helper \"{}\"", h.name())))?;
+    parse_date(value.value()).ok_or_else(|| cannot_parse(h, value.value()))
+}
+
+fn cannot_parse(h: &Helper, value: &Json) -> RenderError {
+    RenderError::new(format!(
+        "Cannot parse {} as a date in helper \"{}\"",
+        value,
+        h.name()
+    ))
+}
+
+fn timezone(h: &Helper, name: &str) -> Result<Tz, RenderError> {
+    name.parse::<Tz>().map_err(|_| {
+        RenderError::new(format!(
+            "Unknown timezone \"{}\" in helper \"{}\"",
+            name,
+            h.name()
+        ))
+    })
+}
+
+fn derived(s: String) -> Result<Option<ScopedJson<'static, 'static>>, RenderError> {
+    Ok(Some(ScopedJson::Derived(Json::String(s))))
+}
+
+/// `{{format_date date "%d.%m.%Y"}}` formats a date with a strftime-style
+/// pattern, `%Y-%m-%d %H:%M:%S` by default. The `tz` hash param, like
+/// `tz="Europe/Berlin"`, converts it to a timezone first.
+#[derive(Clone, Copy)]
+pub struct FormatDateHelper;
+
+impl HelperDef for FormatDateHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Registry,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg>,
+    ) -> Result<Option<ScopedJson<'reg, 'rc>>, RenderError> {
+        let date = date_param(h, 0)?;
+        let pattern = h
+            .param(1)
+            .map(|p| p.value().render())
+            .unwrap_or_else(|| DEFAULT_FORMAT.to_owned());
+
+        let items = StrftimeItems::new(&pattern).collect::<Vec<_>>();
+        if items.contains(&Item::Error) {
+            return Err(RenderError::new(format!(
+                "Invalid date format \"{}\" in helper \"{}\"",
+                pattern,
+                h.name()
+            )));
+        }
+
+        let formatted = match h.hash_get("tz") {
+            Some(tz) => {
+                let tz = timezone(h, &tz.value().render())?;
+                date.with_timezone(&tz)
+                    .format_with_items(items.into_iter())
+                    .to_string()
+            }
+            None => date.format_with_items(items.into_iter()).to_string(),
+        };
+        derived(formatted)
+    }
+}
+
+/// `{{to_timezone date "America/New_York"}}` returns the date in a
+/// timezone, as an RFC 3339 string
+#[derive(Clone, Copy)]
+pub struct ToTimezoneHelper;
+
+impl HelperDef for ToTimezoneHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Registry,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg>,
+    ) -> Result<Option<ScopedJson<'reg, 'rc>>, RenderError> {
+        let date = date_param(h, 0)?;
+        let name = h.param(1).map(|p| p.value().render()).ok_or_else(|| {
+            RenderError::new(format!("Insufficient params for helper \"{}\"", h.name()))
+        })?;
+
+        let tz = timezone(h, &name)?;
+        derived(date.with_timezone(&tz).to_rfc3339())
+    }
+}
+
+/// `{{relative_time date}}` describes a date relative to now, like
+/// `3 hours ago` or `in 2 days`
+///
+/// Now is the `now` hash param, the time given to
+/// `RelativeTimeHelper::with_now`, or the current time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RelativeTimeHelper {
+    now: Option<DateTime<Utc>>,
+}
+
+impl RelativeTimeHelper {
+    /// A `relative_time` helper describing dates relative to `now`, for
+    /// reproducible renders in tests
+    pub fn with_now(now: DateTime<Utc>) -> RelativeTimeHelper {
+        RelativeTimeHelper { now: Some(now) }
+    }
+}
+
+impl HelperDef for RelativeTimeHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Registry,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg>,
+    ) -> Result<Option<ScopedJson<'reg, 'rc>>, RenderError> {
+        let date = date_param(h, 0)?;
+        let now = match h.hash_get("now") {
+            Some(now) => parse_date(now.value())
+                .ok_or_else(|| cannot_parse(h, now.value()))?
+                .with_timezone(&Utc),
+            None => self.now.unwrap_or_else(Utc::now),
+        };
+
+        let seconds = date
+            .with_timezone(&Utc)
+            .signed_duration_since(now)
+            .num_seconds();
+        derived(describe(seconds))
+    }
+}
+
+fn describe(seconds: i64) -> String {
+    const UNITS: &[(&str, i64)] = &[
+        ("year", 365 * 24 * 3600),
+        ("month", 30 * 24 * 3600),
+        ("day", 24 * 3600),
+        ("hour", 3600),
+        ("minute", 60),
+    ];
+
+    let abs = seconds.abs();
+    let (unit, count) = match UNITS.iter().find(|&&(_, size)| abs >= size) {
+        Some(&(unit, size)) => (unit, abs / size),
+        None => return "just now".to_owned(),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    if seconds < 0 {
+        format!("{} {}{} ago", count, unit, plural)
+    } else {
+        format!("in {} {}{}", count, unit, plural)
+    }
+}
+
+pub static FORMAT_DATE_HELPER: FormatDateHelper = FormatDateHelper;
+pub static TO_TIMEZONE_HELPER: ToTimezoneHelper = ToTimezoneHelper;
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+
+    use crate::helpers::helper_date::RelativeTimeHelper;
+    use crate::registry::Registry;
+
+    #[test]
+    fn test_format_date() {
+        let mut handlebars = Registry::new();
+        handlebars.register_date_helpers();
+
+        let data = json!({
+            "rfc": "2019-10-12T07:20:50+02:00",
+            "day": "2019-10-12",
+            "epoch": 1570864850,
+            "epoch_frac": 1570864850.5
+        });
+        assert_eq!(
+            handlebars
+                .render_template("{{format_date rfc}}", &data)
+                .unwrap(),
+            "2019-10-12 07:20:50"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_date rfc \"%d.%m.%Y %H:%M %z\"}}", &data)
+                .unwrap(),
+            "12.10.2019 07:20 +0200"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_date day \"%A %e %B\"}}", &data)
+                .unwrap(),
+            "Saturday 12 October"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_date epoch}}", &data)
+                .unwrap(),
+            "2019-10-12 07:20:50"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_date epoch_frac \"%S%.3f\"}}", &data)
+                .unwrap(),
+            "50.500"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{format_date epoch \"%H:%M %Z\" tz=\"Asia/Tokyo\"}}",
+                    &data
+                )
+                .unwrap(),
+            "16:20 JST"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{to_timezone rfc \"America/New_York\"}}", &data)
+                .unwrap(),
+            "2019-10-12T01:20:50-04:00"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{format_date (to_timezone epoch \"Europe/Berlin\") \"%H:%M\"}}",
+                    &data
+                )
+                .unwrap(),
+            "09:20"
+        );
+    }
+
+    #[test]
+    fn test_relative_time() {
+        let mut handlebars = Registry::new();
+        handlebars.register_date_helpers();
+        let now = Utc.with_ymd_and_hms(2019, 10, 12, 12, 0, 0).unwrap();
+        handlebars.register_helper("relative_time", Box::new(RelativeTimeHelper::with_now(now)));
+
+        let data = json!({
+            "recent": "2019-10-12T11:59:30Z",
+            "hours": "2019-10-12T09:00:00Z",
+            "minute": "2019-10-12T11:59:00Z",
+            "future": "2019-10-14T12:00:00Z",
+            "old": "2017-01-01",
+        });
+        assert_eq!(
+            handlebars
+                .render_template("{{relative_time recent}}", &data)
+                .unwrap(),
+            "just now"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{relative_time minute}}", &data)
+                .unwrap(),
+            "1 minute ago"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{relative_time hours}}", &data)
+                .unwrap(),
+            "3 hours ago"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{relative_time future}}", &data)
+                .unwrap(),
+            "in 2 days"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{relative_time old}}", &data)
+                .unwrap(),
+            "2 years ago"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{relative_time hours now=\"2019-11-12T09:00:00Z\"}}",
+                    &data
+                )
+                .unwrap(),
+            "1 month ago"
+        );
+    }
+
+    #[test]
+    fn test_date_helper_errors() {
+        let mut handlebars = Registry::new();
+        handlebars.register_date_helpers();
+
+        let data = json!({"bad": "yesterday", "date": "2019-10-12"});
+        assert!(handlebars
+            .render_template("{{format_date bad}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("Cannot parse \"yesterday\" as a date in helper \"format_date\""));
+        assert!(handlebars
+            .render_template("{{relative_time missing}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("Cannot parse null as a date in helper \"relative_time\""));
+        assert!(handlebars
+            .render_template("{{format_date date \"%Q\"}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid date format \"%Q\" in helper \"format_date\""));
+        assert!(handlebars
+            .render_template("{{to_timezone date \"Mars/Base\"}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown timezone \"Mars/Base\" in helper \"to_timezone\""));
+    }
+}
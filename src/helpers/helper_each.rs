@@ -2,12 +2,20 @@ use serde_json::value::Value as Json;
 
 use crate::context::{BlockParams, Context};
 use crate::error::RenderError;
+use crate::helpers::helper_let::render_in_scope;
 use crate::helpers::{HelperDef, HelperResult};
 use crate::output::Output;
 use crate::registry::Registry;
 use crate::render::{Helper, RenderContext, Renderable};
 use crate::value::{to_json, JsonTruthy};
 
+/// `{{#each list}}` renders its block for the items of an array or the
+/// values of an object
+///
+/// The `sort_keys=true` hash param iterates an object by sorted keys,
+/// `reverse=true` backwards, and `offset` and `limit` render a part of the
+/// items. `@index`, `@first`, `@last`, `@odd`, `@even` and `@length` are
+/// about the rendered items.
 #[derive(Clone, Copy)]
 pub struct EachHelper;
 
@@ -24,140 +32,160 @@ impl HelperDef for EachHelper {
             .param(0)
             .ok_or_else(|| RenderError::new("Param not found for helper \"each\""))?;
 
-        let template = h.template();
-
-        match template {
-            Some(t) => {
-                rc.promote_local_vars();
-                let local_path_root = value
-                    .path_root()
-                    .map(|p| format!("{}/{}", rc.get_path(), p));
-
-                debug!("each value {:?}", value.value());
-                let rendered = match (value.value().is_truthy(false), value.value()) {
-                    (true, &Json::Array(ref list)) => {
-                        let len = list.len();
-
-                        let array_path = value.path().map(|p| {
-                            if value.is_absolute_path() {
-                                p.to_string()
-                            } else {
-                                format!("{}/{}", rc.get_path(), p)
-                            }
-                        });
-
-                        for (i, _) in list.iter().enumerate().take(len) {
-                            let mut local_rc = rc.derive();
-                            if let Some(ref p) = local_path_root {
-                                local_rc.push_local_path_root(p.clone());
-                            }
-
-                            local_rc.set_local_var("@first".to_string(), to_json(i == 0usize));
-                            local_rc.set_local_var("@last".to_string(), to_json(i == len - 1));
-                            local_rc.set_local_var("@index".to_string(), to_json(i));
-
-                            if let Some(ref p) = array_path {
-                                let new_path = format!("{}/[{}]", p, i);
-                                debug!("each path {:?}", new_path);
-                                local_rc.set_path(new_path);
-                            }
-
-                            if let Some(bp_val) = h.block_param() {
-                                let mut params = BlockParams::new();
-                                params.add_path(bp_val, local_rc.get_path())?;
-
-                                local_rc.push_block_context(params)?;
-                            } else if let Some((bp_val, bp_index)) = h.block_param_pair() {
-                                let mut params = BlockParams::new();
-                                params.add_path(bp_val, local_rc.get_path())?;
-                                params.add_value(bp_index, to_json(i))?;
-
-                                local_rc.push_block_context(params)?;
-                            }
-
-                            t.render(r, ctx, &mut local_rc, out)?;
-
-                            if h.has_block_param() {
-                                local_rc.pop_block_context();
-                            }
-
-                            if local_path_root.is_some() {
-                                local_rc.pop_local_path_root();
-                            }
-                        }
-                        Ok(())
-                    }
-                    (true, &Json::Object(ref obj)) => {
-                        let mut first: bool = true;
-                        let obj_path = value.path().map(|p| {
-                            if value.is_absolute_path() {
-                                p.to_string()
-                            } else {
-                                format!("{}/{}", rc.get_path(), p)
-                            }
-                        });
-
-                        for (k, _) in obj.iter() {
-                            let mut local_rc = rc.derive();
-
-                            if let Some(ref p) = local_path_root {
-                                local_rc.push_local_path_root(p.clone());
-                            }
-                            local_rc.set_local_var("@first".to_string(), to_json(first));
-                            if first {
-                                first = false;
-                            }
-
-                            local_rc.set_local_var("@key".to_string(), to_json(k));
-
-                            if let Some(ref p) = obj_path {
-                                let new_path = format!("{}/[{}]", p, k);
-                                local_rc.set_path(new_path);
-                            }
-
-                            if let Some(bp_val) = h.block_param() {
-                                let mut params = BlockParams::new();
-                                params.add_path(bp_val, local_rc.get_path())?;
-
-                                local_rc.push_block_context(params)?;
-                            } else if let Some((bp_val, bp_key)) = h.block_param_pair() {
-                                let mut params = BlockParams::new();
-                                params.add_path(bp_val, local_rc.get_path())?;
-                                params.add_value(bp_key, to_json(&k))?;
-
-                                local_rc.push_block_context(params)?;
-                            }
-
-                            t.render(r, ctx, &mut local_rc, out)?;
-
-                            if h.has_block_param() {
-                                local_rc.pop_block_context();
-                            }
-
-                            if local_path_root.is_some() {
-                                local_rc.pop_local_path_root();
-                            }
-                        }
-
-                        Ok(())
-                    }
-                    (false, _) => {
-                        if let Some(else_template) = h.inverse() {
-                            else_template.render(r, ctx, rc, out)?;
-                        }
-                        Ok(())
+        let template = match h.template() {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+
+        debug!("each value {:?}", value.value());
+        // keys of objects, array indices and the items
+        let mut entries: Vec<(Option<&String>, usize, &Json)> =
+            match (value.value().is_truthy(false), value.value()) {
+                (true, &Json::Array(ref list)) => {
+                    list.iter().enumerate().map(|(i, v)| (None, i, v)).collect()
+                }
+                (true, &Json::Object(ref obj)) => {
+                    let mut entries = obj
+                        .iter()
+                        .enumerate()
+                        .map(|(i, (k, v))| (Some(k), i, v))
+                        .collect::<Vec<_>>();
+                    if hash_flag(h, "sort_keys") {
+                        entries.sort_by(|a, b| a.0.cmp(&b.0));
                     }
-                    _ => Err(RenderError::new(format!(
+                    entries
+                }
+                (false, _) => Vec::new(),
+                _ => {
+                    return Err(RenderError::new(format!(
                         "Param type is not iterable: {:?}",
                         value.value()
-                    ))),
-                };
+                    )));
+                }
+            };
+        if hash_flag(h, "reverse") {
+            entries.reverse();
+        }
+        let offset = hash_count(h, "offset")?.unwrap_or(0);
+        let limit = hash_count(h, "limit")?.unwrap_or(std::usize::MAX);
+        let entries = entries
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .collect::<Vec<_>>();
+
+        rc.promote_local_vars();
+
+        if entries.is_empty() {
+            if let Some(else_template) = h.inverse() {
+                render_in_scope(else_template, r, ctx, rc, out)?;
+            }
+        }
+
+        let local_path_root = value
+            .path_root()
+            .map(|p| format!("{}/{}", rc.get_path(), p));
+        let base_path = value.path().map(|p| {
+            if value.is_absolute_path() {
+                p.to_string()
+            } else {
+                format!("{}/{}", rc.get_path(), p)
+            }
+        });
+
+        let len = entries.len();
+        for (i, &(key, index, item)) in entries.iter().enumerate() {
+            let mut local_rc = rc.derive();
+            if let Some(ref p) = local_path_root {
+                local_rc.push_local_path_root(p.clone());
+            }
+
+            local_rc.set_local_var("@first".to_string(), to_json(i == 0));
+            local_rc.set_local_var("@last".to_string(), to_json(i == len - 1));
+            local_rc.set_local_var("@index".to_string(), to_json(i));
+            local_rc.set_local_var("@length".to_string(), to_json(len));
+            local_rc.set_local_var("@odd".to_string(), to_json(i % 2 == 1));
+            local_rc.set_local_var("@even".to_string(), to_json(i % 2 == 0));
+            if let Some(k) = key {
+                local_rc.set_local_var("@key".to_string(), to_json(k));
+            }
+
+            match base_path {
+                Some(ref p) => {
+                    let new_path = match key {
+                        Some(k) => format!("{}/[{}]", p, k),
+                        None => format!("{}/[{}]", p, index),
+                    };
+                    debug!("each path {:?}", new_path);
+                    local_rc.set_path(new_path);
+                }
+                // items of a value without a path, like the result of a
+                // subexpression, are the context of the block
+                None => local_rc.push_value_context(item.clone()),
+            }
+
+            if let Some(bp_val) = h.block_param() {
+                let mut params = BlockParams::new();
+                add_item_param(&mut params, bp_val, &local_rc, &base_path, item)?;
+
+                local_rc.push_block_context(params)?;
+            } else if let Some((bp_val, bp_key)) = h.block_param_pair() {
+                let mut params = BlockParams::new();
+                add_item_param(&mut params, bp_val, &local_rc, &base_path, item)?;
+                match key {
+                    Some(k) => params.add_value(bp_key, to_json(k))?,
+                    None => params.add_value(bp_key, to_json(i))?,
+                }
+
+                local_rc.push_block_context(params)?;
+            }
+
+            template.render(r, ctx, &mut local_rc, out)?;
+
+            if h.has_block_param() {
+                local_rc.pop_block_context();
+            }
 
-                rc.demote_local_vars();
-                rendered
+            if local_path_root.is_some() {
+                local_rc.pop_local_path_root();
             }
-            None => Ok(()),
         }
+
+        rc.demote_local_vars();
+        Ok(())
+    }
+}
+
+fn hash_flag(h: &Helper, name: &str) -> bool {
+    h.hash_get(name)
+        .and_then(|v| v.value().as_bool())
+        .unwrap_or(false)
+}
+
+fn hash_count(h: &Helper, name: &str) -> Result<Option<usize>, RenderError> {
+    match h.hash_get(name) {
+        Some(v) => v.value().as_u64().map(|n| Some(n as usize)).ok_or_else(|| {
+            RenderError::new(format!(
+                "Hash \"{}\" of helper \"each\" must be a non-negative integer",
+                name
+            ))
+        }),
+        None => Ok(None),
+    }
+}
+
+/// The item as a block param, by its path or, for an item without a path, by
+/// its value
+fn add_item_param(
+    params: &mut BlockParams,
+    name: &str,
+    rc: &RenderContext,
+    base_path: &Option<String>,
+    item: &Json,
+) -> Result<(), RenderError> {
+    match *base_path {
+        Some(_) => params.add_path(name, rc.get_path()),
+        None => params.add_value(name, item.clone()),
     }
 }
 
@@ -316,6 +344,19 @@ mod test {
         assert_eq!(r1, "empty");
     }
 
+    #[test]
+    fn test_each_object_index() {
+        let handlebars = Registry::new();
+        let data = json!({"a": {"x": 1, "y": 2, "z": 3}});
+        let r0 = handlebars
+            .render_template(
+                "{{#each a}}{{@index}}{{@key}}{{#if @first}}f{{/if}}{{#if @last}}l{{/if}} {{/each}}",
+                &data,
+            )
+            .unwrap();
+        assert_eq!(r0, "0xf 1y 2zl ");
+    }
+
     #[test]
     fn test_block_param() {
         let mut handlebars = Registry::new();
@@ -417,4 +458,180 @@ mod test {
         assert!(r0.contains("😂: 4"));
         assert!(r0.contains("me.dot.key: 5"));
     }
+
+    #[test]
+    fn test_each_loop_metadata() {
+        let handlebars = Registry::new();
+        let data = json!({"a": [{"b": [1, 2]}, {"b": [3]}], "o": {"x": 1, "y": 2}});
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each a}}{{@index}}/{{@length}}{{#if @odd}}o{{/if}}{{#if @even}}e{{/if}} {{/each}}",
+                    &data
+                )
+                .unwrap(),
+            "0/2e 1/2o "
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each o as |v k|}}{{k}}={{v}}:{{@index}}/{{@length}} {{/each}}",
+                    &data
+                )
+                .unwrap(),
+            "x=1:0/2 y=2:1/2 "
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each a}}{{#each b}}{{@../index}}.{{@index}}/{{@../length}} {{/each}}{{/each}}",
+                    &data
+                )
+                .unwrap(),
+            "0.0/2 0.1/2 1.0/2 "
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each a as |x|}}{{#each x.b as |y i|}}{{@../index}}.{{i}}={{y}} {{/each}}{{@index}} {{/each}}",
+                    &data
+                )
+                .unwrap(),
+            "0.0=1 0.1=2 0 1.0=3 1 "
+        );
+    }
+
+    #[test]
+    fn test_each_value_without_path() {
+        let mut handlebars = Registry::new();
+        handlebars.register_collection_helpers();
+        let data = json!({
+            "site": "blog",
+            "outer": [{"name": "x"}],
+            "posts": [
+                {"d": 2, "name": "b", "tags": ["t1"]},
+                {"d": 1, "name": "a", "tags": ["t2", "t3"]}
+            ]
+        });
+
+        let r0 = handlebars.render_template(
+            "{{#each (sort_by posts \"d\")}}{{name}}@{{../site}}/{{@root.site}} {{/each}}",
+            &data,
+        );
+        assert_eq!(r0.unwrap(), "a@blog/blog b@blog/blog ");
+
+        let r1 = handlebars.render_template(
+            "{{#each outer as |o|}}{{#each (sort_by ../posts \"d\")}}{{o.name}}{{name}} {{/each}}{{/each}}",
+            &data,
+        );
+        assert_eq!(r1.unwrap(), "xa xb ");
+
+        // `../` goes up through the items of the enclosing blocks
+        let r2 = handlebars.render_template(
+            "{{#each (sort_by posts \"d\")}}{{#each tags}}{{this}}:{{../name}}@{{../../site}} {{/each}}{{/each}}",
+            &data,
+        );
+        assert_eq!(r2.unwrap(), "t2:a@blog t3:a@blog t1:b@blog ");
+
+        let r3 = handlebars.render_template(
+            "{{#each (sort_by posts \"d\") as |post i|}}{{i}}{{post.name}}{{#each ../posts}}{{name}}{{/each}} {{/each}}",
+            &data,
+        );
+        assert_eq!(r3.unwrap(), "0aba 1bba ");
+    }
+
+    #[test]
+    fn test_each_hash_params() {
+        let mut handlebars = Registry::new();
+        handlebars.register_collection_helpers();
+        let data = json!({
+            "list": [1, 2, 3, 4, 5],
+            "o": {"b": 2, "c": 3, "a": 1}
+        });
+        assert_eq!(
+            handlebars
+                .render_template("{{#each list limit=2}}{{this}}{{/each}}", &data)
+                .unwrap(),
+            "12"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#each list offset=3}}{{this}}{{/each}}", &data)
+                .unwrap(),
+            "45"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each list offset=1 limit=2}}{{@index}}:{{this}}{{#if @last}}!{{/if}} {{/each}}",
+                    &data
+                )
+                .unwrap(),
+            "0:2 1:3! "
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each list reverse=true limit=2}}{{this}}{{/each}}",
+                    &data
+                )
+                .unwrap(),
+            "54"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each list reverse=true as |n i|}}{{i}}={{n}} {{/each}}",
+                    &data
+                )
+                .unwrap(),
+            "0=5 1=4 2=3 3=2 4=1 "
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#each o sort_keys=true}}{{@key}}{{this}}{{/each}}", &data)
+                .unwrap(),
+            "a1b2c3"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each o sort_keys=true reverse=true as |v k|}}{{k}}{{v}}{{/each}}",
+                    &data
+                )
+                .unwrap(),
+            "c3b2a1"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#each list offset=5}}x{{else}}none{{/each}}", &data)
+                .unwrap(),
+            "none"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each list limit=0}}x{{else}}none {{@index}}{{/each}}",
+                    &data
+                )
+                .unwrap(),
+            "none "
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each (reverse list) limit=1}}{{this}}/{{@length}}{{/each}}",
+                    &data
+                )
+                .unwrap(),
+            "5/1"
+        );
+
+        let err = handlebars
+            .render_template("{{#each list limit=-1}}{{/each}}", &data)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Hash \"limit\" of helper \"each\" must be a non-negative integer"));
+    }
 }
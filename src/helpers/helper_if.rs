@@ -1,9 +1,10 @@
 use crate::context::Context;
 use crate::error::RenderError;
+use crate::helpers::helper_let::render_in_scope;
 use crate::helpers::{HelperDef, HelperResult};
 use crate::output::Output;
 use crate::registry::Registry;
-use crate::render::{Helper, RenderContext, Renderable};
+use crate::render::{Helper, RenderContext};
 use crate::value::JsonTruthy;
 
 #[derive(Clone, Copy)]
@@ -36,7 +37,7 @@ impl HelperDef for IfHelper {
 
         let tmpl = if value { h.template() } else { h.inverse() };
         match tmpl {
-            Some(ref t) => t.render(r, ctx, rc, out),
+            Some(t) => render_in_scope(t, r, ctx, rc, out),
             None => Ok(()),
         }
     }
@@ -126,4 +127,33 @@ mod test {
                 .unwrap()
         );
     }
+
+    #[test]
+    fn test_if_else_chain() {
+        let handlebars = Registry::new();
+        let t = "{{#if a}}a{{else if b}}b{{else unless c}}not c{{else}}c{{/if}}";
+
+        for &(ref data, expected) in &[
+            (json!({"a": true, "b": true}), "a"),
+            (json!({"b": true}), "b"),
+            (json!({}), "not c"),
+            (json!({"c": true}), "c"),
+        ] {
+            assert_eq!(handlebars.render_template(t, data).unwrap(), expected);
+        }
+
+        // any block helper can be chained
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#if a}}a{{else each b as |x|}}{{x}}{{else}}empty{{/if}}",
+                    &json!({"b": [1, 2]})
+                )
+                .unwrap(),
+            "12"
+        );
+        assert!(handlebars
+            .render_template("{{#if a}}a{{else if b}}b{{/unless}}", &json!({}))
+            .is_err());
+    }
 }
@@ -0,0 +1,292 @@
+use crate::context::Context;
+use crate::error::RenderError;
+use crate::helpers::{HelperDef, HelperResult};
+use crate::output::Output;
+use crate::partial;
+use crate::registry::Registry;
+use crate::render::{Evaluable, Helper, RenderContext, Renderable};
+use crate::template::{HelperTemplate, Template, TemplateElement};
+
+fn get_name<'reg: 'rc, 'rc>(
+    h: &'rc Helper<'reg, 'rc>,
+    helper_name: &str,
+) -> Result<&'rc str, RenderError> {
+    h.param(0)
+        .ok_or_else(|| RenderError::new(format!("Param not found for helper \"{}\"", helper_name)))
+        .and_then(|v| {
+            v.value()
+                .as_str()
+                .ok_or_else(|| RenderError::new(format!("{} name must be string", helper_name)))
+        })
+}
+
+fn is_block_definition(ht: &HelperTemplate) -> bool {
+    ht.name.as_name() == Some("block")
+}
+
+// Replaces the blocks of the layout with the ones defined in `t`, also
+// inside other blocks like `{{#if}}`. Blocks nested in a definition are
+// rendered with it, so they are left alone.
+fn set_layout_blocks<'reg: 'rc, 'rc>(
+    t: &'reg Template,
+    r: &'reg Registry,
+    ctx: &'rc Context,
+    rc: &mut RenderContext<'reg>,
+) -> Result<(), RenderError> {
+    for e in &t.elements {
+        if let TemplateElement::HelperBlock(ref ht) = *e {
+            if !is_block_definition(ht) {
+                for nested in ht.template.iter().chain(ht.inverse.iter()) {
+                    set_layout_blocks(nested, r, ctx, rc)?;
+                }
+                continue;
+            }
+            let name = ht
+                .params
+                .first()
+                .ok_or_else(|| RenderError::new("Param not found for helper \"block\""))?
+                .expand(r, ctx, rc)?
+                .value()
+                .as_str()
+                .map(|s| s.to_owned())
+                .ok_or_else(|| RenderError::new("block name must be string"))?;
+
+            // a block from an extending template takes precedence
+            if let Some(ref block) = ht.template {
+                rc.set_layout_block(name, block);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Render a layout with the blocks of the inner template
+///
+/// `{{#extend "layout"}}{{#block "head"}}...{{/block}}{{/extend}}`
+///
+/// Blocks defined here replace blocks with the same name in the layout.
+/// When the layout itself extends another one, blocks defined by the
+/// template closest to the rendered one win.
+#[derive(Clone, Copy)]
+pub struct ExtendHelper;
+
+impl HelperDef for ExtendHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        r: &'reg Registry,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let layout_name = get_name(h, "extend")?;
+        if rc.is_current_template(layout_name) || rc.is_extending(layout_name) {
+            return Err(RenderError::new(format!(
+                "Cannot extend \"{}\", it extends the current template",
+                layout_name
+            )));
+        }
+
+        // declared first so that a loaded layout outlives `local_rc`
+        let layout;
+        let mut local_rc = rc.derive();
+        local_rc.push_extending();
+        if let Some(t) = h.template() {
+            // inline partials defined in the block are available to the layout
+            t.eval(r, ctx, &mut local_rc)?;
+            set_layout_blocks(t, r, ctx, &mut local_rc)?;
+        }
+
+        layout = partial::find_partial(&local_rc, r, layout_name)?
+            .ok_or_else(|| RenderError::new(format!("Layout not found: {}", layout_name)))?;
+        layout.render(r, ctx, &mut local_rc, out)
+    }
+}
+
+/// Render a named block of a layout
+///
+/// `{{#block "head"}}default content{{/block}}`
+///
+/// The block is replaced by the block of the same name defined in an
+/// extending template, or by an inline partial of that name. Otherwise its
+/// own content is rendered.
+#[derive(Clone, Copy)]
+pub struct BlockHelper;
+
+impl HelperDef for BlockHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        r: &'reg Registry,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let name = get_name(h, "block")?;
+
+        let block = rc
+            .get_layout_block(name)
+            .or_else(|| rc.get_partial(name).cloned())
+            .or_else(|| h.template());
+        match block {
+            Some(t) => t.render(r, ctx, rc, out),
+            None => Ok(()),
+        }
+    }
+}
+
+pub static EXTEND_HELPER: ExtendHelper = ExtendHelper;
+pub static BLOCK_HELPER: BlockHelper = BlockHelper;
+
+#[cfg(test)]
+mod test {
+    use crate::registry::Registry;
+
+    fn layouts() -> Registry {
+        let mut handlebars = Registry::new();
+        handlebars.register_layout_helpers();
+        handlebars
+            .register_template_string(
+                "base",
+                "<head>{{#block \"head\"}}base head{{/block}}</head>\
+                 <body>{{#block \"body\"}}base body{{/block}}</body>\
+                 <footer>{{#block \"footer\"}}base footer{{/block}}</footer>",
+            )
+            .unwrap();
+        handlebars
+            .register_template_string(
+                "page",
+                "{{#extend \"base\"}}\
+                 {{#block \"head\"}}page head{{/block}}\
+                 {{#block \"body\"}}<main>{{#block \"main\"}}page main{{/block}}</main>{{/block}}\
+                 {{/extend}}",
+            )
+            .unwrap();
+        handlebars
+    }
+
+    #[test]
+    fn test_extend() {
+        let handlebars = layouts();
+
+        assert_eq!(
+            handlebars.render("base", &()).unwrap(),
+            "<head>base head</head><body>base body</body><footer>base footer</footer>"
+        );
+        assert_eq!(
+            handlebars.render("page", &()).unwrap(),
+            "<head>page head</head><body><main>page main</main></body>\
+             <footer>base footer</footer>"
+        );
+    }
+
+    #[test]
+    fn test_extend_chain() {
+        let mut handlebars = layouts();
+        handlebars
+            .register_template_string(
+                "article",
+                "{{#extend \"page\"}}\
+                 {{#block \"main\"}}{{title}}{{/block}}\
+                 {{#block \"head\"}}article head{{/block}}\
+                 ignored text\
+                 {{/extend}}",
+            )
+            .unwrap();
+
+        assert_eq!(
+            handlebars
+                .render("article", &json!({"title": "hello"}))
+                .unwrap(),
+            "<head>article head</head><body><main>hello</main></body>\
+             <footer>base footer</footer>"
+        );
+    }
+
+    #[test]
+    fn test_extend_with_inline() {
+        let mut handlebars = layouts();
+        handlebars
+            .register_template_string(
+                "t0",
+                "{{#extend \"base\"}}{{#*inline \"footer\"}}inline footer{{/inline}}{{/extend}}",
+            )
+            .unwrap();
+
+        assert_eq!(
+            handlebars.render("t0", &()).unwrap(),
+            "<head>base head</head><body>base body</body><footer>inline footer</footer>"
+        );
+    }
+
+    #[test]
+    fn test_extend_nested_block() {
+        let mut handlebars = layouts();
+        handlebars
+            .register_template_string(
+                "t0",
+                "{{#extend \"base\"}}{{#if true}}{{#block \"head\"}}if head{{/block}}{{/if}}\
+                 {{#with x}}{{#block \"body\"}}with body{{/block}}{{/with}}{{/extend}}",
+            )
+            .unwrap();
+
+        assert_eq!(
+            handlebars.render("t0", &()).unwrap(),
+            "<head>if head</head><body>with body</body><footer>base footer</footer>"
+        );
+    }
+
+    #[test]
+    fn test_extend_block_and_partial() {
+        let mut handlebars = layouts();
+        handlebars
+            .register_template_string("head", "head partial")
+            .unwrap();
+        handlebars
+            .register_template_string(
+                "t0",
+                "{{#extend \"t1\"}}{{#block \"head\"}}t0 head{{/block}}{{/extend}}",
+            )
+            .unwrap();
+        handlebars
+            .register_template_string("t1", "{{#block \"head\"}}{{/block}}/{{> head}}")
+            .unwrap();
+
+        // blocks don't replace partials of the same name
+        assert_eq!(
+            handlebars.render("t0", &()).unwrap(),
+            "t0 head/head partial"
+        );
+    }
+
+    #[test]
+    fn test_extend_errors() {
+        let mut handlebars = layouts();
+        handlebars
+            .register_template_string("t0", "{{#extend \"missing\"}}{{/extend}}")
+            .unwrap();
+        handlebars
+            .register_template_string("t1", "{{#extend \"t1\"}}{{/extend}}")
+            .unwrap();
+        handlebars
+            .register_template_string("t2", "{{#extend \"base\"}}{{#block}}{{/block}}{{/extend}}")
+            .unwrap();
+        handlebars
+            .register_template_string("t3", "{{#extend \"t4\"}}{{/extend}}")
+            .unwrap();
+        handlebars
+            .register_template_string("t4", "{{#extend \"t5\"}}{{/extend}}")
+            .unwrap();
+        handlebars
+            .register_template_string("t5", "{{#extend \"t3\"}}{{/extend}}")
+            .unwrap();
+
+        assert!(handlebars.render("t0", &()).is_err());
+        assert!(handlebars.render("t1", &()).is_err());
+        assert!(handlebars.render("t2", &()).is_err());
+        assert_eq!(
+            handlebars.render("t3", &()).unwrap_err().desc,
+            "Cannot extend \"t3\", it extends the current template"
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use hashbrown::HashMap;
+
+use crate::context::{keys_path, BlockParams, Context};
+use crate::error::RenderError;
+use crate::helpers::{HelperDef, HelperResult};
+use crate::output::Output;
+use crate::registry::Registry;
+use crate::render::{Helper, RenderContext, Renderable};
+use crate::template::Template;
+use crate::value::{PathAndJson, ScopedJson};
+
+/// Block params binding the names of a hash to its values, shared by `let`
+/// and `*set`
+///
+/// Values of the data are bound by their path, so that they keep their
+/// place in the data when used as the param of blocks like `#each`.
+pub(crate) fn hash_block_params(
+    hash: &HashMap<String, PathAndJson>,
+    rc: &RenderContext,
+) -> Result<BlockParams, RenderError> {
+    let mut params = BlockParams::new();
+    for (name, value) in hash {
+        let path = match (value.scoped_value(), value.path()) {
+            (&ScopedJson::BlockContext(..), Some(p)) if value.is_absolute_path() => Some(p.clone()),
+            (&ScopedJson::Context(_), Some(p)) => Some(keys_path(&rc.resolve_path(p)?)),
+            _ => None,
+        };
+
+        match path {
+            Some(ref p) => params.add_path(name, p)?,
+            None => params.add_value(name, value.value().clone())?,
+        }
+    }
+    Ok(params)
+}
+
+/// Render a block in the scope around it, like the blocks of `#if`, where
+/// the names bound by `*set` end with the block
+pub(crate) fn render_in_scope<'reg: 'rc, 'rc>(
+    t: &'reg Template,
+    r: &'reg Registry,
+    ctx: &'rc Context,
+    rc: &mut RenderContext<'reg>,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let len = rc.block_context_len();
+    let result = t.render(r, ctx, rc, out);
+    while rc.block_context_len() > len {
+        rc.pop_block_context();
+    }
+    result
+}
+
+/// Bind names to values in a block
+///
+/// `{{#let total=(sum prices) author=post.author}}{{total}} by {{author.name}}{{/let}}`
+///
+/// The names are block params, taking precedence over the data.
+#[derive(Clone, Copy)]
+pub struct LetHelper;
+
+impl HelperDef for LetHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        r: &'reg Registry,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        if h.hash().is_empty() {
+            return Err(RenderError::new("Hash required for helper \"let\""));
+        }
+
+        match h.template() {
+            Some(t) => {
+                let mut local_rc = rc.derive();
+                let params = hash_block_params(h.hash(), &local_rc)?;
+                local_rc.push_block_context(params)?;
+                t.render(r, ctx, &mut local_rc, out)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+pub static LET_HELPER: LetHelper = LetHelper;
+
+#[cfg(test)]
+mod test {
+    use crate::registry::Registry;
+
+    #[test]
+    fn test_let() {
+        let mut handlebars = Registry::new();
+        handlebars.register_variable_helpers();
+        handlebars.register_math_helpers();
+        handlebars.register_collection_helpers();
+        let data = json!({
+            "title": "Posts",
+            "prices": [1, 2, 3],
+            "posts": [
+                {"name": "a", "author": {"name": "ann", "tags": ["x", "y"]}},
+                {"name": "b", "author": {"name": "bob", "tags": []}}
+            ]
+        });
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#let total=(sum prices) n=2}}{{total}}/{{n}}{{/let}}",
+                    &data
+                )
+                .unwrap(),
+            "6/2"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#let author=posts.[0].author}}{{author.name}}{{/let}}",
+                    &data
+                )
+                .unwrap(),
+            "ann"
+        );
+        // names take precedence over the data, only in the block
+        assert_eq!(
+            handlebars
+                .render_template("{{#let title=\"Other\"}}{{title}}{{/let}} {{title}}", &data)
+                .unwrap(),
+            "Other Posts"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each posts}}{{#let author=author i=@index}}{{i}}:{{author.name}} {{/let}}{{/each}}",
+                    &data
+                )
+                .unwrap(),
+            "0:ann 1:bob "
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each posts as |post|}}{{#let a=post.author}}{{a.name}} {{/let}}{{/each}}",
+                    &data
+                )
+                .unwrap(),
+            "ann bob "
+        );
+        // values of the data keep their path, so `../` works in blocks
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#let tags=posts.[0].author.tags}}{{#each tags}}{{this}}@{{../title}} {{/each}}{{/let}}",
+                    &data
+                )
+                .unwrap(),
+            "x@Posts y@Posts "
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#let last=(last posts)}}{{#with last}}{{name}}{{/with}}{{/let}}",
+                    &data
+                )
+                .unwrap(),
+            "b"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#let a=1}}{{#let b=(add a 1)}}{{a}}{{b}}{{/let}}{{b}}{{/let}}",
+                    &data
+                )
+                .unwrap(),
+            "12"
+        );
+
+        assert!(handlebars
+            .render_template("{{#let}}x{{/let}}", &data)
+            .is_err());
+    }
+}
@@ -1,55 +1,134 @@
+use std::collections::VecDeque;
+
 use serde_json::value::Value as Json;
 
-use crate::context::Context;
+use crate::context::{resolve_path, Context};
 use crate::error::RenderError;
-use crate::helpers::{HelperDef, HelperResult};
-use crate::output::Output;
+use crate::helpers::HelperDef;
 use crate::registry::Registry;
 use crate::render::{Helper, RenderContext};
-use crate::value::JsonRender;
+use crate::value::{JsonRender, ScopedJson};
 
+/// Lookup helper, `{{lookup collection key}}`
+///
+/// The key is an array index, negative ones counting from the end, an
+/// object key or a path like `"a.b.0"` or `"a/b/-1"`. The value found keeps
+/// referencing the context, so `(lookup ...)` can be the param of `#with`
+/// or `#each`. When nothing is found, the `default` hash value is returned.
 #[derive(Clone, Copy)]
 pub struct LookupHelper;
 
 impl HelperDef for LookupHelper {
-    fn call<'reg: 'rc, 'rc>(
+    fn call_inner<'reg: 'rc, 'rc>(
         &self,
         h: &Helper<'reg, 'rc>,
-        _: &'reg Registry,
+        r: &'reg Registry,
         _: &'rc Context,
-        _: &mut RenderContext<'reg>,
-        out: &mut dyn Output,
-    ) -> HelperResult {
-        let collection_value = h
+        rc: &mut RenderContext<'reg>,
+    ) -> Result<Option<ScopedJson<'reg, 'rc>>, RenderError> {
+        let collection = h
             .param(0)
             .ok_or_else(|| RenderError::new("Param not found for helper \"lookup\""))?;
-        let index = h
+        let key = h
             .param(1)
             .ok_or_else(|| RenderError::new("Insufficient params for helper \"lookup\""))?;
 
-        let null = Json::Null;
-        let value = match *collection_value.value() {
-            Json::Array(ref v) => index
-                .value()
-                .as_u64()
-                .and_then(|u| Some(u as usize))
-                .and_then(|u| v.get(u))
-                .unwrap_or(&null),
-            Json::Object(ref m) => index
-                .value()
-                .as_str()
-                .and_then(|k| m.get(k))
-                .unwrap_or(&null),
-            _ => &null,
+        // the path of the value found, so it can be the context of blocks
+        // like `#with`
+        let base_keys = match *collection.scoped_value() {
+            ScopedJson::BlockContext(_, ref path) => Some(absolute_keys(path)?),
+            ScopedJson::Context(_) => match collection.path() {
+                Some(p) if collection.is_absolute_path() => Some(absolute_keys(p)?),
+                Some(p) => Some(rc.resolve_path(p)?),
+                None => None,
+            },
+            _ => None,
+        };
+        let value_path = |keys: Vec<String>| {
+            base_keys.as_ref().map(|base_keys| {
+                base_keys
+                    .iter()
+                    .chain(keys.iter())
+                    .map(|key| format!("[{}]", key))
+                    .collect::<Vec<_>>()
+                    .join("/")
+            })
+        };
+
+        let key_value = key.value();
+        let result = match *collection.scoped_value() {
+            ScopedJson::Constant(c) => lookup(c, key_value).map(|(v, _)| ScopedJson::Constant(v)),
+            ScopedJson::Context(c) | ScopedJson::BlockContext(c, _) => {
+                lookup(c, key_value).map(|(v, keys)| match value_path(keys) {
+                    Some(path) => ScopedJson::BlockContext(v, path),
+                    None => ScopedJson::Context(v),
+                })
+            }
+            ScopedJson::Derived(ref c) => {
+                lookup(c, key_value).map(|(v, _)| ScopedJson::Derived(v.clone()))
+            }
+            ScopedJson::Missing => None,
         };
-        let r = value.render();
-        out.write(r.as_ref())?;
-        Ok(())
+
+        let result = match result {
+            Some(result) => result,
+            None => match h.hash_get("default") {
+                Some(default) => default.scoped_value().clone(),
+                None if r.strict_mode() => {
+                    let name = match collection.path() {
+                        Some(path) => format!("{}/{}", path, key_value.render()),
+                        None => key_value.render(),
+                    };
+                    return Err(RenderError::strict_error(Some(&name)));
+                }
+                None => ScopedJson::Missing,
+            },
+        };
+        Ok(Some(result))
     }
 }
 
-pub static LOOKUP_HELPER: LookupHelper = LookupHelper;
+fn absolute_keys(path: &str) -> Result<Vec<String>, RenderError> {
+    resolve_path(path, &VecDeque::new(), "this", &VecDeque::new())
+}
 
+/// Finds the value of `key` in `value`, returns it with the keys of the
+/// path to it
+fn lookup<'a>(value: &'a Json, key: &Json) -> Option<(&'a Json, Vec<String>)> {
+    let key = match *key {
+        Json::String(ref s) => s.clone(),
+        Json::Number(ref n) => n.to_string(),
+        _ => return None,
+    };
+    // a key of the collection itself comes before a path
+    if let Some(found) = get_item(value, &key) {
+        return Some((found.0, vec![found.1]));
+    }
+    key.split(&['.', '/'][..])
+        .try_fold((value, Vec::new()), |(value, mut keys), seg| {
+            let (found, key) = get_item(value, seg)?;
+            keys.push(key);
+            Some((found, keys))
+        })
+}
+
+fn get_item<'a>(value: &'a Json, key: &str) -> Option<(&'a Json, String)> {
+    match *value {
+        Json::Array(ref list) => {
+            let idx = key.parse::<i64>().ok()?;
+            let idx = if idx < 0 {
+                list.len().checked_sub(idx.wrapping_neg() as u64 as usize)?
+            } else {
+                idx as usize
+            };
+            list.get(idx).map(|v| (v, idx.to_string()))
+        }
+        Json::Object(ref m) => m.get(key).map(|v| (v, key.to_owned())),
+        _ => None,
+    }
+}
+
+pub static LOOKUP_HELPER: LookupHelper = LookupHelper;
 #[cfg(test)]
 mod test {
     use crate::registry::Registry;
@@ -86,4 +165,136 @@ mod test {
         let r2 = handlebars.render("t2", &m2);
         assert_eq!(r2.ok().unwrap(), "world".to_string());
     }
+
+    #[test]
+    fn test_lookup_path() {
+        let handlebars = Registry::new();
+
+        let data = json!({
+            "a": {"b": [{"n": 1}, {"n": 2}], "c.d": 3},
+            "k": "b",
+            "top": 0
+        });
+        assert_eq!(
+            handlebars
+                .render_template("{{lookup a \"b.1.n\"}}", &data)
+                .unwrap(),
+            "2"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{lookup a \"b/0/n\"}}", &data)
+                .unwrap(),
+            "1"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{lookup a \"c.d\"}}", &data)
+                .unwrap(),
+            "3"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{lookup a.b -1}}", &data)
+                .unwrap(),
+            "[object]"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{lookup (lookup a k) \"-2.n\"}}", &data)
+                .unwrap(),
+            "1"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{lookup a.b 2}}", &data)
+                .unwrap(),
+            ""
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{lookup a.b \"-3\"}}", &data)
+                .unwrap(),
+            ""
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{lookup a \"x\" default=\"none\"}}", &data)
+                .unwrap(),
+            "none"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{lookup a \"b.5\" default=top}}", &data)
+                .unwrap(),
+            "0"
+        );
+    }
+
+    #[test]
+    fn test_lookup_as_context() {
+        let handlebars = Registry::new();
+
+        let data = json!({
+            "a": {"b": [{"n": 1}, {"n": 2}]},
+            "k": "b",
+            "top": 0
+        });
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#with (lookup a \"b.-1\")}}{{n}}{{../top}}{{/with}}",
+                    &data
+                )
+                .unwrap(),
+            "20"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#each (lookup a k)}}{{@index}}{{n}}{{/each}}", &data)
+                .unwrap(),
+            "0112"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each a.b as |item|}}{{#with (lookup item \"n\")}}{{this}}{{/with}}{{/each}}",
+                    &data
+                )
+                .unwrap(),
+            "12"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#with (lookup a \"x\")}}yes{{else}}no{{/with}}", &data)
+                .unwrap(),
+            "no"
+        );
+    }
+
+    #[test]
+    fn test_lookup_strict() {
+        let mut handlebars = Registry::new();
+        handlebars.set_strict_mode(true);
+
+        let data = json!({"a": {"b": 1}});
+        assert_eq!(
+            handlebars
+                .render_template("{{lookup a \"b\"}}", &data)
+                .unwrap(),
+            "1"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{lookup a \"c\" default=2}}", &data)
+                .unwrap(),
+            "2"
+        );
+        assert!(handlebars
+            .render_template("{{lookup a \"c\"}}", &data)
+            .is_err());
+        assert!(handlebars
+            .render_template("{{#if (lookup a \"c\")}}yes{{/if}}", &data)
+            .is_err());
+    }
 }
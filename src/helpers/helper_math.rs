@@ -0,0 +1,469 @@
+//! Helpers for arithmetic
+//!
+//! Results of integers are integers as long as they fit in `i64`, other
+//! results are `f64`.
+
+use serde_json::value::{Number, Value as Json};
+
+use crate::context::Context;
+use crate::error::RenderError;
+use crate::helpers::HelperDef;
+use crate::registry::Registry;
+use crate::render::{Helper, RenderContext};
+use crate::value::ScopedJson;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Num {
+    Int(i64),
+    Float(f64),
+}
+
+impl Num {
+    fn from_json(value: &Json) -> Option<Num> {
+        match value.as_i64() {
+            Some(i) => Some(Num::Int(i)),
+            None => value.as_f64().map(Num::Float),
+        }
+    }
+
+    /// Integral floats, like results of `floor`, as integers when they fit
+    fn from_integral(f: f64) -> Num {
+        if f >= std::i64::MIN as f64 && f < std::i64::MAX as f64 {
+            Num::Int(f as i64)
+        } else {
+            Num::Float(f)
+        }
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Num::Int(i) => i as f64,
+            Num::Float(f) => f,
+        }
+    }
+
+    fn is_zero(self) -> bool {
+        self.as_f64() == 0.0
+    }
+
+    fn apply(
+        self,
+        other: Num,
+        int_op: fn(i64, i64) -> Option<i64>,
+        float_op: fn(f64, f64) -> f64,
+    ) -> Num {
+        match (self, other) {
+            (Num::Int(a), Num::Int(b)) => int_op(a, b)
+                .map(Num::Int)
+                .unwrap_or_else(|| Num::Float(float_op(a as f64, b as f64))),
+            (a, b) => Num::Float(float_op(a.as_f64(), b.as_f64())),
+        }
+    }
+
+    fn into_json(self, h: &Helper) -> Result<Json, RenderError> {
+        match self {
+            Num::Int(i) => Ok(Json::from(i)),
+            Num::Float(f) => Number::from_f64(f).map(Json::Number).ok_or_else(|| {
+                RenderError::new(format!(
+                    "Result of helper \"{}\" is not a finite number",
+                    h.name()
+                ))
+            }),
+        }
+    }
+}
+
+fn number(h: &Helper, idx: usize) -> Result<Num, RenderError> {
+    let param = h.param(idx).ok_or_else(|| {
+        RenderError::new(format!("Insufficient params for helper \"{}\"", h.name()))
+    })?;
+    Num::from_json(param.value()).ok_or_else(|| not_a_number(h, idx, param.value()))
+}
+
+fn not_a_number(h: &Helper, idx: usize, value: &Json) -> RenderError {
+    RenderError::new(format!(
+        "Param {} of helper \"{}\" is not a number: {}",
+        idx,
+        h.name(),
+        value
+    ))
+}
+
+/// All params, with the items of array params
+fn numbers(h: &Helper) -> Result<Vec<Num>, RenderError> {
+    let mut numbers = Vec::new();
+    for (idx, param) in h.params().iter().enumerate() {
+        match *param.value() {
+            Json::Array(ref items) => {
+                for item in items {
+                    numbers.push(Num::from_json(item).ok_or_else(|| not_a_number(h, idx, item))?);
+                }
+            }
+            ref value => {
+                numbers.push(Num::from_json(value).ok_or_else(|| not_a_number(h, idx, value))?)
+            }
+        }
+    }
+    Ok(numbers)
+}
+
+/// Folds two or more params
+fn fold(
+    h: &Helper,
+    int_op: fn(i64, i64) -> Option<i64>,
+    float_op: fn(f64, f64) -> f64,
+) -> Result<Num, RenderError> {
+    let mut result = number(h, 0)?;
+    for idx in 1..h.params().len().max(2) {
+        result = result.apply(number(h, idx)?, int_op, float_op);
+    }
+    Ok(result)
+}
+
+fn divisor(h: &Helper) -> Result<Num, RenderError> {
+    let divisor = number(h, 1)?;
+    if divisor.is_zero() {
+        Err(RenderError::new(format!(
+            "Division by zero in helper \"{}\"",
+            h.name()
+        )))
+    } else {
+        Ok(divisor)
+    }
+}
+
+fn add(h: &Helper) -> Result<Num, RenderError> {
+    fold(h, i64::checked_add, |a, b| a + b)
+}
+
+fn sub(h: &Helper) -> Result<Num, RenderError> {
+    Ok(number(h, 0)?.apply(number(h, 1)?, i64::checked_sub, |a, b| a - b))
+}
+
+fn mul(h: &Helper) -> Result<Num, RenderError> {
+    fold(h, i64::checked_mul, |a, b| a * b)
+}
+
+fn div(h: &Helper) -> Result<Num, RenderError> {
+    let divisor = divisor(h)?;
+    // integers divisible without a remainder stay integers
+    Ok(number(h, 0)?.apply(
+        divisor,
+        |a, b| a.checked_rem(b).filter(|r| *r == 0).and(a.checked_div(b)),
+        |a, b| a / b,
+    ))
+}
+
+fn modulo(h: &Helper) -> Result<Num, RenderError> {
+    let divisor = divisor(h)?;
+    // `i64::MIN % -1` overflows, but its remainder is 0
+    Ok(number(h, 0)?.apply(
+        divisor,
+        |a, b| Some(a.checked_rem(b).unwrap_or_else(|| a.wrapping_rem(b))),
+        |a, b| a % b,
+    ))
+}
+
+fn abs(h: &Helper) -> Result<Num, RenderError> {
+    Ok(match number(h, 0)? {
+        Num::Int(i) => i
+            .checked_abs()
+            .map(Num::Int)
+            .unwrap_or_else(|| Num::Float((i as f64).abs())),
+        Num::Float(f) => Num::Float(f.abs()),
+    })
+}
+
+fn extremum(h: &Helper, max: bool) -> Result<Num, RenderError> {
+    numbers(h)?
+        .into_iter()
+        .fold(None, |result: Option<Num>, n| match result {
+            Some(r) if max && r.as_f64() >= n.as_f64() => Some(r),
+            Some(r) if !max && r.as_f64() <= n.as_f64() => Some(r),
+            _ => Some(n),
+        })
+        .ok_or_else(|| RenderError::new(format!("No numbers for helper \"{}\"", h.name())))
+}
+
+fn min(h: &Helper) -> Result<Num, RenderError> {
+    extremum(h, false)
+}
+
+fn max(h: &Helper) -> Result<Num, RenderError> {
+    extremum(h, true)
+}
+
+fn sum(h: &Helper) -> Result<Num, RenderError> {
+    Ok(numbers(h)?.into_iter().fold(Num::Int(0), |sum, n| {
+        sum.apply(n, i64::checked_add, |a, b| a + b)
+    }))
+}
+
+/// `{{round n}}` rounds to an integer, `{{round n 2}}` to 2 decimal places
+fn round(h: &Helper) -> Result<Num, RenderError> {
+    let n = number(h, 0)?;
+    let digits = match h.param(1) {
+        Some(digits) => Some(digits.value().as_u64().ok_or_else(|| {
+            RenderError::new(format!(
+                "Param 1 of helper \"{}\" must be a non-negative integer",
+                h.name()
+            ))
+        })?),
+        None => None,
+    };
+    match (n, digits) {
+        (Num::Int(_), _) => Ok(n),
+        (Num::Float(f), None) => Ok(Num::from_integral(f.round())),
+        (Num::Float(f), Some(digits)) => {
+            let scale = 10f64.powi(digits.min(std::i32::MAX as u64) as i32);
+            let scaled = f * scale;
+            // more digits than a float has leave it as it is
+            if scaled.is_finite() {
+                Ok(Num::Float(scaled.round() / scale))
+            } else {
+                Ok(n)
+            }
+        }
+    }
+}
+
+fn floor(h: &Helper) -> Result<Num, RenderError> {
+    Ok(match number(h, 0)? {
+        Num::Float(f) => Num::from_integral(f.floor()),
+        n => n,
+    })
+}
+
+fn ceil(h: &Helper) -> Result<Num, RenderError> {
+    Ok(match number(h, 0)? {
+        Num::Float(f) => Num::from_integral(f.ceil()),
+        n => n,
+    })
+}
+
+/// An arithmetic helper, returning a number computed from its params
+#[derive(Clone, Copy)]
+pub struct MathHelper(fn(&Helper) -> Result<Num, RenderError>);
+
+impl HelperDef for MathHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Registry,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg>,
+    ) -> Result<Option<ScopedJson<'reg, 'rc>>, RenderError> {
+        let result = (self.0)(h)?.into_json(h)?;
+        Ok(Some(ScopedJson::Derived(result)))
+    }
+}
+
+pub static ADD_HELPER: MathHelper = MathHelper(add);
+pub static SUB_HELPER: MathHelper = MathHelper(sub);
+pub static MUL_HELPER: MathHelper = MathHelper(mul);
+pub static DIV_HELPER: MathHelper = MathHelper(div);
+pub static MOD_HELPER: MathHelper = MathHelper(modulo);
+pub static ABS_HELPER: MathHelper = MathHelper(abs);
+pub static MIN_HELPER: MathHelper = MathHelper(min);
+pub static MAX_HELPER: MathHelper = MathHelper(max);
+pub static ROUND_HELPER: MathHelper = MathHelper(round);
+pub static FLOOR_HELPER: MathHelper = MathHelper(floor);
+pub static CEIL_HELPER: MathHelper = MathHelper(ceil);
+pub static SUM_HELPER: MathHelper = MathHelper(sum);
+
+#[cfg(test)]
+mod test {
+    use crate::registry::Registry;
+
+    #[test]
+    fn test_math_helpers() {
+        let mut handlebars = Registry::new();
+        handlebars.register_math_helpers();
+
+        let data = json!({
+            "list": [1, 2, 3],
+            "prices": [1.5, 2.25],
+            "empty": [],
+            "big": 9_223_372_036_854_775_807i64,
+            "small": -9_223_372_036_854_775_808i64
+        });
+        assert_eq!(
+            handlebars.render_template("{{add 1 2}}", &data).unwrap(),
+            "3"
+        );
+        assert_eq!(
+            handlebars.render_template("{{add 1 2 3}}", &data).unwrap(),
+            "6"
+        );
+        assert_eq!(
+            handlebars.render_template("{{add 1 0.5}}", &data).unwrap(),
+            "1.5"
+        );
+        assert_eq!(
+            handlebars.render_template("{{add big 1}}", &data).unwrap(),
+            "9.223372036854776e+18"
+        );
+        assert_eq!(
+            handlebars.render_template("{{sub 1 3}}", &data).unwrap(),
+            "-2"
+        );
+        assert_eq!(
+            handlebars.render_template("{{mul 4 -2}}", &data).unwrap(),
+            "-8"
+        );
+        assert_eq!(
+            handlebars.render_template("{{mul 1.5 2}}", &data).unwrap(),
+            "3.0"
+        );
+        assert_eq!(
+            handlebars.render_template("{{div 6 3}}", &data).unwrap(),
+            "2"
+        );
+        assert_eq!(
+            handlebars.render_template("{{div 7 2}}", &data).unwrap(),
+            "3.5"
+        );
+        assert_eq!(
+            handlebars.render_template("{{mod 7 3}}", &data).unwrap(),
+            "1"
+        );
+        assert_eq!(
+            handlebars.render_template("{{mod -7 3}}", &data).unwrap(),
+            "-1"
+        );
+        assert_eq!(
+            handlebars.render_template("{{mod 7.5 2}}", &data).unwrap(),
+            "1.5"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{mod small -1}}", &data)
+                .unwrap(),
+            "0"
+        );
+        assert_eq!(
+            handlebars.render_template("{{abs -4}}", &data).unwrap(),
+            "4"
+        );
+        assert_eq!(
+            handlebars.render_template("{{abs -0.5}}", &data).unwrap(),
+            "0.5"
+        );
+        assert_eq!(
+            handlebars.render_template("{{min 3 1 2}}", &data).unwrap(),
+            "1"
+        );
+        assert_eq!(
+            handlebars.render_template("{{max list}}", &data).unwrap(),
+            "3"
+        );
+        assert_eq!(
+            handlebars.render_template("{{max 1 2.5}}", &data).unwrap(),
+            "2.5"
+        );
+        assert_eq!(
+            handlebars.render_template("{{round 2.5}}", &data).unwrap(),
+            "3"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{round 2.345 2}}", &data)
+                .unwrap(),
+            "2.35"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{round 1.5 400}}", &data)
+                .unwrap(),
+            "1.5"
+        );
+        assert_eq!(
+            handlebars.render_template("{{round 4}}", &data).unwrap(),
+            "4"
+        );
+        assert_eq!(
+            handlebars.render_template("{{floor -1.5}}", &data).unwrap(),
+            "-2"
+        );
+        assert_eq!(
+            handlebars.render_template("{{ceil 1.2}}", &data).unwrap(),
+            "2"
+        );
+        assert_eq!(
+            handlebars.render_template("{{sum list}}", &data).unwrap(),
+            "6"
+        );
+        assert_eq!(
+            handlebars.render_template("{{sum prices}}", &data).unwrap(),
+            "3.75"
+        );
+        assert_eq!(
+            handlebars.render_template("{{sum list 4}}", &data).unwrap(),
+            "10"
+        );
+        assert_eq!(
+            handlebars.render_template("{{sum empty}}", &data).unwrap(),
+            "0"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#each list}}{{add @index 1}}.{{/each}}", &data)
+                .unwrap(),
+            "1.2.3."
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#if (gt (mul 2 3) 5)}}yes{{/if}}", &data)
+                .unwrap(),
+            "yes"
+        );
+    }
+
+    #[test]
+    fn test_math_helper_errors() {
+        let mut handlebars = Registry::new();
+        handlebars.register_math_helpers();
+
+        let data = json!({"s": "a", "list": [1, "b"]});
+        assert!(handlebars
+            .render_template("{{div 1 0}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("Division by zero in helper \"div\""));
+        assert!(handlebars
+            .render_template("{{mod 1 0.0}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("Division by zero in helper \"mod\""));
+        assert!(handlebars
+            .render_template("{{round 1.5 -1}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("Param 1 of helper \"round\" must be a non-negative integer"));
+        assert!(handlebars
+            .render_template("{{add 1 s}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("Param 1 of helper \"add\" is not a number: \"a\""));
+        assert!(handlebars
+            .render_template("{{abs missing}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("Param 0 of helper \"abs\" is not a number: null"));
+        assert!(handlebars
+            .render_template("{{sum list}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("Param 0 of helper \"sum\" is not a number: \"b\""));
+        assert!(handlebars
+            .render_template("{{sub 1}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("Insufficient params for helper \"sub\""));
+        assert!(handlebars
+            .render_template("{{max}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("No numbers for helper \"max\""));
+    }
+}
@@ -0,0 +1,511 @@
+//! Helpers formatting numbers for a locale, registered by
+//! `Registry::register_number_helpers`
+//!
+//! The locale is the `locale` hash param of a helper, or the one set on the
+//! render with the `{{*locale "de-DE"}}` decorator, `en-US` by default. The
+//! locale data of common locales is bundled, taken from CLDR.
+
+use serde_json::value::Value as Json;
+
+use crate::context::Context;
+use crate::error::RenderError;
+use crate::helpers::HelperDef;
+use crate::registry::Registry;
+use crate::render::{Helper, RenderContext};
+use crate::value::{JsonRender, ScopedJson};
+
+const DEFAULT_LOCALE: &str = "en-US";
+// the most fraction digits the `precision` hash param takes
+const MAX_PRECISION: usize = 100;
+
+/// Number format of a locale
+#[derive(Debug)]
+pub(crate) struct NumberLocale {
+    tag: &'static str,
+    decimal: &'static str,
+    group: &'static str,
+    /// groups of 2 digits after the first 3, like `12,34,567`
+    indian_grouping: bool,
+    /// numbers of 4 digits are not grouped, like `1234`
+    min_grouping_two: bool,
+    currency: &'static str,
+    /// `¤` is replaced with the currency symbol and `#` with the number
+    currency_pattern: &'static str,
+    percent_pattern: &'static str,
+}
+
+macro_rules! locale {
+    ($tag:expr, $decimal:expr, $group:expr, $indian:expr, $min_two:expr,
+     $currency:expr, $currency_pattern:expr, $percent_pattern:expr) => {
+        NumberLocale {
+            tag: $tag,
+            decimal: $decimal,
+            group: $group,
+            indian_grouping: $indian,
+            min_grouping_two: $min_two,
+            currency: $currency,
+            currency_pattern: $currency_pattern,
+            percent_pattern: $percent_pattern,
+        }
+    };
+}
+
+// tag, decimal, group, indian grouping, min grouping of two, currency,
+// currency pattern, percent pattern
+#[rustfmt::skip]
+static LOCALES: &[NumberLocale] = &[
+    locale!("en-US", ".", ",",        false, false, "USD", "¤#",       "#%"),
+    locale!("en-GB", ".", ",",        false, false, "GBP", "¤#",       "#%"),
+    locale!("en-IN", ".", ",",        true,  false, "INR", "¤#",       "#%"),
+    locale!("hi-IN", ".", ",",        true,  false, "INR", "¤#",       "#%"),
+    locale!("de-DE", ",", ".",        false, false, "EUR", "#\u{a0}¤", "#\u{a0}%"),
+    locale!("de-AT", ",", "\u{a0}",   false, false, "EUR", "¤\u{a0}#", "#\u{a0}%"),
+    locale!("de-CH", ".", "’",        false, false, "CHF", "¤\u{a0}#", "#%"),
+    locale!("fr-FR", ",", "\u{202f}", false, false, "EUR", "#\u{a0}¤", "#\u{202f}%"),
+    locale!("es-ES", ",", ".",        false, true,  "EUR", "#\u{a0}¤", "#\u{a0}%"),
+    locale!("it-IT", ",", ".",        false, false, "EUR", "#\u{a0}¤", "#%"),
+    locale!("nl-NL", ",", ".",        false, false, "EUR", "¤\u{a0}#", "#%"),
+    locale!("pt-BR", ",", ".",        false, false, "BRL", "¤\u{a0}#", "#%"),
+    locale!("pt-PT", ",", "\u{a0}",   false, true,  "EUR", "#\u{a0}¤", "#%"),
+    locale!("pl-PL", ",", "\u{a0}",   false, true,  "PLN", "#\u{a0}¤", "#%"),
+    locale!("ru-RU", ",", "\u{a0}",   false, false, "RUB", "#\u{a0}¤", "#\u{a0}%"),
+    locale!("sv-SE", ",", "\u{a0}",   false, false, "SEK", "#\u{a0}¤", "#\u{a0}%"),
+    locale!("ja-JP", ".", ",",        false, false, "JPY", "¤#",       "#%"),
+    locale!("zh-CN", ".", ",",        false, false, "CNY", "¤#",       "#%"),
+    locale!("ko-KR", ".", ",",        false, false, "KRW", "¤#",       "#%"),
+];
+
+/// Currency codes with their symbols and fraction digits
+static CURRENCIES: &[(&str, &str, usize)] = &[
+    ("USD", "$", 2),
+    ("EUR", "€", 2),
+    ("GBP", "£", 2),
+    ("JPY", "¥", 0),
+    ("CNY", "¥", 2),
+    ("KRW", "₩", 0),
+    ("INR", "₹", 2),
+    ("CHF", "CHF", 2),
+    ("BRL", "R$", 2),
+    ("PLN", "zł", 2),
+    ("RUB", "₽", 2),
+    ("SEK", "kr", 2),
+    ("CAD", "CA$", 2),
+    ("AUD", "A$", 2),
+];
+
+/// Finds the data of a locale like `de-DE` or `de_DE`, or of the first
+/// bundled locale of its language like `de`
+pub(crate) fn find_locale(tag: &str) -> Option<&'static NumberLocale> {
+    let tag = tag.replace('_', "-");
+    LOCALES
+        .iter()
+        .find(|l| l.tag.eq_ignore_ascii_case(&tag))
+        .or_else(|| {
+            let language = tag.split('-').next().unwrap_or("");
+            LOCALES.iter().find(|l| {
+                l.tag
+                    .split('-')
+                    .next()
+                    .unwrap_or("")
+                    .eq_ignore_ascii_case(language)
+            })
+        })
+}
+
+#[derive(Clone, Copy)]
+enum Amount {
+    Int(i128),
+    Float(f64),
+}
+
+impl Amount {
+    fn from_json(value: &Json) -> Option<Amount> {
+        value
+            .as_i64()
+            .map(|i| Amount::Int(i128::from(i)))
+            .or_else(|| value.as_u64().map(|u| Amount::Int(i128::from(u))))
+            .or_else(|| value.as_f64().map(Amount::Float))
+    }
+
+    fn percent(self) -> Amount {
+        match self {
+            Amount::Int(i) => Amount::Int(i * 100),
+            Amount::Float(f) => Amount::Float(f * 100.0),
+        }
+    }
+
+    /// The sign, integer digits and fraction digits, with `precision`
+    /// fraction digits or up to `max_fraction` without trailing zeros
+    fn digits(self, precision: Option<usize>, max_fraction: usize) -> (bool, String, String) {
+        let (negative, digits) = match self {
+            Amount::Int(i) => {
+                let fraction = "0".repeat(precision.unwrap_or(0));
+                (i < 0, format!("{}.{}", i.abs(), fraction))
+            }
+            Amount::Float(f) => {
+                let digits = format!("{:.*}", precision.unwrap_or(max_fraction), f.abs());
+                (f < 0.0, digits)
+            }
+        };
+        let mut parts = digits.splitn(2, '.');
+        let int = parts.next().unwrap_or("0").to_owned();
+        let mut fraction = parts.next().unwrap_or("").to_owned();
+        if precision.is_none() {
+            fraction.truncate(fraction.trim_end_matches('0').len());
+        }
+        // no sign when it rounds to zero
+        let negative = negative && (int.chars().chain(fraction.chars())).any(|c| c != '0');
+        (negative, int, fraction)
+    }
+}
+
+impl NumberLocale {
+    fn group(&self, int: &str) -> String {
+        if int.len() <= 3 || (self.min_grouping_two && int.len() <= 4) {
+            return int.to_owned();
+        }
+        let (head, last) = int.split_at(int.len() - 3);
+        let size = if self.indian_grouping { 2 } else { 3 };
+        let mut groups = vec![last];
+        let mut rest = head;
+        while rest.len() > size {
+            let (head, group) = rest.split_at(rest.len() - size);
+            groups.push(group);
+            rest = head;
+        }
+        groups.push(rest);
+        groups.reverse();
+        groups.join(self.group)
+    }
+
+    fn number(&self, int: &str, fraction: &str, grouping: bool) -> String {
+        let int = if grouping {
+            self.group(int)
+        } else {
+            int.to_owned()
+        };
+        if fraction.is_empty() {
+            int
+        } else {
+            format!("{}{}{}", int, self.decimal, fraction)
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Style {
+    Number,
+    Percent,
+    Currency,
+}
+
+/// Number formatting helpers
+///
+/// * `{{format_number n}}`: `1,234.5`, up to 3 fraction digits
+/// * `{{format_percent n}}`: `25%` for `0.25`
+/// * `{{format_currency n}}`: `$1,234.50`, in the currency of the locale or
+///   the `currency` hash param, like `currency="EUR"`
+///
+/// The `precision` hash param sets the number of fraction digits, at most
+/// 100, `grouping=false` removes the thousands separators and `locale` sets the
+/// locale.
+#[derive(Clone, Copy)]
+pub struct FormatNumberHelper {
+    style: Style,
+}
+
+impl HelperDef for FormatNumberHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Registry,
+        _: &'rc Context,
+        rc: &mut RenderContext<'reg>,
+    ) -> Result<Option<ScopedJson<'reg, 'rc>>, RenderError> {
+        let value = h.param(0).ok_or_else(|| {
+            RenderError::new(format!("Param not found for helper \"{}\"", h.name()))
+        })?;
+        let amount = Amount::from_json(value.value()).ok_or_else(|| {
+            RenderError::new(format!(
+                "Param 0 of helper \"{}\" is not a number: {}",
+                h.name(),
+                value.value()
+            ))
+        })?;
+
+        let tag = match h.hash_get("locale") {
+            Some(locale) => locale.value().render(),
+            None => rc.get_locale().unwrap_or(DEFAULT_LOCALE).to_owned(),
+        };
+        let locale = find_locale(&tag).ok_or_else(|| {
+            RenderError::new(format!(
+                "Unknown locale \"{}\" in helper \"{}\"",
+                tag,
+                h.name()
+            ))
+        })?;
+        let precision = match h.hash_get("precision") {
+            Some(precision) => {
+                let precision = precision.value().as_u64().ok_or_else(|| {
+                    RenderError::new(format!(
+                        "Hash \"precision\" of helper \"{}\" must be a non-negative integer",
+                        h.name()
+                    ))
+                })?;
+                if precision > MAX_PRECISION as u64 {
+                    return Err(RenderError::new(format!(
+                        "Hash \"precision\" of helper \"{}\" must be at most {}",
+                        h.name(),
+                        MAX_PRECISION
+                    )));
+                }
+                Some(precision as usize)
+            }
+            None => None,
+        };
+        let grouping = h.hash_get("grouping").and_then(|g| g.value().as_bool()) != Some(false);
+
+        let formatted = match self.style {
+            Style::Number => {
+                let (negative, int, fraction) = amount.digits(precision, 3);
+                sign(negative, locale.number(&int, &fraction, grouping))
+            }
+            Style::Percent => {
+                let (negative, int, fraction) = amount.percent().digits(precision, 0);
+                let number = locale.number(&int, &fraction, grouping);
+                sign(negative, locale.percent_pattern.replace('#', &number))
+            }
+            Style::Currency => {
+                let code = match h.hash_get("currency") {
+                    Some(currency) => currency.value().render().to_uppercase(),
+                    None => locale.currency.to_owned(),
+                };
+                let (symbol, digits) = CURRENCIES
+                    .iter()
+                    .find(|c| c.0 == code)
+                    .map_or((code.as_str(), 2), |c| (c.1, c.2));
+                let (negative, int, fraction) =
+                    amount.digits(Some(precision.unwrap_or(digits)), digits);
+                let number = locale.number(&int, &fraction, grouping);
+                let formatted = locale
+                    .currency_pattern
+                    .replace('#', &number)
+                    .replace('¤', symbol);
+                sign(negative, formatted)
+            }
+        };
+        Ok(Some(ScopedJson::Derived(Json::String(formatted))))
+    }
+}
+
+fn sign(negative: bool, formatted: String) -> String {
+    if negative {
+        format!("-{}", formatted)
+    } else {
+        formatted
+    }
+}
+
+pub static FORMAT_NUMBER_HELPER: FormatNumberHelper = FormatNumberHelper {
+    style: Style::Number,
+};
+pub static FORMAT_PERCENT_HELPER: FormatNumberHelper = FormatNumberHelper {
+    style: Style::Percent,
+};
+pub static FORMAT_CURRENCY_HELPER: FormatNumberHelper = FormatNumberHelper {
+    style: Style::Currency,
+};
+
+#[cfg(test)]
+mod test {
+    use crate::registry::Registry;
+
+    #[test]
+    fn test_format_number() {
+        let mut handlebars = Registry::new();
+        handlebars.register_number_helpers();
+
+        let data =
+            json!({"n": 1234567.891, "i": -1234, "small": 0.256, "big": 18446744073709551615u64});
+        assert_eq!(
+            handlebars
+                .render_template("{{format_number n}}", &data)
+                .unwrap(),
+            "1,234,567.891"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_number n precision=1}}", &data)
+                .unwrap(),
+            "1,234,567.9"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_number i precision=2}}", &data)
+                .unwrap(),
+            "-1,234.00"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_number big}}", &data)
+                .unwrap(),
+            "18,446,744,073,709,551,615"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_number 1.5}}", &data)
+                .unwrap(),
+            "1.5"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_number -0.0001 precision=2}}", &data)
+                .unwrap(),
+            "0.00"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_number n grouping=false}}", &data)
+                .unwrap(),
+            "1234567.891"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_number n locale=\"de-DE\"}}", &data)
+                .unwrap(),
+            "1.234.567,891"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_number n locale=\"de\"}}", &data)
+                .unwrap(),
+            "1.234.567,891"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_number n locale=\"fr_FR\"}}", &data)
+                .unwrap(),
+            "1\u{202f}234\u{202f}567,891"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_number n locale=\"de-CH\"}}", &data)
+                .unwrap(),
+            "1’234’567.891"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_number n locale=\"en-IN\"}}", &data)
+                .unwrap(),
+            "12,34,567.891"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_number 1234 locale=\"es-ES\"}}", &data)
+                .unwrap(),
+            "1234"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_number 12345 locale=\"es-ES\"}}", &data)
+                .unwrap(),
+            "12.345"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_percent small}}", &data)
+                .unwrap(),
+            "26%"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_percent small precision=1}}", &data)
+                .unwrap(),
+            "25.6%"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_percent 1}}", &data)
+                .unwrap(),
+            "100%"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_percent small locale=\"de-DE\"}}", &data)
+                .unwrap(),
+            "26\u{a0}%"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_currency n}}", &data)
+                .unwrap(),
+            "$1,234,567.89"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_currency i}}", &data)
+                .unwrap(),
+            "-$1,234.00"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_currency n locale=\"de-DE\"}}", &data)
+                .unwrap(),
+            "1.234.567,89\u{a0}€"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_currency n locale=\"nl-NL\"}}", &data)
+                .unwrap(),
+            "€\u{a0}1.234.567,89"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_currency n currency=\"jpy\"}}", &data)
+                .unwrap(),
+            "¥1,234,568"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_currency 5 currency=\"XYZ\"}}", &data)
+                .unwrap(),
+            "XYZ5.00"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{format_currency 5 precision=0}}", &data)
+                .unwrap(),
+            "$5"
+        );
+    }
+
+    #[test]
+    fn test_format_number_errors() {
+        let mut handlebars = Registry::new();
+        handlebars.register_number_helpers();
+
+        let data = json!({"s": "abc"});
+        assert!(handlebars
+            .render_template("{{format_number s}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("Param 0 of helper \"format_number\" is not a number: \"abc\""));
+        assert!(handlebars
+            .render_template("{{format_currency 1 locale=\"xx\"}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown locale \"xx\" in helper \"format_currency\""));
+        assert!(handlebars
+            .render_template("{{format_percent 1 precision=-1}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains(
+                "Hash \"precision\" of helper \"format_percent\" must be a non-negative integer"
+            ));
+        assert!(handlebars
+            .render_template("{{format_number 1 precision=1000000000}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("Hash \"precision\" of helper \"format_number\" must be at most 100"));
+    }
+}
@@ -0,0 +1,223 @@
+use serde_json::value::Value as Json;
+
+use crate::context::Context;
+use crate::helpers::{HelperDef, HelperResult};
+use crate::output::Output;
+use crate::registry::Registry;
+use crate::render::{Helper, RenderContext, Renderable};
+use crate::value::{to_json, JsonTruthy};
+
+/// Section, the block of a name that is not a helper, like the
+/// `blockHelperMissing` of Handlebars.js
+///
+/// Arrays are iterated, other truthy values are the context of the block and
+/// falsy ones render the inverse. In Mustache mode, the name is also looked up
+/// in the enclosing contexts.
+///
+/// Blocks that are not helpers are sections in Mustache mode. Register it as
+/// `blockHelperMissing` to render them as sections in Handlebars mode too:
+///
+/// ```
+/// use handlebars::{Handlebars, SectionHelper};
+/// use std::collections::BTreeMap;
+///
+/// let mut handlebars = Handlebars::new();
+/// handlebars.register_helper("blockHelperMissing", Box::new(SectionHelper));
+///
+/// let mut data = BTreeMap::new();
+/// data.insert("items", vec![1, 2]);
+/// let output = handlebars.render_template("{{#items}}{{this}} {{/items}}", &data);
+/// assert_eq!(output.unwrap(), "1 2 ");
+/// ```
+#[derive(Clone, Copy)]
+pub struct SectionHelper;
+
+impl HelperDef for SectionHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        r: &'reg Registry,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let section = if r.mustache_mode() {
+            rc.evaluate_in_context_stack(ctx, h.name())
+        } else {
+            rc.evaluate(ctx, h.name())
+                .ok()
+                .filter(|value| !value.is_missing())
+                .map(|value| (format!("{}/{}", rc.get_path(), h.name()), value))
+        }
+        .filter(|(_, value)| value.as_json().is_truthy(false));
+
+        let (path, value) = match section {
+            Some(section) => section,
+            None => {
+                return match h.inverse() {
+                    Some(t) => t.render(r, ctx, rc, out),
+                    None => Ok(()),
+                };
+            }
+        };
+
+        let template = match h.template() {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+
+        // `../` goes up from the path root to the context of the block, while
+        // Mustache lookups search the contexts of the enclosing sections
+        let path_root = if r.mustache_mode() {
+            rc.get_path().clone()
+        } else {
+            path.clone()
+        };
+
+        rc.promote_local_vars();
+        let mut local_rc = rc.derive();
+
+        let result = match *value.as_json() {
+            // `true` keeps the current context
+            Json::Bool(true) if !r.mustache_mode() => template.render(r, ctx, &mut local_rc, out),
+            Json::Array(ref list) => {
+                local_rc.push_local_path_root(path_root);
+                (0..list.len()).try_for_each(|i| {
+                    local_rc.set_local_var("@first".to_string(), to_json(i == 0));
+                    local_rc.set_local_var("@last".to_string(), to_json(i == list.len() - 1));
+                    local_rc.set_local_var("@index".to_string(), to_json(i));
+                    local_rc.set_path(format!("{}/[{}]", path, i));
+                    template.render(r, ctx, &mut local_rc, out)
+                })
+            }
+            _ => {
+                local_rc.push_local_path_root(path_root);
+                local_rc.set_path(path);
+                template.render(r, ctx, &mut local_rc, out)
+            }
+        };
+
+        rc.demote_local_vars();
+        result
+    }
+}
+
+pub static SECTION_HELPER: SectionHelper = SectionHelper;
+
+#[cfg(test)]
+mod test {
+    use crate::registry::Registry;
+
+    #[test]
+    fn test_section() {
+        let mut handlebars = Registry::new();
+        handlebars.set_mustache_mode(true);
+
+        let data = json!({
+            "list": [{"n": 1}, {"n": 2}],
+            "obj": {"n": 3},
+            "flag": true,
+            "empty": [],
+            "n": 0
+        });
+        assert_eq!(
+            handlebars
+                .render_template("{{#list}}{{n}},{{/list}}", &data)
+                .unwrap(),
+            "1,2,"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#obj}}{{n}}{{/obj}}", &data)
+                .unwrap(),
+            "3"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#flag}}{{n}}{{/flag}}", &data)
+                .unwrap(),
+            "0"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#empty}}yes{{/empty}}", &data)
+                .unwrap(),
+            ""
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{^empty}}no{{/empty}}", &data)
+                .unwrap(),
+            "no"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#missing}}yes{{else}}no{{/missing}}", &data)
+                .unwrap(),
+            "no"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#obj}}{{#flag}}{{n}}{{/flag}}{{/obj}}", &data)
+                .unwrap(),
+            "3"
+        );
+    }
+
+    #[test]
+    fn test_section_not_in_handlebars_mode() {
+        let handlebars = Registry::new();
+        assert!(handlebars
+            .render_template("{{#flag}}yes{{/flag}}", &json!({"flag": true}))
+            .is_err());
+    }
+
+    #[test]
+    fn test_section_as_block_helper_missing() {
+        let mut handlebars = Registry::new();
+        handlebars.register_helper("blockHelperMissing", Box::new(super::SectionHelper));
+
+        let data = json!({
+            "list": [{"n": 1}, {"n": 2}],
+            "obj": {"m": 3},
+            "flag": true,
+            "n": 0
+        });
+        assert_eq!(
+            handlebars
+                .render_template("{{#list}}{{@index}}:{{n}},{{/list}}", &data)
+                .unwrap(),
+            "0:1,1:2,"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#list}}{{../n}}{{/list}}", &data)
+                .unwrap(),
+            "00"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#obj}}{{m}}{{n}}{{/obj}}", &data)
+                .unwrap(),
+            "3"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#flag}}{{n}}{{/flag}}", &data)
+                .unwrap(),
+            "0"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#missing}}yes{{else}}no{{/missing}}", &data)
+                .unwrap(),
+            "no"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#obj}}{{#n}}yes{{else}}no{{/n}}{{/obj}}", &data)
+                .unwrap(),
+            "no"
+        );
+    }
+}
@@ -0,0 +1,340 @@
+//! String helpers, registered by `Registry::register_string_helpers`
+//!
+//! Params that are not strings are used as they render, so `{{upper 1}}` is
+//! `1` and a missing value is an empty string.
+
+use serde_json::value::Value as Json;
+
+use crate::context::Context;
+use crate::error::RenderError;
+use crate::helpers::HelperDef;
+use crate::registry::Registry;
+use crate::render::{Helper, RenderContext};
+use crate::value::{JsonRender, ScopedJson};
+
+handlebars_helper!(upper: |s: Json| s.render().to_uppercase());
+handlebars_helper!(lower: |s: Json| s.render().to_lowercase());
+handlebars_helper!(capitalize: |s: Json| {
+    let s = s.render();
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+});
+handlebars_helper!(trim: |s: Json| s.render().trim().to_owned());
+handlebars_helper!(replace: |s: Json, from: Json, to: Json| {
+    s.render().replace(&from.render(), &to.render())
+});
+handlebars_helper!(split: |s: Json, sep: Json| {
+    s.render().split(&sep.render()).map(|item| Json::String(item.to_owned())).collect::<Vec<_>>()
+});
+handlebars_helper!(starts_with: |s: Json, prefix: Json| s.render().starts_with(&prefix.render()));
+handlebars_helper!(ends_with: |s: Json, suffix: Json| s.render().ends_with(&suffix.render()));
+handlebars_helper!(contains: |s: Json, sub: Json| s.render().contains(&sub.render()));
+
+// the longest string `repeat`, `pad_left` and `pad_right` produce, in bytes
+const MAX_LENGTH: usize = 16 * 1024 * 1024;
+
+fn string_param(h: &Helper, idx: usize) -> Result<String, RenderError> {
+    h.param(idx)
+        .map(|p| p.value().render())
+        .ok_or_else(|| RenderError::new(format!("Insufficient params for helper \"{}\"", h.name())))
+}
+
+fn usize_param(h: &Helper, idx: usize) -> Result<usize, RenderError> {
+    h.param(idx)
+        .and_then(|p| p.value().as_u64())
+        .map(|n| n as usize)
+        .ok_or_else(|| {
+            RenderError::new(format!(
+                "Param {} of helper \"{}\" must be a non-negative integer",
+                idx,
+                h.name()
+            ))
+        })
+}
+
+fn hash_string(h: &Helper, key: &str) -> Option<String> {
+    h.hash_get(key).map(|v| v.value().render())
+}
+
+// checks that `count` copies of `len` bytes are not too long
+fn check_length(h: &Helper, len: usize, count: usize) -> Result<(), RenderError> {
+    match len.checked_mul(count) {
+        Some(total) if total <= MAX_LENGTH => Ok(()),
+        _ => Err(RenderError::new(format!(
+            "Output of helper \"{}\" is longer than {} bytes",
+            h.name(),
+            MAX_LENGTH
+        ))),
+    }
+}
+
+/// `{{truncate s 10}}` keeps the first 10 characters of a string, followed
+/// by `…` or the `ellipsis` hash value when it is longer
+#[derive(Clone, Copy)]
+pub struct TruncateHelper;
+
+impl HelperDef for TruncateHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Registry,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg>,
+    ) -> Result<Option<ScopedJson<'reg, 'rc>>, RenderError> {
+        let s = string_param(h, 0)?;
+        let len = usize_param(h, 1)?;
+
+        let truncated = match s.char_indices().nth(len) {
+            Some((end, _)) => {
+                let ellipsis = hash_string(h, "ellipsis").unwrap_or_else(|| "…".to_owned());
+                format!("{}{}", &s[..end], ellipsis)
+            }
+            None => s,
+        };
+        Ok(Some(ScopedJson::Derived(Json::String(truncated))))
+    }
+}
+
+/// `{{pad_left s 5}}` and `{{pad_right s 5}}` pad a string to 5 characters
+/// with spaces, or the `char` hash value
+#[derive(Clone, Copy)]
+pub struct PadHelper {
+    left: bool,
+}
+
+impl HelperDef for PadHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Registry,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg>,
+    ) -> Result<Option<ScopedJson<'reg, 'rc>>, RenderError> {
+        let s = string_param(h, 0)?;
+        let width = usize_param(h, 1)?;
+        let fill = match hash_string(h, "char") {
+            Some(fill) => {
+                let mut chars = fill.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => {
+                        return Err(RenderError::new(format!(
+                            "Hash \"char\" of helper \"{}\" must be one character",
+                            h.name()
+                        )));
+                    }
+                }
+            }
+            None => ' ',
+        };
+
+        let count = width.saturating_sub(s.chars().count());
+        check_length(h, fill.len_utf8(), count)?;
+        let padding: String = std::iter::repeat(fill).take(count).collect();
+        let padded = if self.left {
+            padding + &s
+        } else {
+            s + &padding
+        };
+        Ok(Some(ScopedJson::Derived(Json::String(padded))))
+    }
+}
+
+/// `{{repeat s 3}}` repeats a string 3 times
+#[derive(Clone, Copy)]
+pub struct RepeatHelper;
+
+impl HelperDef for RepeatHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Registry,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg>,
+    ) -> Result<Option<ScopedJson<'reg, 'rc>>, RenderError> {
+        let s = string_param(h, 0)?;
+        let count = usize_param(h, 1)?;
+        check_length(h, s.len(), count)?;
+        Ok(Some(ScopedJson::Derived(Json::String(s.repeat(count)))))
+    }
+}
+
+pub static TRUNCATE_HELPER: TruncateHelper = TruncateHelper;
+pub static REPEAT_HELPER: RepeatHelper = RepeatHelper;
+pub static PAD_LEFT_HELPER: PadHelper = PadHelper { left: true };
+pub static PAD_RIGHT_HELPER: PadHelper = PadHelper { left: false };
+
+#[cfg(test)]
+mod test {
+    use crate::registry::Registry;
+
+    #[test]
+    fn test_string_helpers() {
+        let mut handlebars = Registry::new();
+        handlebars.register_string_helpers();
+        handlebars.register_collection_helpers();
+
+        let data = json!({
+            "name": "  héllo wörld ",
+            "word": "ärger",
+            "csv": "a,b,c",
+            "list": ["x", 1, true],
+            "n": 42
+        });
+        assert_eq!(
+            handlebars.render_template("{{upper word}}", &data).unwrap(),
+            "ÄRGER"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{lower \"ÄB\"}}", &data)
+                .unwrap(),
+            "äb"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{capitalize word}}", &data)
+                .unwrap(),
+            "Ärger"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{capitalize \"\"}}", &data)
+                .unwrap(),
+            ""
+        );
+        assert_eq!(
+            handlebars
+                .render_template("[{{trim name}}]", &data)
+                .unwrap(),
+            "[héllo wörld]"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{replace csv \",\" \";\"}}", &data)
+                .unwrap(),
+            "a;b;c"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{lookup (split csv \",\") 1}}", &data)
+                .unwrap(),
+            "b"
+        );
+        assert_eq!(
+            handlebars.render_template("{{join list}}", &data).unwrap(),
+            "x,1,true"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{join (split csv \",\") \" - \"}}", &data)
+                .unwrap(),
+            "a - b - c"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{truncate word 3}}", &data)
+                .unwrap(),
+            "ärg…"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{truncate word 3 ellipsis=\"...\"}}", &data)
+                .unwrap(),
+            "ärg..."
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{truncate word 5}}", &data)
+                .unwrap(),
+            "ärger"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("[{{pad_left n 5}}]", &data)
+                .unwrap(),
+            "[   42]"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{pad_left n 5 char=\"0\"}}", &data)
+                .unwrap(),
+            "00042"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{pad_right word 7 char=\".\"}}", &data)
+                .unwrap(),
+            "ärger.."
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{pad_right word 2}}", &data)
+                .unwrap(),
+            "ärger"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{starts_with word \"är\"}}", &data)
+                .unwrap(),
+            "true"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{ends_with word \"x\"}}", &data)
+                .unwrap(),
+            "false"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#if (contains csv \"b,c\")}}yes{{/if}}", &data)
+                .unwrap(),
+            "yes"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{repeat \"ab\" 3}}", &data)
+                .unwrap(),
+            "ababab"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{upper missing}}", &data)
+                .unwrap(),
+            ""
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{upper (truncate word 1 ellipsis=\"\")}}", &data)
+                .unwrap(),
+            "Ä"
+        );
+    }
+
+    #[test]
+    fn test_string_helper_errors() {
+        let mut handlebars = Registry::new();
+        handlebars.register_string_helpers();
+
+        let data = json!({"word": "abc"});
+        for tpl in &[
+            "{{truncate word}}",
+            "{{truncate word -1}}",
+            "{{pad_left word 5 char=\"ab\"}}",
+            "{{repeat word \"x\"}}",
+            "{{repeat word 18446744073709551615}}",
+            "{{pad_left word 18446744073709551615}}",
+            "{{replace word \"a\"}}",
+        ] {
+            assert!(handlebars.render_template(tpl, &data).is_err(), "{}", tpl);
+        }
+        assert!(handlebars
+            .render_template("{{repeat word 10000000}}", &data)
+            .unwrap_err()
+            .to_string()
+            .contains("Output of helper \"repeat\" is longer than 16777216 bytes"));
+    }
+}
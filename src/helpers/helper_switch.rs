@@ -0,0 +1,213 @@
+use crate::context::Context;
+use crate::error::RenderError;
+use crate::helpers::helper_boolean::json_eq;
+use crate::helpers::helper_let::render_in_scope;
+use crate::helpers::{HelperDef, HelperResult};
+use crate::output::Output;
+use crate::registry::Registry;
+use crate::render::{Helper, RenderContext, Renderable};
+
+/// Render the first matching `case` of a value
+///
+/// `{{#switch status}}{{#case "up"}}...{{/case}}{{#case "down" "gone"}}...{{/case}}{{#default}}...{{/default}}{{/switch}}`
+///
+/// The value is `@switch` in the block, also inside blocks like `each` that
+/// hide other local variables. It is only visible to the block, so switches
+/// can be nested.
+#[derive(Clone, Copy)]
+pub struct SwitchHelper;
+
+impl HelperDef for SwitchHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        r: &'reg Registry,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let value = h
+            .param(0)
+            .ok_or_else(|| RenderError::new("Param not found for helper \"switch\""))?;
+
+        match h.template() {
+            Some(t) => {
+                let mut local_rc = rc.derive();
+                local_rc.set_switch(value.value().clone());
+                t.render(r, ctx, &mut local_rc, out)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Render a `case` of a `switch` when no case before matched
+///
+/// `{{#case "a" "b"}}` matches when the value of the switch equals one of
+/// its params, compared like the `eq` helper.
+/// `{{#default}}` always matches, so it comes last.
+#[derive(Clone, Copy)]
+pub struct CaseHelper {
+    default: bool,
+}
+
+impl HelperDef for CaseHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        r: &'reg Registry,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let matched = {
+            let (value, switch_matched) = rc.get_switch().ok_or_else(|| {
+                RenderError::new(format!(
+                    "Helper \"{}\" must be used inside \"switch\"",
+                    h.name()
+                ))
+            })?;
+            if switch_matched.get() {
+                return Ok(());
+            }
+
+            let matched = if self.default {
+                true
+            } else if h.params().is_empty() {
+                return Err(RenderError::new("Param not found for helper \"case\""));
+            } else {
+                h.params().iter().any(|p| json_eq(p.value(), value))
+            };
+            switch_matched.set(matched);
+            matched
+        };
+
+        if matched {
+            if let Some(t) = h.template() {
+                render_in_scope(t, r, ctx, rc, out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub static SWITCH_HELPER: SwitchHelper = SwitchHelper;
+pub static CASE_HELPER: CaseHelper = CaseHelper { default: false };
+pub static DEFAULT_HELPER: CaseHelper = CaseHelper { default: true };
+
+#[cfg(test)]
+mod test {
+    use crate::registry::Registry;
+
+    #[test]
+    fn test_switch() {
+        let mut handlebars = Registry::new();
+        handlebars.register_switch_helpers();
+        let tpl = "{{#switch status}}\
+                   {{#case \"up\"}}green{{/case}}\
+                   {{#case \"down\" \"gone\"}}red{{/case}}\
+                   {{#case 1}}one{{/case}}\
+                   {{#default}}unknown {{@switch}}{{/default}}\
+                   {{/switch}}";
+
+        let cases = vec![
+            (json!({"status": "up"}), "green"),
+            (json!({"status": "gone"}), "red"),
+            (json!({"status": 1.0}), "one"),
+            (json!({"status": "odd"}), "unknown odd"),
+            (json!({}), "unknown "),
+        ];
+        for (data, expected) in cases {
+            assert_eq!(handlebars.render_template(tpl, &data).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_switch_scopes() {
+        let mut handlebars = Registry::new();
+        handlebars.register_switch_helpers();
+        let data = json!({
+            "a": "x",
+            "big": 9_007_199_254_740_993u64,
+            "items": [{"kind": "b"}, {"kind": "c"}]
+        });
+        // only the first matching case renders
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#switch a}}{{#case \"x\"}}1{{/case}}{{#case \"x\"}}2{{/case}}{{#default}}3{{/default}}{{/switch}}",
+                    &data
+                )
+                .unwrap(),
+            "1"
+        );
+        // cases may be nested in other blocks of the switch
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#switch a}}{{#if true}}{{#case \"x\"}}1{{/case}}{{/if}}{{#default}}2{{/default}}{{/switch}}",
+                    &data
+                )
+                .unwrap(),
+            "1"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#switch a}}{{#case \"x\"}}{{#each items}}{{#switch kind}}{{#case \"b\"}}b{{/case}}{{#default}}-{{/default}}{{/switch}}{{/each}}{{/case}}{{#default}}no{{/default}}{{/switch}}",
+                    &data
+                )
+                .unwrap(),
+            "b-"
+        );
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#each items}}{{@index}}{{#switch kind}}{{#case \"c\"}}{{@index}}c{{/case}}{{/switch}}{{/each}}",
+                    &data
+                )
+                .unwrap(),
+            "011c"
+        );
+        // cases see the switch inside blocks that hide local variables
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#switch a}}{{#each items}}{{#case \"x\"}}{{@index}}{{@switch}}{{/case}}{{/each}}{{#default}}no{{/default}}{{/switch}}",
+                    &data
+                )
+                .unwrap(),
+            "0x"
+        );
+        assert_eq!(
+            handlebars
+                .render_template("{{#switch a}}{{/switch}}[{{@switch}}]", &data)
+                .unwrap(),
+            "[]"
+        );
+        // integers too large for a float compare exactly
+        assert_eq!(
+            handlebars
+                .render_template(
+                    "{{#switch big}}{{#case 9007199254740992}}no{{/case}}{{#case 9007199254740993}}yes{{/case}}{{/switch}}",
+                    &data
+                )
+                .unwrap(),
+            "yes"
+        );
+    }
+
+    #[test]
+    fn test_case_outside_switch() {
+        let mut handlebars = Registry::new();
+        handlebars.register_switch_helpers();
+        for tpl in &["{{#case 1}}x{{/case}}", "{{#default}}x{{/default}}"] {
+            let err = handlebars.render_template(tpl, &json!([1])).unwrap_err();
+            assert!(
+                err.to_string().contains("must be used inside \"switch\""),
+                "{}",
+                tpl
+            );
+        }
+    }
+}
@@ -42,8 +42,11 @@ impl HelperDef for WithHelper {
                         format!("{}/{}", rc.get_path(), p)
                     }
                 });
-                if let Some(ref new_path) = new_path {
-                    local_rc.set_path(new_path.clone());
+                match new_path {
+                    Some(ref new_path) => local_rc.set_path(new_path.clone()),
+                    // a value without a path, like the result of a
+                    // subexpression, is the context of the block
+                    None => local_rc.push_value_context(param.value().clone()),
                 }
 
                 if let Some(block_param) = h.block_param() {
@@ -228,6 +231,29 @@ mod test {
         assert_eq!(r2.ok().unwrap(), "01".to_string());
     }
 
+    #[test]
+    fn test_with_value_without_path() {
+        let mut handlebars = Registry::new();
+        handlebars.register_collection_helpers();
+        let data = json!({
+            "site": "blog",
+            "outer": {"name": "x"},
+            "posts": [{"name": "a", "author": {"name": "ann"}}]
+        });
+
+        let r0 = handlebars.render_template(
+            "{{#with (first posts)}}{{name}}@{{../site}}/{{@root.site}}{{/with}}",
+            &data,
+        );
+        assert_eq!(r0.unwrap(), "a@blog/blog");
+
+        let r1 = handlebars.render_template(
+            "{{#with outer as |o|}}{{#with (first ../posts)}}{{o.name}}{{#with author}}{{name}}/{{../name}}{{/with}}{{/with}}{{/with}}",
+            &data,
+        );
+        assert_eq!(r1.unwrap(), "xann/a");
+    }
+
     #[test]
     fn test_path_up() {
         let mut handlebars = Registry::new();
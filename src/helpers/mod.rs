@@ -7,9 +7,14 @@ use crate::value::ScopedJson;
 
 pub use self::helper_each::EACH_HELPER;
 pub use self::helper_if::{IF_HELPER, UNLESS_HELPER};
+pub use self::helper_layout::{BLOCK_HELPER, EXTEND_HELPER};
+pub use self::helper_let::LET_HELPER;
 pub use self::helper_log::LOG_HELPER;
 pub use self::helper_lookup::LOOKUP_HELPER;
 pub use self::helper_raw::RAW_HELPER;
+pub(crate) use self::helper_section::SECTION_HELPER;
+pub use self::helper_section::SectionHelper;
+pub use self::helper_switch::{CASE_HELPER, DEFAULT_HELPER, SWITCH_HELPER};
 pub use self::helper_with::WITH_HELPER;
 
 pub type HelperResult = Result<(), RenderError>;
@@ -103,6 +108,14 @@ pub trait HelperDef: Send + Sync {
 
         Ok(())
     }
+
+    /// Whether the helper may render the inverse template, the `{{else}}`
+    /// part of a block
+    ///
+    /// The linter reports `{{else}}` given to helpers that return `false`.
+    fn renders_inverse(&self) -> bool {
+        true
+    }
 }
 
 /// implement HelperDef for bare function so we can use function as helper
@@ -131,11 +144,22 @@ impl<
 }
 
 pub(crate) mod helper_boolean;
+pub(crate) mod helper_collection;
+#[cfg(feature = "date_helpers")]
+pub(crate) mod helper_date;
 mod helper_each;
 mod helper_if;
+mod helper_layout;
+pub(crate) mod helper_let;
 mod helper_log;
 mod helper_lookup;
+pub(crate) mod helper_math;
+pub(crate) mod helper_number;
 mod helper_raw;
+mod helper_section;
+#[cfg(feature = "string_helpers")]
+pub(crate) mod helper_string;
+mod helper_switch;
 mod helper_with;
 
 // pub type HelperDef = for <'a, 'b, 'c> Fn<(&'a Context, &'b Helper, &'b Registry, &'c mut RenderContext), Result<String, RenderError>>;
@@ -90,18 +90,40 @@
 //!
 //! You will get a `RenderError` when accessing fields that not exists.
 //!
+//! #### Mustache mode
+//!
+//! Mustache templates can be rendered with Mustache semantics, including
+//! sections, context stack lookup and set delimiter tags, by enabling
+//! `mustache_mode`:
+//!
+//! ```
+//! # use handlebars::Handlebars;
+//! # let mut handlebars = Handlebars::new();
+//! handlebars.set_mustache_mode(true);
+//! ```
+//!
+//! #### Standalone lines
+//!
+//! Like the JavaScript version, a line holding only a block tag, an
+//! `{{else}}`, a comment or a partial, with whitespace around it, is removed
+//! from the output, and the output of such a partial is indented like the
+//! partial tag. `set_ignore_standalone(true)` keeps these lines.
+//!
 //! ### Limitations
 //!
 //! #### Compatibility with JavaScript version
 //!
 //! This implementation is **not fully compatible** with the original javascript version.
 //!
-//! First of all, mustache block is not supported. I suggest you to use `#if` and `#each` for
-//! same functionality.
-//!
-//! There are some other minor features missing:
+//! Blocks of names that are not helpers, like `{{#items}}`, are errors
+//! outside of Mustache mode. Registering `SectionHelper` as
+//! `blockHelperMissing` renders them as sections, like the javascript
+//! version does. The Handlebars.js spec cases ported to `tests/handlebars`, and the ones this
+//! implementation does not pass on purpose, are listed in
+//! `tests/handlebars/skip.json`.
 //!
-//! * Chained else [#12](https://github.com/sunng87/handlebars-rust/issues/12)
+//! Like in the javascript version, `..` and `this` only start a path, so
+//! `{{foo/../bar}}` fails to compile.
 //!
 //! Feel free to fire an issue on [github](https://github.com/sunng87/handlebars-rust/issues) if
 //! you find missing features.
@@ -294,13 +316,27 @@
 //! * `{{{{raw}}}} ... {{{{/raw}}}}` escape handlebars expression within the block
 //! * `{{#if ...}} ... {{else}} ... {{/if}}` if-else block
 //! * `{{#unless ...}} ... {{else}} .. {{/unless}}` if-not-else block
+//! * `{{else ...}}` chains another block helper as the inverse of a block, like
+//!   `{{#if a}} ... {{else if b}} ... {{else}} ... {{/if}}`
 //! * `{{#each ...}} ... {{/each}}` iterates over an array or object. Handlebar-rust doesn't support mustache iteration syntax so use this instead.
+//!   * `@index`, `@key`, `@first`, `@last`, `@odd`, `@even` and `@length` describe the current
+//!     item, and `@../index` the item of the enclosing `#each`
+//!   * `limit=10`, `offset=20`, `reverse=true` and `sort_keys=true`, for objects, choose the
+//!     rendered items and their order
 //! * `{{#with ...}} ... {{/with}}` change current context. Similar to {{#each}}, used for replace corresponding mustache syntax.
-//! * `{{lookup ... ...}}` get value from array by `@index` or `@key`
+//! * `{{lookup ... ...}}` get value from array by `@index` or `@key`, or by a path like
+//!   `"a.b.-1"`, with an optional `default=...`. `(lookup ...)` can be the context of `#with`
+//!   and `#each`
 //! * `{{> ...}}` include template with name
 //! * `{{log ...}}` log value with rust logger, default level: INFO. Currently you cannot change the level.
+//! * `{{#switch ...}} {{#case ...}} ... {{/case}} {{#default}} ... {{/default}} {{/switch}}`
+//!   render the first `case` with a param equal to the value, or `default`, registered by
+//!   `Registry::register_switch_helpers`
+//! * `{{#let name=(...) other=...}} ... {{/let}}` names values in its block, and the
+//!   `{{*set name=...}}` decorator names them for the rest of the enclosing block, registered
+//!   by `Registry::register_variable_helpers`
 //! * Boolean helpers that can be used in `if` as subexpression, for example `{{#if (gt 2 1)}} ...`:
-//!   * `eq`
+//!   * `eq`, comparing any JSON value, with numbers equal by value like `1` and `1.0`
 //!   * `ne`
 //!   * `gt`
 //!   * `gte`
@@ -309,12 +345,59 @@
 //!   * `and`
 //!   * `or`
 //!   * `not`
+//! * Arithmetic helpers registered by `Registry::register_math_helpers`, keeping results of
+//!   integers integral, for example `{{add @index 1}}`:
+//!   * `add`, `sub`, `mul`, `div`, `mod`
+//!   * `abs`, `round`, `floor`, `ceil`
+//!   * `min`, `max` and `sum`, which also take arrays
+//! * Collection helpers registered by `Registry::register_collection_helpers`, returning new
+//!   values, for example `{{#each (sort_by posts "date")}}`:
+//!   * `len`, `first`, `last`, `slice`, `reverse`
+//!   * `sort_by "field"`, `filter_by "field" value`, `group_by "field"` and `uniq`, where a
+//!     field may be a dotted path like `author.name`
+//!   * `keys`, `values` and `join`
+//!
+//!   The items of `#each`, or the value of `#with`, over such values are the context of the
+//!   block, where `../` and `@root` still reach the enclosing data.
+//! * Number formatting helpers for a locale registered by `Registry::register_number_helpers`,
+//!   like `{{format_number n precision=2}}`:
+//!   * `format_number`, with thousands separators
+//!   * `format_percent`
+//!   * `format_currency`, in the currency of the locale or `currency="EUR"`
+//!
+//!   The locale is the `locale="de-DE"` hash param, or the one set for the rest of the render
+//!   with the `{{*locale ...}}` decorator or `Registry::render_with_locale`, `en-US` by default.
+//! * Layout helpers registered by `Registry::register_layout_helpers`: `{{#extend ...}}`
+//!   renders a layout template, replacing the blocks it defines, and `{{#block ...}}`
+//!   defines a named block that an extending template can replace
+//! * String helpers registered by `Registry::register_string_helpers` with the `string_helpers`
+//!   feature: `upper`, `lower`, `capitalize`, `trim`, `replace`, `split`, `truncate`, `pad_left`,
+//!   `pad_right`, `starts_with`, `ends_with`, `contains` and `repeat`
+//! * Date helpers registered by `Registry::register_date_helpers` with the `date_helpers`
+//!   feature: `format_date`, `relative_time` and `to_timezone`
 //!
 //! ### Template inheritance
 //!
 //! Handlebars.js' partial system is fully supported in this implementation.
 //! Check [example](https://github.com/sunng87/handlebars-rust/blob/master/examples/partials.rs#L49) for details.
 //!
+//! Besides partials, with the helpers of `Registry::register_layout_helpers`,
+//! a layout can declare named blocks with default content, and a template
+//! extending it replaces some of them. Layouts can extend other layouts; the
+//! block defined closest to the rendered template is used.
+//!
+//! ```
+//! use handlebars::Handlebars;
+//!
+//! let mut handlebars = Handlebars::new();
+//! handlebars.register_layout_helpers();
+//! handlebars.register_template_string("base",
+//!     "<title>{{#block \"title\"}}Default{{/block}}</title>{{#block \"body\"}}{{/block}}").unwrap();
+//! handlebars.register_template_string("page",
+//!     "{{#extend \"base\"}}{{#block \"body\"}}<p>{{this}}</p>{{/block}}{{/extend}}").unwrap();
+//!
+//! assert_eq!(handlebars.render("page", &"hi").unwrap(), "<title>Default</title><p>hi</p>");
+//! ```
 //!
 
 #![allow(dead_code)]
@@ -350,14 +433,28 @@ extern crate serde_json;
 extern crate walkdir;
 
 extern crate hashbrown;
+#[cfg(feature = "async_write")]
+extern crate futures;
+#[cfg(feature = "date_helpers")]
+extern crate chrono;
+#[cfg(feature = "date_helpers")]
+extern crate chrono_tz;
 
+pub use self::analysis::{Reference, ReferenceKind, TemplateReport, UnresolvedReference};
+pub use self::codegen::Precompiler;
 pub use self::context::{BlockParams, Context};
 pub use self::directives::DirectiveDef as DecoratorDef;
 pub use self::error::{RenderError, TemplateError, TemplateFileError, TemplateRenderError};
-pub use self::helpers::{HelperDef, HelperResult};
+#[cfg(feature = "date_helpers")]
+pub use self::helpers::helper_date::RelativeTimeHelper;
+pub use self::helpers::{HelperDef, HelperResult, SectionHelper};
+pub use self::lint::{LintFinding, LintRule};
+pub use self::loader::{DirectoryLoader, StaticLoader, TemplateLoader};
 pub use self::output::Output;
 pub use self::registry::{html_escape, no_escape, EscapeFn, Registry as Handlebars};
+pub use self::schema::{Schema, UnresolvedPath};
 pub use self::render::{Directive as Decorator, Evaluable, Helper, RenderContext, Renderable};
+pub use self::shared::SharedRegistry as SharedHandlebars;
 pub use self::support::str::StringWriter;
 pub use self::template::Template;
 pub use self::value::{to_json, JsonRender, PathAndJson, ScopedJson};
@@ -367,15 +464,23 @@ pub use self::serde_json::Value as JsonValue;
 
 #[macro_use]
 mod macros;
+mod analysis;
+#[cfg(feature = "async_write")]
+mod async_write;
+mod codegen;
 mod context;
 mod directives;
 mod error;
 mod grammar;
 mod helpers;
+mod lint;
+mod loader;
 mod output;
 mod partial;
 mod registry;
 mod render;
+mod schema;
+mod shared;
 mod support;
 pub mod template;
 mod value;
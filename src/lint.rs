@@ -0,0 +1,404 @@
+use std::fmt;
+
+use hashbrown::HashSet;
+use serde_json::value::Value as Json;
+
+use crate::registry::Registry;
+use crate::template::{
+    sorted_hash, DirectiveTemplate, HelperTemplate, Parameter, Template, TemplateElement,
+    TemplateMapping,
+};
+
+// parent references going up this many levels, like `../../../x`, are reported
+const MAX_PARENT_DEPTH: usize = 2;
+
+/// The checks done by the linter, all enabled by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintRule {
+    /// An inline partial that no template includes
+    UnusedInline,
+    /// Output not escaped, like `{{{html}}}`
+    UnescapedOutput,
+    /// A path going up more than two levels, like `../../../x`
+    DeepParentReference,
+    /// A helper that is not registered, blocks are sections when
+    /// `blockHelperMissing` is registered or in Mustache mode
+    UnknownHelper,
+    /// A block without content, like `{{#if x}}{{/if}}`
+    EmptyBlock,
+    /// An `{{else}}` given to a helper that never renders it
+    UnusedElse,
+}
+
+impl LintRule {
+    pub fn all() -> &'static [LintRule] {
+        &[
+            LintRule::UnusedInline,
+            LintRule::UnescapedOutput,
+            LintRule::DeepParentReference,
+            LintRule::UnknownHelper,
+            LintRule::EmptyBlock,
+            LintRule::UnusedElse,
+        ]
+    }
+
+    /// The identifier of the rule, like `unused-inline`
+    pub fn id(self) -> &'static str {
+        match self {
+            LintRule::UnusedInline => "unused-inline",
+            LintRule::UnescapedOutput => "unescaped-output",
+            LintRule::DeepParentReference => "deep-parent-reference",
+            LintRule::UnknownHelper => "unknown-helper",
+            LintRule::EmptyBlock => "empty-block",
+            LintRule::UnusedElse => "unused-else",
+        }
+    }
+}
+
+impl fmt::Display for LintRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+/// A problem found by the linter
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintFinding {
+    pub rule: LintRule,
+    pub template: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: [{}] {}",
+            self.template.as_ref().map_or("Unnamed", String::as_str),
+            self.line.unwrap_or(0),
+            self.column.unwrap_or(0),
+            self.rule,
+            self.message
+        )
+    }
+}
+
+/// Lint a template with the rules enabled in a registry
+///
+/// `used_partials` holds the partial names included by any template, an
+/// inline partial may be included by another template.
+pub(crate) fn lint_template(
+    registry: &Registry,
+    template: &Template,
+    used_partials: &HashSet<String>,
+) -> Vec<LintFinding> {
+    let mut linter = Linter {
+        registry,
+        used_partials,
+        template_name: template.name.clone(),
+        in_extend: 0,
+        findings: Vec::new(),
+    };
+    linter.template(template);
+    linter.findings
+}
+
+struct Linter<'a> {
+    registry: &'a Registry,
+    used_partials: &'a HashSet<String>,
+    template_name: Option<String>,
+    // inline partials defined in `extend` blocks replace blocks of layouts
+    in_extend: usize,
+    findings: Vec<LintFinding>,
+}
+
+fn is_empty(t: &Option<Template>) -> bool {
+    t.iter().flat_map(|t| t.elements.iter()).all(|e| match *e {
+        TemplateElement::RawString(ref s) => s.trim().is_empty(),
+        TemplateElement::Comment(_) => true,
+        _ => false,
+    })
+}
+
+// the helper rendering helpers that are not registered
+fn missing_helper(ht: &HelperTemplate) -> &'static str {
+    if ht.block {
+        "blockHelperMissing"
+    } else {
+        "helperMissing"
+    }
+}
+
+fn parent_depth(path: &str) -> usize {
+    let mut rest = path.trim_start_matches('@');
+    let mut depth = 0;
+    while rest.starts_with("../") || rest == ".." {
+        depth += 1;
+        rest = rest.get(3..).unwrap_or("");
+    }
+    depth
+}
+
+impl<'a> Linter<'a> {
+    fn report(&mut self, rule: LintRule, position: Option<(usize, usize)>, message: String) {
+        if self.registry.lint_rule_enabled(rule) {
+            self.findings.push(LintFinding {
+                rule,
+                template: self.template_name.clone(),
+                line: position.map(|p| p.0),
+                column: position.map(|p| p.1),
+                message,
+            });
+        }
+    }
+
+    fn template(&mut self, t: &Template) {
+        for (idx, e) in t.elements.iter().enumerate() {
+            let position = t
+                .mapping
+                .as_ref()
+                .and_then(|m| m.get(idx))
+                .map(|&TemplateMapping(line, col)| (line, col));
+            self.element(e, position);
+        }
+    }
+
+    fn element(&mut self, e: &TemplateElement, position: Option<(usize, usize)>) {
+        match *e {
+            TemplateElement::HTMLExpression(ref p) => {
+                self.report(
+                    LintRule::UnescapedOutput,
+                    position,
+                    "Output is not escaped".to_owned(),
+                );
+                self.param(p, position);
+            }
+            TemplateElement::Expression(ref ht) | TemplateElement::HelperBlock(ref ht) => {
+                self.helper(ht, position)
+            }
+            TemplateElement::DirectiveExpression(ref dt)
+            | TemplateElement::DirectiveBlock(ref dt) => {
+                if dt.name.as_name() == Some("inline") && self.in_extend == 0 {
+                    if let Some(Parameter::Literal(Json::String(ref name))) = dt.params.first() {
+                        if !self.used_partials.contains(name) {
+                            self.report(
+                                LintRule::UnusedInline,
+                                position,
+                                format!("Inline partial \"{}\" is never used", name),
+                            );
+                        }
+                    }
+                }
+                self.directive(dt, position);
+            }
+            TemplateElement::PartialExpression(ref dt) | TemplateElement::PartialBlock(ref dt) => {
+                self.directive(dt, position)
+            }
+            TemplateElement::RawString(_) | TemplateElement::Comment(_) => {}
+        }
+    }
+
+    fn param(&mut self, p: &Parameter, position: Option<(usize, usize)>) {
+        match *p {
+            Parameter::Name(ref path) => {
+                if parent_depth(path) > MAX_PARENT_DEPTH {
+                    self.report(
+                        LintRule::DeepParentReference,
+                        position,
+                        format!("Path \"{}\" goes up too many levels", path),
+                    );
+                }
+            }
+            Parameter::Subexpression(ref s) => {
+                let own_position = s.mapping().map(|&TemplateMapping(line, col)| (line, col));
+                self.element(s.as_element(), own_position.or(position))
+            }
+            Parameter::Literal(_) => {}
+        }
+    }
+
+    fn helper(&mut self, ht: &HelperTemplate, position: Option<(usize, usize)>) {
+        if ht.is_name_only() {
+            self.param(&ht.name, position);
+            return;
+        }
+
+        if let Some(name) = ht.name.as_name() {
+            match self.registry.get_helper(name) {
+                Some(def) if ht.inverse.is_some() && !def.renders_inverse() => self.report(
+                    LintRule::UnusedElse,
+                    position,
+                    format!("Helper \"{}\" never renders its else block", name),
+                ),
+                Some(_) => {}
+                // blocks of other names are sections in Mustache mode
+                None if ht.block && self.registry.mustache_mode() => {}
+                None if self.registry.get_helper(missing_helper(ht)).is_some() => {}
+                None => self.report(
+                    LintRule::UnknownHelper,
+                    position,
+                    format!("Helper not defined: \"{}\"", name),
+                ),
+            }
+
+            // placeholders of layouts are often empty
+            if ht.block && name != "block" && is_empty(&ht.template) && is_empty(&ht.inverse) {
+                self.report(
+                    LintRule::EmptyBlock,
+                    position,
+                    format!("Block \"{}\" is empty", name),
+                );
+            }
+        } else {
+            self.param(&ht.name, position);
+        }
+
+        for p in &ht.params {
+            self.param(p, position);
+        }
+        for (_, p) in sorted_hash(&ht.hash) {
+            self.param(p, position);
+        }
+
+        let extend = ht.name.as_name() == Some("extend");
+        if extend {
+            self.in_extend += 1;
+        }
+        if let Some(ref t) = ht.template {
+            self.template(t);
+        }
+        if extend {
+            self.in_extend -= 1;
+        }
+        if let Some(ref t) = ht.inverse {
+            self.template(t);
+        }
+    }
+
+    fn directive(&mut self, dt: &DirectiveTemplate, position: Option<(usize, usize)>) {
+        if let Parameter::Subexpression(_) = dt.name {
+            self.param(&dt.name, position);
+        }
+        for p in &dt.params {
+            self.param(p, position);
+        }
+        for (_, p) in sorted_hash(&dt.hash) {
+            self.param(p, position);
+        }
+        if let Some(ref t) = dt.template {
+            self.template(t);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::context::Context;
+    use crate::error::RenderError;
+    use crate::helpers::HelperDef;
+    use crate::lint::{parent_depth, LintRule};
+    use crate::registry::Registry;
+    use crate::render::{Helper, RenderContext};
+    use crate::value::ScopedJson;
+
+    struct ValueHelper;
+
+    impl HelperDef for ValueHelper {
+        fn call_inner<'reg: 'rc, 'rc>(
+            &self,
+            _: &Helper<'reg, 'rc>,
+            _: &'reg Registry,
+            _: &'rc Context,
+            _: &mut RenderContext<'reg>,
+        ) -> Result<Option<ScopedJson<'reg, 'rc>>, RenderError> {
+            Ok(None)
+        }
+
+        fn renders_inverse(&self) -> bool {
+            false
+        }
+    }
+
+    fn findings(r: &Registry) -> Vec<(&'static str, String, usize, usize)> {
+        r.lint()
+            .into_iter()
+            .map(|f| {
+                (
+                    f.rule.id(),
+                    f.template.unwrap(),
+                    f.line.unwrap(),
+                    f.column.unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parent_depth() {
+        assert_eq!(parent_depth("name"), 0);
+        assert_eq!(parent_depth("../name"), 1);
+        assert_eq!(parent_depth("../../../name"), 3);
+        assert_eq!(parent_depth("@../../../index"), 3);
+    }
+
+    #[test]
+    fn test_lint() {
+        let mut r = Registry::new();
+        r.register_layout_helpers();
+        r.register_helper("value", Box::new(ValueHelper));
+        r.register_template_string(
+            "t0",
+            "{{#*inline \"used\"}}x{{/inline}}{{#*inline \"unused\"}}y{{/inline}}{{> used}}\n\
+             {{{html}}}{{#each a}}{{#each b}}{{#each c}}{{../../../x}}{{../../y}}{{/each}}{{/each}}{{/each}}\n\
+             {{#if x}} {{/if}}{{#if x}}{{else}}no{{/if}}{{lookup x 0}}{{format (nope x)}}\n\
+             {{#block \"b\"}}{{/block}}{{#with x}}a{{else}}b{{/with}}{{#value x}}a{{else}}b{{/value}}\n\
+             {{#items}}x{{/items}}",
+        )
+        .unwrap();
+        r.register_template_string(
+            "t1",
+            "{{#extend \"t0\"}}{{#*inline \"b\"}}{{/inline}}{{/extend}}{{> unused_elsewhere}}",
+        )
+        .unwrap();
+
+        assert_eq!(
+            findings(&r),
+            vec![
+                ("unused-inline", "t0".to_owned(), 1, 32),
+                ("unescaped-output", "t0".to_owned(), 2, 1),
+                ("deep-parent-reference", "t0".to_owned(), 2, 44),
+                ("empty-block", "t0".to_owned(), 3, 1),
+                ("unknown-helper", "t0".to_owned(), 3, 58),
+                ("unknown-helper", "t0".to_owned(), 3, 67),
+                ("unused-else", "t0".to_owned(), 4, 55),
+                ("unknown-helper", "t0".to_owned(), 5, 1),
+            ]
+        );
+
+        // unknown blocks are sections with these
+        r.set_mustache_mode(true);
+        assert_eq!(findings(&r).len(), 7);
+        r.set_mustache_mode(false);
+        r.register_helper("blockHelperMissing", Box::new(ValueHelper));
+        assert_eq!(findings(&r).len(), 7);
+
+        r.set_lint_rule(LintRule::UnknownHelper, false);
+        r.set_lint_rule(LintRule::UnescapedOutput, false);
+        assert!(!r.lint_rule_enabled(LintRule::UnknownHelper));
+        let ids: Vec<_> = findings(&r).into_iter().map(|f| f.0).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "unused-inline",
+                "deep-parent-reference",
+                "empty-block",
+                "unused-else"
+            ]
+        );
+
+        assert_eq!(r.lint_template("t1").unwrap(), vec![]);
+        assert!(r.lint_template("t2").is_err());
+    }
+}
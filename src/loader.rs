@@ -0,0 +1,194 @@
+use std::fs::File;
+use std::io::{Error as IOError, ErrorKind, Read};
+use std::path::{Component, Path, PathBuf};
+
+/// Template Loader Definition
+///
+/// A loader provides template sources on demand. When a template or partial
+/// is requested but not registered, the registry asks its loaders, in the
+/// order they were added, for the source of that name. The first source
+/// found is compiled and kept in the registry for later renders.
+///
+/// Return `Ok(None)` when the loader doesn't know the name, so the next
+/// loader can be tried.
+///
+/// Bare functions and closures can be used as loaders:
+///
+/// ```
+/// use handlebars::*;
+///
+/// let mut handlebars = Handlebars::new();
+/// handlebars.register_template_loader(Box::new(|name: &str| {
+///     Ok(if name == "hello" {
+///         Some("Hello {{this}}".to_owned())
+///     } else {
+///         None
+///     })
+/// }));
+///
+/// assert_eq!(handlebars.render("hello", &"world").unwrap(), "Hello world");
+/// ```
+pub trait TemplateLoader: Send + Sync {
+    fn load(&self, name: &str) -> Result<Option<String>, IOError>;
+}
+
+/// implement TemplateLoader for bare function so we can use function as loader
+impl<F: Send + Sync + Fn(&str) -> Result<Option<String>, IOError>> TemplateLoader for F {
+    fn load(&self, name: &str) -> Result<Option<String>, IOError> {
+        (*self)(name)
+    }
+}
+
+/// Load templates from files in a directory
+///
+/// The template `some/path/file` is read from `dir_path/some/path/file.hbs`
+/// when the extension is `.hbs`, the same naming used by
+/// `Registry::register_templates_directory`. Names that would escape the
+/// directory, like `../secret`, are never loaded.
+#[derive(Debug, Clone)]
+pub struct DirectoryLoader {
+    dir_path: PathBuf,
+    extension: String,
+}
+
+impl DirectoryLoader {
+    pub fn new<P: AsRef<Path>>(dir_path: P, extension: &str) -> DirectoryLoader {
+        DirectoryLoader {
+            dir_path: dir_path.as_ref().to_path_buf(),
+            extension: extension.to_owned(),
+        }
+    }
+}
+
+impl TemplateLoader for DirectoryLoader {
+    fn load(&self, name: &str) -> Result<Option<String>, IOError> {
+        let relative = Path::new(name);
+        if !relative.components().all(|c| match c {
+            Component::Normal(_) => true,
+            _ => false,
+        }) {
+            return Ok(None);
+        }
+
+        let tpl_path = self.dir_path.join(format!("{}{}", name, self.extension));
+        let mut file = match File::open(tpl_path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let mut buf = String::new();
+        file.read_to_string(&mut buf)?;
+        Ok(Some(buf))
+    }
+}
+
+/// Load templates from a static table of names and sources
+///
+/// Useful for templates embedded in the binary with `include_str!`.
+///
+/// ```
+/// use handlebars::*;
+///
+/// static TEMPLATES: &[(&str, &str)] = &[("hello", "Hello {{this}}")];
+///
+/// let mut handlebars = Handlebars::new();
+/// handlebars.register_template_loader(Box::new(StaticLoader::new(TEMPLATES)));
+///
+/// assert_eq!(handlebars.render("hello", &"world").unwrap(), "Hello world");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct StaticLoader {
+    templates: &'static [(&'static str, &'static str)],
+}
+
+impl StaticLoader {
+    pub fn new(templates: &'static [(&'static str, &'static str)]) -> StaticLoader {
+        StaticLoader { templates }
+    }
+}
+
+impl TemplateLoader for StaticLoader {
+    fn load(&self, name: &str) -> Result<Option<String>, IOError> {
+        Ok(self
+            .templates
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, source)| (*source).to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::loader::{DirectoryLoader, StaticLoader, TemplateLoader};
+    use crate::registry::Registry;
+    use std::fs::{DirBuilder, File};
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_static_loader() {
+        static TEMPLATES: &[(&str, &str)] = &[("t0", "t0: {{> t1}}"), ("t1", "{{this}}")];
+        let loader = StaticLoader::new(TEMPLATES);
+        assert_eq!(loader.load("t1").unwrap(), Some("{{this}}".to_owned()));
+        assert_eq!(loader.load("t2").unwrap(), None);
+
+        let mut handlebars = Registry::new();
+        handlebars.register_template_loader(Box::new(loader));
+        assert!(!handlebars.has_template("t0"));
+        assert_eq!(handlebars.render("t0", &1).unwrap(), "t0: 1");
+        assert!(handlebars.render("t2", &1).is_err());
+
+        // registered templates take precedence over loaded ones
+        handlebars.register_template_string("t1", "<{{this}}>").unwrap();
+        assert_eq!(handlebars.render("t0", &1).unwrap(), "t0: <1>");
+    }
+
+    #[test]
+    fn test_directory_loader() {
+        let dir = tempdir().unwrap();
+        DirBuilder::new()
+            .create(dir.path().join("partials"))
+            .unwrap();
+        let mut file = File::create(dir.path().join("index.hbs")).unwrap();
+        write!(file, "<h1>{{{{> partials/title}}}}</h1>").unwrap();
+        let mut file = File::create(dir.path().join("partials/title.hbs")).unwrap();
+        write!(file, "{{{{title}}}}").unwrap();
+        let mut file = File::create(dir.path().join("broken.hbs")).unwrap();
+        write!(file, "{{{{#if title}}").unwrap();
+
+        let mut handlebars = Registry::new();
+        handlebars.register_template_loader(Box::new(DirectoryLoader::new(dir.path(), ".hbs")));
+
+        let data = json!({"title": "hello"});
+        assert_eq!(handlebars.render("index", &data).unwrap(), "<h1>hello</h1>");
+        assert_eq!(
+            handlebars
+                .render_template("{{> partials/title}}!", &data)
+                .unwrap(),
+            "hello!"
+        );
+        assert!(handlebars.render("missing", &data).is_err());
+        assert!(handlebars.render("broken", &data).is_err());
+
+        let loader = DirectoryLoader::new(dir.path().join("partials"), ".hbs");
+        assert_eq!(loader.load("../index").unwrap(), None);
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_loader_chain() {
+        static FIRST: &[(&str, &str)] = &[("a", "first a")];
+        static SECOND: &[(&str, &str)] = &[("a", "second a"), ("b", "second b")];
+
+        let mut handlebars = Registry::new();
+        handlebars.register_template_loader(Box::new(StaticLoader::new(FIRST)));
+        handlebars.register_template_loader(Box::new(StaticLoader::new(SECOND)));
+
+        assert_eq!(
+            handlebars.render_template("{{> a}}, {{> b}}", &()).unwrap(),
+            "first a, second b"
+        );
+    }
+}
@@ -1,3 +1,7 @@
+use std::cell::Cell;
+use std::io::Error as IOError;
+use std::rc::Rc;
+
 use hashbrown::HashMap;
 
 use serde_json::value::Value as Json;
@@ -5,10 +9,29 @@ use serde_json::value::Value as Json;
 use crate::context::{merge_json, Context};
 use crate::error::RenderError;
 use crate::output::Output;
-use crate::registry::Registry;
+use crate::registry::{Registry, TemplateRef};
 use crate::render::{Directive, Evaluable, RenderContext, Renderable};
 use crate::template::Template;
 
+// Output of a partial alone on its line, the indentation of the partial tag
+// is written before each line
+struct IndentOutput<'a> {
+    out: &'a mut dyn Output,
+    indent: &'a str,
+    line_start: Rc<Cell<bool>>,
+}
+
+impl<'a> Output for IndentOutput<'a> {
+    fn write(&mut self, seg: &str) -> Result<(), IOError> {
+        if !seg.is_empty() && self.line_start.get() {
+            // an enclosing indented partial writes its own indentation first
+            self.out.write(self.indent)?;
+            self.line_start.set(false);
+        }
+        self.out.write(seg)
+    }
+}
+
 fn render_partial<'reg: 'rc, 'rc>(
     t: &'reg Template,
     d: &Directive<'reg, 'rc>,
@@ -30,12 +53,28 @@ fn render_partial<'reg: 'rc, 'rc>(
         if let Some(param_path) = param_path {
             local_rc.promote_local_vars();
             local_rc.set_path(param_path);
+        } else if !param_ctx.is_value_missing() {
+            // a literal or a value from a subexpression is the context itself
+            let ctx = Context::wraps(param_ctx.value())?;
+            let mut partial_rc = local_rc.new_for_block();
+            return render_partial_in(t, d, r, &ctx, &mut partial_rc, out);
         }
     };
 
+    render_partial_in(t, d, r, ctx, local_rc, out)
+}
+
+fn render_partial_in<'reg: 'rc, 'rc>(
+    t: &'reg Template,
+    d: &Directive<'reg, 'rc>,
+    r: &'reg Registry,
+    ctx: &Context,
+    local_rc: &mut RenderContext<'reg>,
+    out: &mut dyn Output,
+) -> Result<(), RenderError> {
     // @partial-block
     if let Some(t) = d.template() {
-        local_rc.set_partial("@partial-block".to_owned(), t);
+        local_rc.push_partial_block(t);
     }
 
     if d.hash().is_empty() {
@@ -66,28 +105,58 @@ pub fn expand_partial<'reg: 'rc, 'rc>(
     }
 
     let tname = d.name();
+    // `[name]` escapes names that are not valid paths
+    let tname = if tname.len() > 1 && tname.starts_with('[') && tname.ends_with(']') {
+        &tname[1..tname.len() - 1]
+    } else {
+        tname
+    };
     if rc.is_current_template(tname) {
         return Err(RenderError::new("Cannot include self in >"));
     }
 
-    let partial = rc.get_partial(tname);
-
-    match partial {
-        Some(t) => {
-            let mut local_rc = rc.derive();
-            render_partial(&t, d, r, ctx, &mut local_rc, out)?;
+    let partial = find_partial(rc, r, tname)?.or_else(|| d.template().map(TemplateRef::Borrowed));
+    if let Some(t) = partial {
+        let mut local_rc = rc.derive();
+        if tname == "@partial-block" {
+            local_rc.pop_partial_block();
         }
-        None => {
-            if let Some(t) = r.get_template(tname).or_else(|| d.template()) {
-                let mut local_rc = rc.derive();
-                render_partial(t, d, r, ctx, &mut local_rc, out)?;
+        match d.indent() {
+            Some(indent) => {
+                // lines are tracked by all the nested indented partials
+                let line_start = local_rc.line_start().unwrap_or_else(|| {
+                    let line_start = Rc::new(Cell::new(true));
+                    local_rc.set_line_start(line_start.clone());
+                    line_start
+                });
+                line_start.set(true);
+                let mut out = IndentOutput {
+                    out,
+                    indent,
+                    line_start,
+                };
+                render_partial(&t, d, r, ctx, &mut local_rc, &mut out)?;
             }
+            None => render_partial(&t, d, r, ctx, &mut local_rc, out)?,
         }
     }
 
     Ok(())
 }
 
+/// Find a partial by name, in partials defined on the render context
+/// first and then in the registry.
+pub(crate) fn find_partial<'reg>(
+    rc: &RenderContext<'reg>,
+    r: &'reg Registry,
+    name: &str,
+) -> Result<Option<TemplateRef<'reg>>, RenderError> {
+    if let Some(t) = rc.get_partial(name) {
+        return Ok(Some(TemplateRef::Borrowed(*t)));
+    }
+    r.get_or_load_template_optional(name)
+}
+
 #[cfg(test)]
 mod test {
     use crate::registry::Registry;
@@ -221,4 +290,67 @@ mod test {
         let r0 = handlebars.render("t", &data);
         assert_eq!(r0.ok().unwrap(), "2 true2 false");
     }
+
+    #[test]
+    fn test_nested_partial_blocks() {
+        let mut handlebars = Registry::new();
+        handlebars
+            .register_template_string(
+                "outer",
+                "<outer>{{#> nested}}<block>{{> @partial-block}}</block>{{/nested}}</outer>",
+            )
+            .unwrap();
+        handlebars
+            .register_template_string("nested", "<nested>{{> @partial-block}}</nested>")
+            .unwrap();
+
+        let r0 = handlebars.render_template("{{#> outer}}{{a}}{{/outer}}", &json!({"a": 1}));
+        assert_eq!(
+            r0.unwrap(),
+            "<outer><nested><block>1</block></nested></outer>"
+        );
+    }
+
+    #[test]
+    fn test_partial_literal_context() {
+        let mut handlebars = Registry::new();
+        handlebars
+            .register_template_string("p", "{{this}}")
+            .unwrap();
+
+        let r0 = handlebars.render_template("{{> p \"a\"}} {{> p 1}}", &json!({}));
+        assert_eq!(r0.unwrap(), "a 1");
+    }
+
+    #[test]
+    fn test_standalone_partial_indent() {
+        let mut handlebars = Registry::new();
+        handlebars
+            .register_template_string("t", "list:\n  {{> items}}\nend")
+            .unwrap();
+        handlebars
+            .register_template_string(
+                "items",
+                "{{#each this}}\n- {{name}}\n  {{> value}}\n{{/each}}\n",
+            )
+            .unwrap();
+        handlebars
+            .register_template_string("value", "value: {{{value}}}\n")
+            .unwrap();
+
+        let data = json!([{"name": "a", "value": 1}, {"name": "b", "value": "x\ny"}]);
+        assert_eq!(
+            handlebars.render("t", &data).unwrap(),
+            "list:\n  - a\n    value: 1\n  - b\n    value: x\ny\nend"
+        );
+
+        handlebars.set_ignore_standalone(true);
+        handlebars
+            .register_template_string("t", "list:\n  {{> value}}\nend")
+            .unwrap();
+        assert_eq!(
+            handlebars.render("t", &json!({"value": 1})).unwrap(),
+            "list:\n  value: 1\n\nend"
+        );
+    }
 }
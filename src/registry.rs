@@ -1,21 +1,28 @@
 use std::fmt::{self, Debug, Formatter};
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::prelude::*;
-use std::path::Path;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
 
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use serde::Serialize;
 
 use regex::{Captures, Regex};
 
+use crate::analysis::{ReferenceKind, UnresolvedReference};
 use crate::context::Context;
 use crate::directives::{self, DirectiveDef};
 use crate::error::{RenderError, TemplateError, TemplateFileError, TemplateRenderError};
 use crate::helpers::{self, HelperDef};
+use crate::lint::{self, LintFinding, LintRule};
+use crate::loader::TemplateLoader;
 use crate::output::{Output, StringOutput, WriteOutput};
 use crate::render::{RenderContext, Renderable};
+use crate::schema::{Schema, UnresolvedPath};
 use crate::support::str::StringWriter;
-use crate::template::Template;
+use crate::template::{Template, TemplateOptions};
 
 #[cfg(not(feature = "no_dir_source"))]
 use walkdir::{DirEntry, WalkDir};
@@ -54,29 +61,124 @@ pub fn no_escape(data: &str) -> String {
     data.to_owned()
 }
 
+/// A template registered from a file, kept for reloading in dev mode
+struct TemplateFile {
+    path: PathBuf,
+    state: RwLock<TemplateFileState>,
+}
+
+struct TemplateFileState {
+    // modification time of the file when it was last compiled
+    modified: Option<SystemTime>,
+    // the last good template compiled after registration, if any
+    reloaded: Option<Arc<Template>>,
+}
+
+impl TemplateFile {
+    fn new(path: PathBuf, modified: Option<SystemTime>) -> TemplateFile {
+        TemplateFile {
+            path,
+            state: RwLock::new(TemplateFileState {
+                modified,
+                reloaded: None,
+            }),
+        }
+    }
+}
+
+impl Clone for TemplateFile {
+    fn clone(&self) -> TemplateFile {
+        let state = self.state.read().unwrap();
+        TemplateFile {
+            path: self.path.clone(),
+            state: RwLock::new(TemplateFileState {
+                modified: state.modified,
+                reloaded: state.reloaded.clone(),
+            }),
+        }
+    }
+}
+
+/// A template resolved for rendering, either registered or loaded on demand
+pub(crate) enum TemplateRef<'reg> {
+    Borrowed(&'reg Template),
+    Loaded(Arc<Template>),
+}
+
+impl<'reg> Deref for TemplateRef<'reg> {
+    type Target = Template;
+
+    fn deref(&self) -> &Template {
+        match *self {
+            TemplateRef::Borrowed(t) => t,
+            TemplateRef::Loaded(ref t) => t,
+        }
+    }
+}
+
+fn modified_time<P: AsRef<Path>>(path: P) -> Result<SystemTime, std::io::Error> {
+    fs::metadata(path).and_then(|m| m.modified())
+}
+
 /// The single entry point of your Handlebars templates
 ///
 /// It maintains compiled templates and registered helpers.
 pub struct Registry {
-    templates: HashMap<String, Template>,
-    helpers: HashMap<String, Box<dyn HelperDef + 'static>>,
-    directives: HashMap<String, Box<dyn DirectiveDef + 'static>>,
-    escape_fn: EscapeFn,
+    templates: HashMap<String, Arc<Template>>,
+    template_files: HashMap<String, TemplateFile>,
+    template_loaders: Vec<Arc<dyn TemplateLoader + 'static>>,
+    loaded_templates: RwLock<HashMap<String, Arc<Template>>>,
+    helpers: HashMap<String, Arc<dyn HelperDef + 'static>>,
+    directives: HashMap<String, Arc<dyn DirectiveDef + 'static>>,
+    // shared so that a registry can be cloned
+    escape_fn: Arc<dyn Fn(&str) -> String + Send + Sync>,
     source_map: bool,
     strict_mode: bool,
+    dev_mode: bool,
+    mustache_mode: bool,
+    delimiters: Option<(String, String)>,
+    ignore_standalone: bool,
+    disabled_lint_rules: HashSet<LintRule>,
 }
 
 impl Debug for Registry {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
         f.debug_struct("Handlebars")
             .field("templates", &self.templates)
+            .field("template_loaders", &self.template_loaders.len())
             .field("helpers", &self.helpers.keys())
             .field("directives", &self.directives.keys())
             .field("source_map", &self.source_map)
+            .field("dev_mode", &self.dev_mode)
+            .field("mustache_mode", &self.mustache_mode)
+            .field("delimiters", &self.delimiters)
+            .field("ignore_standalone", &self.ignore_standalone)
+            .field("disabled_lint_rules", &self.disabled_lint_rules)
             .finish()
     }
 }
 
+impl Clone for Registry {
+    fn clone(&self) -> Registry {
+        Registry {
+            templates: self.templates.clone(),
+            template_files: self.template_files.clone(),
+            template_loaders: self.template_loaders.clone(),
+            loaded_templates: RwLock::new(self.loaded_templates.read().unwrap().clone()),
+            helpers: self.helpers.clone(),
+            directives: self.directives.clone(),
+            escape_fn: self.escape_fn.clone(),
+            source_map: self.source_map,
+            strict_mode: self.strict_mode,
+            dev_mode: self.dev_mode,
+            mustache_mode: self.mustache_mode,
+            delimiters: self.delimiters.clone(),
+            ignore_standalone: self.ignore_standalone,
+            disabled_lint_rules: self.disabled_lint_rules.clone(),
+        }
+    }
+}
+
 impl Default for Registry {
     fn default() -> Self {
         Self::new()
@@ -102,11 +204,19 @@ impl Registry {
     pub fn new() -> Registry {
         let r = Registry {
             templates: HashMap::new(),
+            template_files: HashMap::new(),
+            template_loaders: Vec::new(),
+            loaded_templates: RwLock::new(HashMap::new()),
             helpers: HashMap::new(),
             directives: HashMap::new(),
-            escape_fn: Box::new(html_escape),
+            escape_fn: Arc::new(html_escape),
             source_map: true,
             strict_mode: false,
+            dev_mode: false,
+            mustache_mode: false,
+            delimiters: None,
+            ignore_standalone: false,
+            disabled_lint_rules: HashSet::new(),
         };
 
         r.setup_builtins()
@@ -167,6 +277,110 @@ impl Registry {
         self.strict_mode
     }
 
+    /// Enable handlebars dev mode
+    ///
+    /// In dev mode, templates registered from files (with
+    /// `register_template_file` or `register_templates_directory`) are
+    /// re-read and recompiled when the modification time of their file
+    /// changes, so edits show up on the next render without restarting.
+    /// If the modified file fails to compile, rendering it returns an
+    /// error and the last good template is kept.
+    ///
+    /// Templates from a `TemplateLoader` are not cached in dev mode, the
+    /// loader is asked again on every use.
+    ///
+    /// Default is false.
+    pub fn set_dev_mode(&mut self, enable: bool) {
+        self.dev_mode = enable;
+    }
+
+    /// Return dev mode state, default is false.
+    pub fn dev_mode(&self) -> bool {
+        self.dev_mode
+    }
+
+    /// Enable Mustache compatibility mode
+    ///
+    /// In Mustache mode, templates are parsed and rendered following the
+    /// Mustache spec:
+    ///
+    /// * set delimiter tags like `{{=<% %>=}}` are accepted,
+    /// * a name not found in the current context is looked up in the
+    ///   enclosing ones,
+    /// * a block whose name is not a helper is a section: it iterates over
+    ///   a list, renders once with a truthy value as its context and renders
+    ///   the inverse for falsy values.
+    ///
+    /// Templates registered before the change are not recompiled.
+    ///
+    /// Default is false.
+    pub fn set_mustache_mode(&mut self, enable: bool) {
+        self.mustache_mode = enable;
+    }
+
+    /// Return Mustache compatibility mode state, default is false.
+    pub fn mustache_mode(&self) -> bool {
+        self.mustache_mode
+    }
+
+    /// Set the open and close delimiters of tags, like `<%` and `%>`
+    ///
+    /// Every kind of tag uses the delimiters instead of `{{` and `}}`:
+    /// `<%name%>`, `<%#if a%>`, `<%> partial%>`, `<%! comment %>`,
+    /// `<%~name~%>` and raw blocks `<%<%raw%>%>`. Braces become plain text.
+    ///
+    /// The delimiters apply to templates registered after the call. A
+    /// delimiter can not be empty or contain whitespace or `=`, templates
+    /// fail to compile with an invalid delimiters error
+    /// otherwise. To use other delimiters for a single template, compile it
+    /// with `Template::compile_with_options` and add it with
+    /// `register_template`.
+    pub fn set_delimiters(&mut self, open: &str, close: &str) {
+        self.delimiters = Some((open.to_owned(), close.to_owned()));
+    }
+
+    /// Use the default delimiters `{{` and `}}` again
+    pub fn reset_delimiters(&mut self) {
+        self.delimiters = None;
+    }
+
+    /// Keep the lines of standalone tags
+    ///
+    /// Block tags like `{{#each list}}`, `{{else}}` and `{{/each}}`, comments
+    /// and partials alone on their line, with only whitespace around them,
+    /// are standalone: their whole line is removed from the output. The
+    /// output of a standalone partial is indented like the partial tag.
+    ///
+    /// When enabled, these lines are kept, like any other text, for templates
+    /// registered after the call.
+    ///
+    /// Default is false.
+    pub fn set_ignore_standalone(&mut self, enable: bool) {
+        self.ignore_standalone = enable;
+    }
+
+    /// Return if the lines of standalone tags are kept, default is false.
+    pub fn ignore_standalone(&self) -> bool {
+        self.ignore_standalone
+    }
+
+    /// Return the open and close delimiters of tags
+    pub fn delimiters(&self) -> (&str, &str) {
+        match self.delimiters {
+            Some((ref open, ref close)) => (open, close),
+            None => ("{{", "}}"),
+        }
+    }
+
+    pub(crate) fn template_options(&self) -> TemplateOptions {
+        TemplateOptions {
+            source_map: self.source_map,
+            mustache: self.mustache_mode,
+            delimiters: self.delimiters.clone(),
+            ignore_standalone: self.ignore_standalone,
+        }
+    }
+
     /// Register a template string
     ///
     /// Returns `TemplateError` if there is syntax error on parsing template.
@@ -178,11 +392,24 @@ impl Registry {
     where
         S: AsRef<str>,
     {
-        Template::compile_with_name(tpl_str, name.to_owned(), self.source_map)
-            .and_then(|t| Ok(self.templates.insert(name.to_string(), t)))?;
+        Template::compile_named(tpl_str, name.to_owned(), &self.template_options())
+            .and_then(|t| Ok(self.templates.insert(name.to_string(), Arc::new(t))))?;
+        self.template_files.remove(name);
+        self.forget_loaded_template(name);
         Ok(())
     }
 
+    /// Register a compiled template
+    ///
+    /// Useful for templates compiled ahead of time, like the ones generated
+    /// by `Precompiler`. The template takes `name` as its name.
+    pub fn register_template(&mut self, name: &str, mut tpl: Template) {
+        tpl.name = Some(name.to_owned());
+        self.templates.insert(name.to_owned(), Arc::new(tpl));
+        self.template_files.remove(name);
+        self.forget_loaded_template(name);
+    }
+
     /// Register a partial string
     ///
     /// A named partial will be added to the registry. It will overwrite template with
@@ -195,6 +422,8 @@ impl Registry {
     }
 
     /// Register a template from a path
+    ///
+    /// The path is remembered so the template can be reloaded in dev mode.
     pub fn register_template_file<P>(
         &mut self,
         name: &str,
@@ -203,9 +432,16 @@ impl Registry {
     where
         P: AsRef<Path>,
     {
+        let tpl_path = tpl_path.as_ref();
+        let modified = modified_time(tpl_path).ok();
         let mut file =
             File::open(tpl_path).map_err(|e| TemplateFileError::IOError(e, name.to_owned()))?;
-        self.register_template_source(name, &mut file)
+        self.register_template_source(name, &mut file)?;
+        self.template_files.insert(
+            name.to_owned(),
+            TemplateFile::new(tpl_path.to_path_buf(), modified),
+        );
+        Ok(())
     }
 
     /// Register templates from a directory
@@ -274,6 +510,21 @@ impl Registry {
     /// remove a template from the registry
     pub fn unregister_template(&mut self, name: &str) {
         self.templates.remove(name);
+        self.template_files.remove(name);
+        self.forget_loaded_template(name);
+    }
+
+    fn forget_loaded_template(&mut self, name: &str) {
+        self.loaded_templates.get_mut().unwrap().remove(name);
+    }
+
+    /// Register a template loader
+    ///
+    /// Loaders are asked, in the order they are registered, for templates
+    /// and partials that are not registered. A loaded template is compiled
+    /// on first use and kept in the registry for later renders.
+    pub fn register_template_loader(&mut self, loader: Box<dyn TemplateLoader + 'static>) {
+        self.template_loaders.push(Arc::from(loader));
     }
 
     /// register a helper
@@ -281,8 +532,153 @@ impl Registry {
         &mut self,
         name: &str,
         def: Box<dyn HelperDef + 'static>,
-    ) -> Option<Box<dyn HelperDef + 'static>> {
-        self.helpers.insert(name.to_string(), def)
+    ) -> Option<Arc<dyn HelperDef + 'static>> {
+        self.helpers.insert(name.to_string(), Arc::from(def))
+    }
+
+    /// Register the layout helpers
+    ///
+    /// `extend` and `block`, replacing helpers of the same names. A layout
+    /// declares named blocks with `{{#block "name"}}default{{/block}}`,
+    /// and a template renders it with `{{#extend "layout"}}`, replacing
+    /// the blocks it defines.
+    pub fn register_layout_helpers(&mut self) {
+        self.register_helper("extend", Box::new(helpers::EXTEND_HELPER));
+        self.register_helper("block", Box::new(helpers::BLOCK_HELPER));
+    }
+
+    /// Register the switch helpers
+    ///
+    /// `switch`, `case` and `default`, replacing helpers of the same names.
+    /// `{{#switch status}}{{#case "up"}}...{{/case}}{{#default}}...{{/default}}{{/switch}}`
+    /// renders the first case matching the value.
+    pub fn register_switch_helpers(&mut self) {
+        self.register_helper("switch", Box::new(helpers::SWITCH_HELPER));
+        self.register_helper("case", Box::new(helpers::CASE_HELPER));
+        self.register_helper("default", Box::new(helpers::DEFAULT_HELPER));
+    }
+
+    /// Register the variable helpers
+    ///
+    /// The `let` helper and the `set` decorator, replacing the helper and
+    /// decorator of the same names. `{{#let total=(sum prices)}}` names
+    /// values in its block, and `{{*set total=(sum prices)}}` names them for
+    /// the rest of the enclosing block.
+    pub fn register_variable_helpers(&mut self) {
+        self.register_helper("let", Box::new(helpers::LET_HELPER));
+        self.register_decorator("set", Box::new(directives::SET_DIRECTIVE));
+    }
+
+    /// Register the arithmetic helpers
+    ///
+    /// `add`, `sub`, `mul`, `div`, `mod`, `abs`, `min`, `max`, `round`,
+    /// `floor`, `ceil` and `sum`, replacing helpers of the same names.
+    /// Results of integers are integers as long as they fit in `i64`.
+    pub fn register_math_helpers(&mut self) {
+        use crate::helpers::helper_math as math;
+
+        self.register_helper("add", Box::new(math::ADD_HELPER));
+        self.register_helper("sub", Box::new(math::SUB_HELPER));
+        self.register_helper("mul", Box::new(math::MUL_HELPER));
+        self.register_helper("div", Box::new(math::DIV_HELPER));
+        self.register_helper("mod", Box::new(math::MOD_HELPER));
+        self.register_helper("abs", Box::new(math::ABS_HELPER));
+        self.register_helper("min", Box::new(math::MIN_HELPER));
+        self.register_helper("max", Box::new(math::MAX_HELPER));
+        self.register_helper("round", Box::new(math::ROUND_HELPER));
+        self.register_helper("floor", Box::new(math::FLOOR_HELPER));
+        self.register_helper("ceil", Box::new(math::CEIL_HELPER));
+        self.register_helper("sum", Box::new(math::SUM_HELPER));
+    }
+
+    /// Register the collection helpers
+    ///
+    /// `len`, `first`, `last`, `slice`, `reverse`, `sort_by`, `filter_by`,
+    /// `group_by`, `uniq`, `keys`, `values` and `join`, replacing helpers of
+    /// the same names. They return new values, so they are mostly used as
+    /// subexpressions like `{{#each (sort_by posts "date")}}`.
+    pub fn register_collection_helpers(&mut self) {
+        use crate::helpers::helper_collection as collection;
+
+        self.register_helper("len", Box::new(collection::LEN_HELPER));
+        self.register_helper("first", Box::new(collection::FIRST_HELPER));
+        self.register_helper("last", Box::new(collection::LAST_HELPER));
+        self.register_helper("slice", Box::new(collection::SLICE_HELPER));
+        self.register_helper("reverse", Box::new(collection::REVERSE_HELPER));
+        self.register_helper("sort_by", Box::new(collection::SORT_BY_HELPER));
+        self.register_helper("filter_by", Box::new(collection::FILTER_BY_HELPER));
+        self.register_helper("group_by", Box::new(collection::GROUP_BY_HELPER));
+        self.register_helper("uniq", Box::new(collection::UNIQ_HELPER));
+        self.register_helper("keys", Box::new(collection::KEYS_HELPER));
+        self.register_helper("values", Box::new(collection::VALUES_HELPER));
+        self.register_helper("join", Box::new(collection::JOIN_HELPER));
+    }
+
+    /// Register the number formatting helpers
+    ///
+    /// `format_number`, `format_percent` and `format_currency`, and the
+    /// `locale` decorator setting their locale for the rest of the render,
+    /// replacing helpers and decorators of the same names.
+    pub fn register_number_helpers(&mut self) {
+        use crate::helpers::helper_number as number;
+
+        self.register_helper("format_number", Box::new(number::FORMAT_NUMBER_HELPER));
+        self.register_helper("format_percent", Box::new(number::FORMAT_PERCENT_HELPER));
+        self.register_helper("format_currency", Box::new(number::FORMAT_CURRENCY_HELPER));
+        self.register_decorator("locale", Box::new(directives::LOCALE_DIRECTIVE));
+    }
+
+    /// Register the string helpers
+    ///
+    /// `upper`, `lower`, `capitalize`, `trim`, `replace`, `split`,
+    /// `truncate`, `pad_left`, `pad_right`, `starts_with`, `ends_with`,
+    /// `contains` and `repeat`, replacing helpers of the same names. They
+    /// return values, so they can also be used as subexpressions like
+    /// `{{#each (split tags ",")}}`.
+    ///
+    /// Available with the `string_helpers` feature.
+    #[cfg(feature = "string_helpers")]
+    pub fn register_string_helpers(&mut self) {
+        use crate::helpers::helper_string as string;
+
+        self.register_helper("upper", Box::new(string::upper));
+        self.register_helper("lower", Box::new(string::lower));
+        self.register_helper("capitalize", Box::new(string::capitalize));
+        self.register_helper("trim", Box::new(string::trim));
+        self.register_helper("replace", Box::new(string::replace));
+        self.register_helper("split", Box::new(string::split));
+        self.register_helper("truncate", Box::new(string::TRUNCATE_HELPER));
+        self.register_helper("pad_left", Box::new(string::PAD_LEFT_HELPER));
+        self.register_helper("pad_right", Box::new(string::PAD_RIGHT_HELPER));
+        self.register_helper("starts_with", Box::new(string::starts_with));
+        self.register_helper("ends_with", Box::new(string::ends_with));
+        self.register_helper("contains", Box::new(string::contains));
+        self.register_helper("repeat", Box::new(string::REPEAT_HELPER));
+    }
+
+    /// Register the date helpers
+    ///
+    /// `format_date`, `relative_time` and `to_timezone`, replacing helpers
+    /// of the same names. Dates are RFC 3339 strings, `YYYY-MM-DD` dates or
+    /// unix epochs in seconds. See `RelativeTimeHelper` to set the time
+    /// `relative_time` is relative to.
+    ///
+    /// Available with the `date_helpers` feature.
+    #[cfg(feature = "date_helpers")]
+    pub fn register_date_helpers(&mut self) {
+        use crate::helpers::helper_date as date;
+
+        self.register_helper("format_date", Box::new(date::FORMAT_DATE_HELPER));
+        self.register_helper(
+            "relative_time",
+            Box::new(date::RelativeTimeHelper::default()),
+        );
+        self.register_helper("to_timezone", Box::new(date::TO_TIMEZONE_HELPER));
+    }
+
+    /// remove a helper from the registry
+    pub fn unregister_helper(&mut self, name: &str) -> Option<Arc<dyn HelperDef + 'static>> {
+        self.helpers.remove(name)
     }
 
     /// register a decorator
@@ -290,8 +686,13 @@ impl Registry {
         &mut self,
         name: &str,
         def: Box<dyn DirectiveDef + 'static>,
-    ) -> Option<Box<dyn DirectiveDef + 'static>> {
-        self.directives.insert(name.to_string(), def)
+    ) -> Option<Arc<dyn DirectiveDef + 'static>> {
+        self.directives.insert(name.to_string(), Arc::from(def))
+    }
+
+    /// remove a decorator from the registry
+    pub fn unregister_decorator(&mut self, name: &str) -> Option<Arc<dyn DirectiveDef + 'static>> {
+        self.directives.remove(name)
     }
 
     /// Register a new *escape fn* to be used from now on by this registry.
@@ -299,12 +700,12 @@ impl Registry {
         &mut self,
         escape_fn: F,
     ) {
-        self.escape_fn = Box::new(escape_fn);
+        self.escape_fn = Arc::new(escape_fn);
     }
 
     /// Restore the default *escape fn*.
     pub fn unregister_escape_fn(&mut self) {
-        self.escape_fn = Box::new(html_escape);
+        self.escape_fn = Arc::new(html_escape);
     }
 
     /// Get a reference to the current *escape fn*.
@@ -314,12 +715,33 @@ impl Registry {
 
     /// Return `true` if a template is registered for the given name
     pub fn has_template(&self, name: &str) -> bool {
-        self.get_template(name).is_some()
+        self.templates.contains_key(name)
     }
 
     /// Return a registered template,
+    ///
+    /// This is the template as it was registered, use `current_template`
+    /// to get the one reloaded in dev mode.
     pub fn get_template(&self, name: &str) -> Option<&Template> {
-        self.templates.get(name)
+        self.templates.get(name).map(|t| t.as_ref())
+    }
+
+    /// Return the template used to render `name`
+    ///
+    /// In dev mode, a template registered from a file is reloaded first
+    /// when the file has changed, and the last good one is returned.
+    /// Otherwise this is the registered template.
+    pub fn current_template(&self, name: &str) -> Option<Arc<Template>> {
+        if self.dev_mode {
+            if let Some(file) = self.template_files.get(name) {
+                // a broken file is reported when rendering
+                let _ = self.reload_template_file(name, file);
+                if let Some(ref t) = file.state.read().unwrap().reloaded {
+                    return Some(t.clone());
+                }
+            }
+        }
+        self.templates.get(name).cloned()
     }
 
     /// Return a registered helper
@@ -333,30 +755,251 @@ impl Registry {
     }
 
     /// Return all templates registered
-    pub fn get_templates(&self) -> &HashMap<String, Template> {
+    ///
+    /// These are the templates as they were registered, templates reloaded
+    /// in dev mode are only returned by `current_template`.
+    pub fn get_templates(&self) -> &HashMap<String, Arc<Template>> {
         &self.templates
     }
 
+    /// Report the helpers, decorators and partials used by registered
+    /// templates that can't be found
+    ///
+    /// Partials are also searched with the template loaders. Inline
+    /// partials count as found in the template defining them, and partial
+    /// blocks like `{{#> name}}fallback{{/name}}` as found, as they render
+    /// their block instead of a missing partial.
+    pub fn unresolved_references(&self) -> Vec<UnresolvedReference> {
+        let mut names: Vec<&String> = self.templates.keys().collect();
+        names.sort();
+
+        let mut unresolved = Vec::new();
+        for name in names {
+            let report = self.templates[name].analyze();
+            for reference in report.references.iter() {
+                let resolved = match reference.kind {
+                    ReferenceKind::Helper => self.helpers.contains_key(&reference.name),
+                    ReferenceKind::Decorator => self.directives.contains_key(&reference.name),
+                    // a partial block renders its block when the partial is missing
+                    ReferenceKind::Partial => {
+                        reference.block
+                            || reference.name.starts_with('@')
+                            || report.inline_partials.contains(&reference.name)
+                            || self
+                                .get_or_load_template_optional(&reference.name)
+                                .map(|t| t.is_some())
+                                .unwrap_or(false)
+                    }
+                    ReferenceKind::Variable | ReferenceKind::BlockParam => true,
+                };
+                if !resolved {
+                    unresolved.push(UnresolvedReference {
+                        template: name.clone(),
+                        reference: reference.clone(),
+                    });
+                }
+            }
+        }
+        unresolved
+    }
+
+    /// Report the paths of a registered template that can't be resolved
+    /// with a schema, see `Schema`
+    ///
+    /// Expressions naming a registered helper are not treated as paths.
+    pub fn check_template_schema(
+        &self,
+        name: &str,
+        schema: &Schema,
+    ) -> Result<Vec<UnresolvedPath>, RenderError> {
+        let t = self
+            .get_or_load_template_optional(name)?
+            .ok_or_else(|| RenderError::new(format!("Template not found: {}", name)))?;
+        Ok(schema.check_with(&t, &|name| self.helpers.contains_key(name)))
+    }
+
+    /// Enable or disable a rule of the linter
+    pub fn set_lint_rule(&mut self, rule: LintRule, enabled: bool) {
+        if enabled {
+            self.disabled_lint_rules.remove(&rule);
+        } else {
+            self.disabled_lint_rules.insert(rule);
+        }
+    }
+
+    /// Return if a rule of the linter is enabled
+    pub fn lint_rule_enabled(&self, rule: LintRule) -> bool {
+        !self.disabled_lint_rules.contains(&rule)
+    }
+
+    /// Lint all registered templates with the enabled rules
+    ///
+    /// ```
+    /// use handlebars::Handlebars;
+    ///
+    /// let mut handlebars = Handlebars::new();
+    /// handlebars.register_template_string("t0", "{{{body}}}").unwrap();
+    ///
+    /// let findings = handlebars.lint();
+    /// assert_eq!(findings[0].to_string(), "t0:1:1: [unescaped-output] Output is not escaped");
+    /// ```
+    pub fn lint(&self) -> Vec<LintFinding> {
+        let used_partials = self.used_partials();
+        let mut names: Vec<&String> = self.templates.keys().collect();
+        names.sort();
+
+        names
+            .into_iter()
+            .flat_map(|name| lint::lint_template(self, &self.templates[name], &used_partials))
+            .collect()
+    }
+
+    /// Lint a template with the enabled rules
+    pub fn lint_template(&self, name: &str) -> Result<Vec<LintFinding>, RenderError> {
+        let t = self
+            .get_or_load_template_optional(name)?
+            .ok_or_else(|| RenderError::new(format!("Template not found: {}", name)))?;
+        Ok(lint::lint_template(self, &t, &self.used_partials()))
+    }
+
+    // names of the partials included by registered templates
+    fn used_partials(&self) -> HashSet<String> {
+        self.templates
+            .values()
+            .flat_map(|t| t.analyze().references)
+            .filter(|r| r.kind == ReferenceKind::Partial)
+            .map(|r| r.name)
+            .collect()
+    }
+
     /// Unregister all templates
     pub fn clear_templates(&mut self) {
         self.templates.clear();
+        self.template_files.clear();
+        self.loaded_templates.get_mut().unwrap().clear();
+    }
+
+    /// Return a template for rendering, reloading it from its file first
+    /// when dev mode is enabled and the file has changed, or asking the
+    /// template loaders when it's not registered.
+    pub(crate) fn get_or_load_template_optional(
+        &self,
+        name: &str,
+    ) -> Result<Option<TemplateRef<'_>>, RenderError> {
+        if self.dev_mode {
+            if let Some(file) = self.template_files.get(name) {
+                return self
+                    .reload_template_file(name, file)
+                    .map_err(RenderError::from);
+            }
+        }
+        if let Some(t) = self.templates.get(name) {
+            return Ok(Some(TemplateRef::Borrowed(t)));
+        }
+        self.load_template(name).map_err(RenderError::from)
+    }
+
+    fn load_template(&self, name: &str) -> Result<Option<TemplateRef<'_>>, TemplateFileError> {
+        if self.template_loaders.is_empty() {
+            return Ok(None);
+        }
+        if !self.dev_mode {
+            if let Some(t) = self.loaded_templates.read().unwrap().get(name) {
+                return Ok(Some(TemplateRef::Loaded(t.clone())));
+            }
+        }
+
+        for loader in &self.template_loaders {
+            let source = loader
+                .load(name)
+                .map_err(|e| TemplateFileError::IOError(e, name.to_owned()))?;
+            if let Some(source) = source {
+                let t = Arc::new(Template::compile_named(
+                    source,
+                    name.to_owned(),
+                    &self.template_options(),
+                )?);
+                if !self.dev_mode {
+                    self.loaded_templates
+                        .write()
+                        .unwrap()
+                        .insert(name.to_owned(), t.clone());
+                }
+                return Ok(Some(TemplateRef::Loaded(t)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn reload_template_file(
+        &self,
+        name: &str,
+        file: &TemplateFile,
+    ) -> Result<Option<TemplateRef<'_>>, TemplateFileError> {
+        let modified = modified_time(&file.path)
+            .map_err(|e| TemplateFileError::IOError(e, name.to_owned()))?;
+
+        {
+            let state = file.state.read().unwrap();
+            if state.modified == Some(modified) {
+                return Ok(match state.reloaded {
+                    Some(ref t) => Some(TemplateRef::Loaded(t.clone())),
+                    None => self.templates.get(name).map(|t| TemplateRef::Borrowed(t)),
+                });
+            }
+        }
+
+        let mut tpl_str = String::new();
+        File::open(&file.path)
+            .and_then(|mut f| f.read_to_string(&mut tpl_str))
+            .map_err(|e| TemplateFileError::IOError(e, name.to_owned()))?;
+        let t = Arc::new(Template::compile_named(
+            tpl_str,
+            name.to_owned(),
+            &self.template_options(),
+        )?);
+
+        let mut state = file.state.write().unwrap();
+        state.modified = Some(modified);
+        state.reloaded = Some(t.clone());
+        Ok(Some(TemplateRef::Loaded(t)))
     }
 
     fn render_to_output<T, O>(
         &self,
         name: &str,
         data: &T,
+        locale: Option<&str>,
         output: &mut O,
     ) -> Result<(), RenderError>
     where
         T: Serialize,
         O: Output,
     {
-        self.get_template(name)
+        let ctx = Context::wraps(data)?;
+        self.render_context_to_output(name, &ctx, locale, output)
+    }
+
+    pub(crate) fn render_context_to_output<O>(
+        &self,
+        name: &str,
+        ctx: &Context,
+        locale: Option<&str>,
+        output: &mut O,
+    ) -> Result<(), RenderError>
+    where
+        O: Output,
+    {
+        self.get_or_load_template_optional(name)?
             .ok_or_else(|| RenderError::new(format!("Template not found: {}", name)))
             .and_then(|t| {
-                let ctx = Context::wraps(data)?;
                 let mut render_context = RenderContext::new(t.name.as_ref());
+                if let Some(locale) = locale {
+                    if helpers::helper_number::find_locale(locale).is_none() {
+                        return Err(RenderError::new(format!("Unknown locale \"{}\"", locale)));
+                    }
+                    render_context.set_locale(locale.to_owned());
+                }
                 t.render(self, &ctx, &mut render_context, output)
             })
             .map(|_| ())
@@ -373,7 +1016,27 @@ impl Registry {
         T: Serialize,
     {
         let mut output = StringOutput::new();
-        self.render_to_output(name, data, &mut output)?;
+        self.render_to_output(name, data, None, &mut output)?;
+        output.into_string().map_err(RenderError::from)
+    }
+
+    /// Render a registered template with some data into a string, formatting
+    /// numbers for `locale`, like `de-DE`
+    ///
+    /// The formatting helpers are registered by `register_number_helpers`.
+    /// The `locale` decorator and their `locale` hash param still take
+    /// precedence.
+    pub fn render_with_locale<T>(
+        &self,
+        name: &str,
+        data: &T,
+        locale: &str,
+    ) -> Result<String, RenderError>
+    where
+        T: Serialize,
+    {
+        let mut output = StringOutput::new();
+        self.render_to_output(name, data, Some(locale), &mut output)?;
         output.into_string().map_err(RenderError::from)
     }
 
@@ -384,7 +1047,7 @@ impl Registry {
         W: Write,
     {
         let mut output = WriteOutput::new(writer);
-        self.render_to_output(name, data, &mut output)
+        self.render_to_output(name, data, None, &mut output)
     }
 
     /// render a template string using current registry without register it
@@ -412,7 +1075,7 @@ impl Registry {
         T: Serialize,
         W: Write,
     {
-        let tpl = Template::compile2(template_string, self.source_map)?;
+        let tpl = Template::compile_with_options(template_string, &self.template_options())?;
         let ctx = Context::wraps(data)?;
         let mut render_context = RenderContext::new(None);
         let mut out = WriteOutput::new(writer);
@@ -451,6 +1114,8 @@ mod test {
     use crate::render::{Helper, RenderContext, Renderable};
     use crate::support::str::StringWriter;
     #[cfg(not(feature = "no_dir_source"))]
+    use crate::template::Template;
+    #[cfg(not(feature = "no_dir_source"))]
     use std::fs::{DirBuilder, File};
     #[cfg(not(feature = "no_dir_source"))]
     use std::io::Write;
@@ -607,6 +1272,78 @@ mod test {
         }
     }
 
+    #[test]
+    #[cfg(not(feature = "no_dir_source"))]
+    fn test_dev_mode_template_reload() {
+        use super::modified_time;
+        use std::thread::sleep;
+        use std::time::Duration;
+
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("t0.hbs");
+        // rewrite the file until its modification time changes
+        let write_template = |content: &str| {
+            let before = modified_time(&file_path).ok();
+            loop {
+                let mut file = File::create(&file_path).unwrap();
+                write!(file, "{}", content).unwrap();
+                drop(file);
+                if modified_time(&file_path).ok() != before {
+                    break;
+                }
+                sleep(Duration::from_millis(10));
+            }
+        };
+
+        let mut r = Registry::new();
+        write_template("<h1>{{title}}</h1>");
+        r.register_template_file("t0", &file_path).unwrap();
+
+        let data = json!({"title": "hello"});
+        write_template("<h2>{{title}}</h2>");
+        // changes are ignored unless dev mode is on
+        assert_eq!(r.render("t0", &data).unwrap(), "<h1>hello</h1>");
+
+        r.set_dev_mode(true);
+        assert!(r.dev_mode());
+        assert_eq!(r.render("t0", &data).unwrap(), "<h2>hello</h2>");
+        assert_eq!(r.render("t0", &data).unwrap(), "<h2>hello</h2>");
+
+        // a broken template is reported and the last good one is kept
+        write_template("<h3>{{#if title}}</h3>");
+        let e = r.render("t0", &data).unwrap_err();
+        assert!(e.desc.contains("invalid handlebars syntax"));
+        assert_eq!(
+            r.current_template("t0").unwrap().elements,
+            Template::compile("<h2>{{title}}</h2>").unwrap().elements
+        );
+
+        write_template("<h3>{{title}}</h3>");
+        assert_eq!(
+            r.current_template("t0").unwrap().elements,
+            Template::compile("<h3>{{title}}</h3>").unwrap().elements
+        );
+        // the registered template is unchanged
+        assert_eq!(
+            r.get_template("t0").unwrap().elements,
+            Template::compile("<h1>{{title}}</h1>").unwrap().elements
+        );
+        assert_eq!(r.render("t0", &data).unwrap(), "<h3>hello</h3>");
+
+        // partials are reloaded as well
+        r.register_template_string("t1", "{{> t0}}!").unwrap();
+        write_template("<h4>{{title}}</h4>");
+        assert_eq!(r.render("t1", &data).unwrap(), "<h4>hello</h4>!");
+
+        // registering from a string stops tracking the file
+        r.register_template_string("t0", "<p>{{title}}</p>")
+            .unwrap();
+        write_template("<h5>{{title}}</h5>");
+        assert_eq!(r.render("t0", &data).unwrap(), "<p>hello</p>");
+
+        dir.close().unwrap();
+    }
+
     #[test]
     fn test_render_to_write() {
         let mut r = Registry::new();
@@ -1,4 +1,5 @@
 use std::borrow::Borrow;
+use std::cell::Cell;
 use std::collections::VecDeque;
 use std::fmt;
 use std::ops::Deref;
@@ -9,7 +10,7 @@ use serde_json::value::Value as Json;
 
 use crate::context::{self, BlockParamHolder, BlockParams, Context};
 use crate::error::RenderError;
-use crate::helpers::HelperDef;
+use crate::helpers::{self, HelperDef};
 use crate::output::{Output, StringOutput};
 use crate::partial;
 use crate::registry::Registry;
@@ -36,6 +37,12 @@ pub struct RenderContext<'reg> {
 #[derive(Clone)]
 pub struct RenderContextInner<'reg> {
     partials: HashMap<String, &'reg Template>,
+    /// blocks of the partial blocks being rendered, innermost first
+    partial_blocks: VecDeque<&'reg Template>,
+    /// blocks defined by the templates extending the layout being rendered
+    layout_blocks: HashMap<String, &'reg Template>,
+    /// names of the templates extending the layout being rendered
+    extending: Vec<&'reg String>,
     local_helpers: HashMap<String, Rc<Box<dyn HelperDef + 'static>>>,
     local_variables: HashMap<String, Json>,
     /// current template name
@@ -43,6 +50,13 @@ pub struct RenderContextInner<'reg> {
     /// root template name
     root_template: Option<&'reg String>,
     disable_escape: bool,
+    /// locale of the formatting helpers, set by the `locale` decorator
+    locale: Option<String>,
+    /// the value of the innermost `switch` being rendered, and whether one
+    /// of its cases matched
+    switch: Option<(Json, Rc<Cell<bool>>)>,
+    /// set when the output of an indented partial is at the start of a line
+    line_start: Option<Rc<Cell<bool>>>,
 }
 
 #[derive(Debug, Clone)]
@@ -51,6 +65,10 @@ pub struct BlockRenderContext {
     local_path_root: VecDeque<String>,
     // current block context variables
     block_context: VecDeque<BlockParams>,
+    // values without a path in the data, like the results of
+    // subexpressions, that are the context of the enclosing blocks
+    // since `path`, innermost first
+    value_context: VecDeque<Json>,
 }
 
 impl BlockRenderContext {
@@ -59,6 +77,7 @@ impl BlockRenderContext {
             path: ".".to_owned(),
             local_path_root: VecDeque::new(),
             block_context: VecDeque::new(),
+            value_context: VecDeque::new(),
         }
     }
 }
@@ -68,11 +87,17 @@ impl<'reg> RenderContext<'reg> {
     pub fn new(root_template: Option<&'reg String>) -> RenderContext<'reg> {
         let inner = Rc::new(RenderContextInner {
             partials: HashMap::new(),
+            partial_blocks: VecDeque::new(),
+            layout_blocks: HashMap::new(),
+            extending: Vec::new(),
             local_variables: HashMap::new(),
             local_helpers: HashMap::new(),
             current_template: None,
             root_template,
             disable_escape: false,
+            locale: None,
+            switch: None,
+            line_start: None,
         });
 
         let block = Rc::new(BlockRenderContext::new());
@@ -129,7 +154,60 @@ impl<'reg> RenderContext<'reg> {
         context: &'rc Context,
         path: &str,
     ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
-        context.navigate(
+        let values = &self.block().value_context;
+        if values.is_empty() {
+            return context.navigate(
+                self.get_path(),
+                self.get_local_path_root(),
+                path,
+                &self.block.block_context,
+            );
+        }
+
+        // `../` goes up through the value contexts first
+        let mut ups = 0;
+        let mut rest = path;
+        while rest.starts_with("..") && ups < values.len() {
+            ups += 1;
+            rest = &rest[2..];
+            if rest.starts_with('/') || rest.starts_with('.') {
+                rest = &rest[1..];
+            }
+        }
+        if rest.is_empty() {
+            rest = ".";
+        }
+        let first = rest.split(&['.', '/'][..]).next().unwrap_or("");
+        let rest = if first == "@root"
+            || context::get_in_block_params(&self.block.block_context, first).is_some()
+        {
+            path
+        } else if ups < values.len() {
+            return context::navigate_value(&values[ups], rest);
+        } else {
+            rest
+        };
+
+        let value = context.navigate(
+            self.get_path(),
+            self.get_local_path_root(),
+            rest,
+            &self.block.block_context,
+        )?;
+        // the path of the value is absolute, as it is not relative to the
+        // value context
+        match value {
+            ScopedJson::Context(v) => {
+                let keys = self.resolve_path(rest)?;
+                Ok(ScopedJson::BlockContext(v, context::keys_path(&keys)))
+            }
+            value => Ok(value),
+        }
+    }
+
+    /// Returns the keys of `path` from the root of the data
+    pub(crate) fn resolve_path(&self, path: &str) -> Result<Vec<String>, RenderError> {
+        context::resolve_path(
             self.get_path(),
             self.get_local_path_root(),
             path,
@@ -137,7 +215,47 @@ impl<'reg> RenderContext<'reg> {
         )
     }
 
-    pub fn get_partial(&self, name: &str) -> Option<&&Template> {
+    /// Mustache lookup of a name missing from the current context: the first
+    /// key of the name is searched in the current context, then in the
+    /// enclosing ones. Returns the absolute path and the value of the name in
+    /// the first context having the key.
+    pub(crate) fn evaluate_in_context_stack<'rc>(
+        &self,
+        context: &'rc Context,
+        path: &str,
+    ) -> Option<(String, ScopedJson<'reg, 'rc>)> {
+        let first = path.split(&['.', '/'][..]).next().unwrap_or("");
+        // `.`, `this`, `../name` and `[key]` are only relative to the
+        // current context
+        let walk = !(first.is_empty()
+            || first.starts_with('@')
+            || first.starts_with('[')
+            || first == "this");
+        let key = if walk { first } else { path };
+
+        let no_roots = VecDeque::new();
+        let no_block_params = VecDeque::new();
+        let enclosing = self.get_local_path_root().iter().filter(|_| walk);
+        for base in Some(self.get_path()).into_iter().chain(enclosing) {
+            // keys missing or not valid in the context are both not found
+            let found = context
+                .navigate(base, &no_roots, key, &no_block_params)
+                .map(|v| !v.is_missing())
+                .unwrap_or(false);
+            if found {
+                let value = context
+                    .navigate(base, &no_roots, path, &no_block_params)
+                    .unwrap_or(ScopedJson::Missing);
+                return Some((format!("{}/{}", base, path), value));
+            }
+        }
+        None
+    }
+
+    pub fn get_partial(&self, name: &str) -> Option<&&'reg Template> {
+        if name == "@partial-block" {
+            return self.inner().partial_blocks.front();
+        }
         self.inner().partials.get(name)
     }
 
@@ -145,6 +263,41 @@ impl<'reg> RenderContext<'reg> {
         self.inner_mut().partials.insert(name, result);
     }
 
+    /// Sets the block of a partial block, rendered by `{{> @partial-block}}`
+    pub(crate) fn push_partial_block(&mut self, block: &'reg Template) {
+        self.inner_mut().partial_blocks.push_front(block);
+    }
+
+    /// Restores the `@partial-block` of the enclosing partial block, so
+    /// that a partial block renders the ones it is nested in
+    pub(crate) fn pop_partial_block(&mut self) {
+        self.inner_mut().partial_blocks.pop_front();
+    }
+
+    /// Returns the block of a layout replaced by an extending template
+    pub(crate) fn get_layout_block(&self, name: &str) -> Option<&'reg Template> {
+        self.inner().layout_blocks.get(name).cloned()
+    }
+
+    /// Replaces a block of the layout being rendered, unless a template
+    /// extending it already did
+    pub(crate) fn set_layout_block(&mut self, name: String, block: &'reg Template) {
+        self.inner_mut().layout_blocks.entry(name).or_insert(block);
+    }
+
+    /// Returns `true` if the template `name` extends the layout being
+    /// rendered, directly or through other layouts
+    pub(crate) fn is_extending(&self, name: &str) -> bool {
+        self.inner().extending.iter().any(|s| *s == name)
+    }
+
+    /// Marks the current template as extending the layout it renders next
+    pub(crate) fn push_extending(&mut self) {
+        if let Some(name) = self.inner().current_template {
+            self.inner_mut().extending.push(name);
+        }
+    }
+
     pub fn set_local_var(&mut self, name: String, value: Json) {
         self.inner_mut().local_variables.insert(name, value);
     }
@@ -172,7 +325,39 @@ impl<'reg> RenderContext<'reg> {
     }
 
     pub fn get_local_var(&self, name: &str) -> Option<&Json> {
-        self.inner().local_variables.get(name)
+        let inner = self.inner();
+        match inner.local_variables.get(name) {
+            // `@switch` is visible in blocks that hide local variables
+            None if name == "@switch" => inner.switch.as_ref().map(|s| &s.0),
+            value => value,
+        }
+    }
+
+    // a path into a local var, like `@./index`, `@../index` or `@item.name`
+    fn evaluate_local_var_path<'rc>(
+        &self,
+        path: &str,
+    ) -> Result<Option<ScopedJson<'reg, 'rc>>, RenderError> {
+        if !path.starts_with('@') {
+            return Ok(None);
+        }
+        let mut rest = &path[1..];
+        if rest.starts_with("./") {
+            rest = &rest[2..];
+        }
+        let mut ups = 0;
+        while rest[ups..].starts_with("../") {
+            ups += 3;
+        }
+        let (var, sub_path) = match rest[ups..].find(&['.', '/'][..]) {
+            Some(i) => (&rest[..ups + i], Some(&rest[ups + i + 1..])),
+            None => (rest, None),
+        };
+        match (self.get_local_var(&format!("@{}", var)), sub_path) {
+            (Some(value), Some(sub_path)) => context::navigate_value(value, sub_path).map(Some),
+            (Some(value), None) => Ok(Some(ScopedJson::Derived(value.clone()))),
+            (None, _) => Ok(None),
+        }
     }
 
     pub fn is_current_template(&self, p: &str) -> bool {
@@ -224,12 +409,56 @@ impl<'reg> RenderContext<'reg> {
         self.inner_mut().disable_escape = disable
     }
 
+    pub(crate) fn line_start(&self) -> Option<Rc<Cell<bool>>> {
+        self.inner().line_start.clone()
+    }
+
+    pub(crate) fn set_line_start(&mut self, line_start: Rc<Cell<bool>>) {
+        self.inner_mut().line_start = Some(line_start);
+    }
+
+    /// Set the locale of the formatting helpers, like `format_number`, for
+    /// the rest of the render
+    pub fn set_locale(&mut self, locale: String) {
+        self.inner_mut().locale = Some(locale);
+    }
+
+    /// Get the locale set by `set_locale` or the `locale` decorator, if any
+    pub fn get_locale(&self) -> Option<&str> {
+        self.inner().locale.as_ref().map(String::as_str)
+    }
+
+    /// Returns the value of the innermost `switch` being rendered, and
+    /// whether one of its cases matched
+    pub(crate) fn get_switch(&self) -> Option<(&Json, Rc<Cell<bool>>)> {
+        self.inner()
+            .switch
+            .as_ref()
+            .map(|switch| (&switch.0, switch.1.clone()))
+    }
+
+    /// Starts a `switch` on `value`, with no case matched yet
+    pub(crate) fn set_switch(&mut self, value: Json) {
+        self.inner_mut().switch = Some((value, Rc::new(Cell::new(false))));
+    }
+
     pub fn get_path(&self) -> &String {
         &self.block().path
     }
 
     pub fn set_path(&mut self, path: String) {
-        self.block_mut().path = path;
+        let block = self.block_mut();
+        block.path = path;
+        block.value_context.clear();
+    }
+
+    /// Makes a value without a path in the data, like the result of a
+    /// subexpression, the context of the block
+    ///
+    /// `../` in the block goes to the enclosing context, and `@root` to the
+    /// data.
+    pub(crate) fn push_value_context(&mut self, value: Json) {
+        self.block_mut().value_context.push_front(value);
     }
 
     #[deprecated]
@@ -261,16 +490,26 @@ impl<'reg> RenderContext<'reg> {
     pub fn pop_block_context(&mut self) {
         self.block_mut().block_context.pop_front();
     }
+
+    /// Returns the number of block params pushed in the current block
+    pub(crate) fn block_context_len(&self) -> usize {
+        self.block().block_context.len()
+    }
 }
 
 impl<'reg> fmt::Debug for RenderContextInner<'reg> {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         f.debug_struct("RenderContextInner")
             .field("partials", &self.partials)
+            .field("partial_blocks", &self.partial_blocks)
+            .field("layout_blocks", &self.layout_blocks)
+            .field("extending", &self.extending)
             .field("local_variables", &self.local_variables)
             .field("root_template", &self.root_template)
             .field("current_template", &self.current_template)
             .field("disable_eacape", &self.disable_escape)
+            .field("locale", &self.locale)
+            .field("switch", &self.switch)
             .finish()
     }
 }
@@ -428,6 +667,7 @@ pub struct Directive<'reg: 'rc, 'rc> {
     params: Vec<PathAndJson<'reg, 'rc>>,
     hash: HashMap<String, PathAndJson<'reg, 'rc>>,
     template: Option<&'reg Template>,
+    indent: Option<&'reg str>,
 }
 
 impl<'reg: 'rc, 'rc> Directive<'reg, 'rc> {
@@ -456,6 +696,7 @@ impl<'reg: 'rc, 'rc> Directive<'reg, 'rc> {
             params: pv,
             hash: hm,
             template: dt.template.as_ref(),
+            indent: dt.indent.as_ref().map(String::as_str),
         })
     }
 
@@ -488,6 +729,11 @@ impl<'reg: 'rc, 'rc> Directive<'reg, 'rc> {
     pub fn template(&self) -> Option<&'reg Template> {
         self.template
     }
+
+    /// Returns the indentation of a partial alone on its line
+    pub fn indent(&self) -> Option<&'reg str> {
+        self.indent
+    }
 }
 
 /// Render trait
@@ -532,7 +778,12 @@ fn call_helper_for_value<'reg: 'rc, 'rc>(
     rc: &mut RenderContext<'reg>,
 ) -> Result<PathAndJson<'reg, 'rc>, RenderError> {
     if let Some(result) = hd.call_inner(ht, r, ctx, rc)? {
-        Ok(PathAndJson::new(None, result))
+        // a value of the context keeps its path, for blocks changing the
+        // context to it
+        match result.block_context_path().cloned() {
+            Some(path) => Ok(PathAndJson::new_absolute(Some(path), result)),
+            None => Ok(PathAndJson::new(None, result)),
+        }
     } else {
         // parse value from output
         let mut so = StringOutput::new();
@@ -585,6 +836,8 @@ impl Parameter {
                         Some(name.to_owned()),
                         ScopedJson::Derived(value.clone()),
                     ))
+                } else if let Some(value) = rc.evaluate_local_var_path(name)? {
+                    Ok(PathAndJson::new(Some(name.to_owned()), value))
                 } else if let Some(rc_context) = rc.context() {
                     // the context is modified from a decorator
                     // use the modified one
@@ -597,6 +850,11 @@ impl Parameter {
                     ))
                 } else {
                     let value = rc.evaluate(ctx, name)?;
+                    if value.is_missing() && registry.mustache_mode() {
+                        if let Some((path, value)) = rc.evaluate_in_context_stack(ctx, name) {
+                            return Ok(PathAndJson::new_absolute(Some(path), value));
+                        }
+                    }
                     if let Some(ref block_context_path) = value.block_context_path() {
                         Ok(PathAndJson::new_absolute(
                             Some(block_context_path.to_string()),
@@ -615,31 +873,15 @@ impl Parameter {
             Parameter::Literal(ref j) => Ok(PathAndJson::new(None, ScopedJson::Constant(j))),
             Parameter::Subexpression(ref t) => match *t.as_element() {
                 Expression(ref ht) => {
-                    if ht.is_name_only() {
-                        ht.name.expand(registry, ctx, rc)
-                    } else {
-                        let name = ht.name.expand_as_name(registry, ctx, rc)?;
-
-                        let h = Helper::try_from_template(ht, registry, ctx, rc)?;
-                        if let Some(ref d) = rc.get_local_helper(&name) {
-                            let helper_def = d.deref().as_ref();
-                            call_helper_for_value(helper_def, &h, registry, ctx, rc)
-                        } else {
-                            registry
-                                .get_helper(&name)
-                                .or_else(|| {
-                                    registry.get_helper(if ht.block {
-                                        "blockHelperMissing"
-                                    } else {
-                                        "helperMissing"
-                                    })
-                                })
-                                .ok_or_else(|| {
-                                    RenderError::new(format!("Helper not defined: {:?}", ht.name))
-                                })
-                                .and_then(move |d| call_helper_for_value(d, &h, registry, ctx, rc))
+                    expand_subexpression(ht, registry, ctx, rc).map_err(|mut e| {
+                        // errors of inner subexpressions keep their own position
+                        if let (None, Some(&TemplateMapping(line, col))) = (e.line_no, t.mapping())
+                        {
+                            e.line_no = Some(line);
+                            e.column_no = Some(col);
                         }
-                    }
+                        e
+                    })
                 }
                 _ => unreachable!(),
             },
@@ -735,11 +977,48 @@ fn render_helper<'reg: 'rc, 'rc>(
                     "helperMissing"
                 })
             })
+            .or_else(|| {
+                // in Mustache mode, other blocks are sections
+                if ht.block && registry.mustache_mode() {
+                    Some(&helpers::SECTION_HELPER as &dyn HelperDef)
+                } else {
+                    None
+                }
+            })
             .ok_or_else(|| RenderError::new(format!("Helper not defined: {:?}", ht.name)))
             .and_then(move |d| d.call(&h, registry, ctx, rc, out))
     }
 }
 
+fn expand_subexpression<'reg: 'rc, 'rc>(
+    ht: &'reg HelperTemplate,
+    registry: &'reg Registry,
+    ctx: &'rc Context,
+    rc: &mut RenderContext<'reg>,
+) -> Result<PathAndJson<'reg, 'rc>, RenderError> {
+    if ht.is_name_only() {
+        return ht.name.expand(registry, ctx, rc);
+    }
+
+    let h = Helper::try_from_template(ht, registry, ctx, rc)?;
+    if let Some(ref d) = rc.get_local_helper(h.name()) {
+        let helper_def = d.deref().as_ref();
+        call_helper_for_value(helper_def, &h, registry, ctx, rc)
+    } else {
+        registry
+            .get_helper(h.name())
+            .or_else(|| {
+                registry.get_helper(if ht.block {
+                    "blockHelperMissing"
+                } else {
+                    "helperMissing"
+                })
+            })
+            .ok_or_else(|| RenderError::new(format!("Helper not defined: {:?}", h.name())))
+            .and_then(move |d| call_helper_for_value(d, &h, registry, ctx, rc))
+    }
+}
+
 impl Renderable for TemplateElement {
     fn render<'reg: 'rc, 'rc>(
         &'reg self,
@@ -750,7 +1029,22 @@ impl Renderable for TemplateElement {
     ) -> Result<(), RenderError> {
         match *self {
             RawString(ref v) => {
-                out.write(v.as_ref())?;
+                match rc.line_start() {
+                    // in an indented partial, a new line starts after each
+                    // newline of the template
+                    Some(line_start) => {
+                        let mut rest = v.as_str();
+                        while let Some(i) = rest.find('\n') {
+                            out.write(&rest[..=i])?;
+                            line_start.set(true);
+                            rest = &rest[i + 1..];
+                        }
+                        if !rest.is_empty() {
+                            out.write(rest)?;
+                        }
+                    }
+                    None => out.write(v.as_ref())?,
+                }
                 Ok(())
             }
             Expression(ref ht) => {
@@ -942,6 +1236,23 @@ fn test_render_context_promotion_and_demotion() {
     );
 }
 
+#[test]
+fn test_local_var_path() {
+    let r = Registry::new();
+    let mut out = StringOutput::new();
+    let ctx = Context::wraps(&json!({"world": {"bar": "data"}})).unwrap();
+    let template =
+        Template::compile("{{@world.bar}} {{@./world/bar}} {{@../world.bar}} {{@x.y}}").unwrap();
+
+    let mut rc = RenderContext::new(None);
+    rc.set_local_var("@world".to_owned(), json!({"bar": "local"}));
+    rc.promote_local_vars();
+    rc.set_local_var("@world".to_owned(), json!({"bar": "inner"}));
+    template.render(&r, &ctx, &mut rc, &mut out).unwrap();
+
+    assert_eq!(out.into_string().unwrap(), "inner inner local ");
+}
+
 #[test]
 fn test_render_subexpression() {
     use crate::support::str::StringWriter;
@@ -0,0 +1,735 @@
+use pest::Parser;
+use serde::Serialize;
+use serde_json::value::{to_value, Map, Value as Json};
+
+use crate::error::RenderError;
+use crate::grammar::{HandlebarsParser, Rule};
+use crate::template::{
+    sorted_hash, BlockParam, DirectiveTemplate, HelperTemplate, Parameter, Template,
+    TemplateElement, TemplateMapping,
+};
+
+// guard against recursive `$ref`s
+const MAX_REF_DEPTH: usize = 32;
+
+/// The shape of the data a template is rendered with
+///
+/// A schema is a [JSON Schema](https://json-schema.org/) document, of which
+/// `type`, `properties`, `additionalProperties`, `items`, `anyOf`, `oneOf`,
+/// `allOf` and local `$ref`s are used. An object that declares
+/// `properties` only accepts those properties, unless `additionalProperties`
+/// says otherwise, so that misspelled paths are reported.
+///
+/// A schema inferred from sample data only knows the shapes found in the
+/// samples. Optional fields that are `None`, enum variants and items of
+/// arrays that are empty in every sample are unknown, so their paths are
+/// either reported or not checked. Give samples covering these shapes to
+/// `Schema::infer_from_samples`, or write the JSON Schema and use
+/// `Schema::new`.
+///
+/// ```
+/// use handlebars::{Handlebars, Schema};
+/// use serde_json::json;
+///
+/// let mut handlebars = Handlebars::new();
+/// handlebars
+///     .register_template_string("t0", "{{#each users as |u|}}{{u.nmae}}{{/each}}")
+///     .unwrap();
+///
+/// let schema = Schema::infer_from_sample(&json!({"users": [{"name": "Ann"}]})).unwrap();
+/// let errors = handlebars.check_template_schema("t0", &schema).unwrap();
+/// assert_eq!(errors[0].path, "u.nmae");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    root: Json,
+}
+
+/// A path of a template that can't be resolved with a schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnresolvedPath {
+    pub template: Option<String>,
+    pub path: String,
+    /// Line and column of the template element using the path, when the
+    /// template was compiled with source map
+    pub position: Option<(usize, usize)>,
+}
+
+impl Schema {
+    /// Use a JSON Schema document
+    pub fn new(json_schema: Json) -> Schema {
+        Schema { root: json_schema }
+    }
+
+    /// Infer a schema from a sample of the data
+    ///
+    /// The schema only describes the serialized sample, not its type: items
+    /// of arrays are merged, `null` values and empty arrays accept any path
+    /// since their shape is unknown, and fields missing from the sample,
+    /// like skipped `None` fields, are reported. Use `Schema::new` with a
+    /// JSON Schema generated from the type when the sample doesn't cover
+    /// every shape of the data.
+    pub fn infer_from_sample<T: Serialize>(sample: &T) -> Result<Schema, RenderError> {
+        let value = to_value(sample)?;
+        Ok(Schema::new(sample_schema(&value)))
+    }
+
+    /// Infer a schema from several samples of the data
+    ///
+    /// The schemas of the samples are merged like the items of an array:
+    /// a field or an array item found in any sample is accepted, so samples
+    /// with the optional fields set and with each enum variant describe
+    /// the whole data.
+    pub fn infer_from_samples<T: Serialize>(samples: &[T]) -> Result<Schema, RenderError> {
+        let value = to_value(samples)?;
+        let merged = match sample_schema(&value).get_mut("items") {
+            Some(items) => items.take(),
+            None => json!({}),
+        };
+        Ok(Schema::new(merged))
+    }
+
+    pub fn as_json(&self) -> &Json {
+        &self.root
+    }
+
+    /// Report the paths of a template that can't be resolved
+    ///
+    /// Every expression without parameters is treated as a path, use
+    /// `Registry::check_template_schema` to skip registered helpers.
+    pub fn check(&self, template: &Template) -> Vec<UnresolvedPath> {
+        self.check_with(template, &|_| false)
+    }
+
+    pub(crate) fn check_with(
+        &self,
+        template: &Template,
+        is_helper: &dyn Fn(&str) -> bool,
+    ) -> Vec<UnresolvedPath> {
+        let mut checker = Checker {
+            schema: self,
+            scopes: vec![Node::of(self, &self.root)],
+            block_params: Vec::new(),
+            is_helper,
+            template_name: template.name.clone(),
+            errors: Vec::new(),
+        };
+        checker.template(template);
+        checker.errors
+    }
+
+    // expand references and combinations into plain schemas
+    fn expand<'a>(&'a self, s: &'a Json, out: &mut Vec<&'a Json>, depth: usize) {
+        if depth > MAX_REF_DEPTH {
+            return;
+        }
+        if let Some(r) = s.get("$ref").and_then(|r| r.as_str()) {
+            let target = if r.starts_with('#') {
+                self.root.pointer(&r[1..])
+            } else {
+                None
+            };
+            if let Some(target) = target {
+                self.expand(target, out, depth + 1);
+            } else {
+                // remote references are not followed
+                out.push(&Json::Bool(true));
+            }
+            return;
+        }
+
+        let mut combined = false;
+        for key in &["anyOf", "oneOf", "allOf"] {
+            if let Some(Json::Array(ref schemas)) = s.get(*key) {
+                combined = true;
+                for sub in schemas {
+                    self.expand(sub, out, depth + 1);
+                }
+            }
+        }
+        if !combined {
+            out.push(s);
+        }
+    }
+}
+
+fn sample_schema(value: &Json) -> Json {
+    match *value {
+        Json::Null => json!({}),
+        Json::Bool(_) => json!({"type": "boolean"}),
+        Json::Number(_) => json!({"type": "number"}),
+        Json::String(_) => json!({"type": "string"}),
+        Json::Array(ref items) => {
+            let items = items
+                .iter()
+                .map(sample_schema)
+                .fold(None, |merged, s| match merged {
+                    None => Some(s),
+                    Some(m) => Some(merge_schemas(m, s)),
+                })
+                .unwrap_or_else(|| json!({}));
+            json!({"type": "array", "items": items})
+        }
+        Json::Object(ref m) => {
+            let properties: Map<String, Json> = m
+                .iter()
+                .map(|(k, v)| (k.clone(), sample_schema(v)))
+                .collect();
+            json!({"type": "object", "properties": properties, "additionalProperties": false})
+        }
+    }
+}
+
+fn merge_schemas(a: Json, b: Json) -> Json {
+    if a == b || b == json!({}) {
+        return a;
+    }
+    if a == json!({}) {
+        return b;
+    }
+    match (a, b) {
+        (Json::Object(mut a), Json::Object(mut b)) => {
+            if a.get("type") != b.get("type") {
+                return json!({});
+            }
+            for key in &["properties", "items"] {
+                match (a.remove(*key), b.remove(*key)) {
+                    (Some(Json::Object(mut pa)), Some(Json::Object(pb))) => {
+                        for (k, v) in pb {
+                            let merged = match pa.remove(&k) {
+                                Some(existing) => merge_schemas(existing, v),
+                                None => v,
+                            };
+                            pa.insert(k, merged);
+                        }
+                        a.insert((*key).to_owned(), Json::Object(pa));
+                    }
+                    (Some(va), Some(vb)) => {
+                        a.insert((*key).to_owned(), merge_schemas(va, vb));
+                    }
+                    (Some(v), None) | (None, Some(v)) => {
+                        a.insert((*key).to_owned(), v);
+                    }
+                    (None, None) => {}
+                }
+            }
+            Json::Object(a)
+        }
+        _ => json!({}),
+    }
+}
+
+fn is_any(s: &Json) -> bool {
+    match *s {
+        Json::Bool(b) => b,
+        Json::Object(ref m) => ![
+            "type",
+            "properties",
+            "additionalProperties",
+            "items",
+            "enum",
+            "const",
+        ]
+        .iter()
+        .any(|k| m.contains_key(*k)),
+        _ => true,
+    }
+}
+
+fn has_type(s: &Json, t: &str) -> bool {
+    match s.get("type") {
+        Some(Json::String(ref s)) => s == t,
+        Some(Json::Array(ref types)) => types.iter().any(|s| s.as_str() == Some(t)),
+        _ => false,
+    }
+}
+
+fn is_object(s: &Json) -> bool {
+    has_type(s, "object") || s.get("properties").is_some()
+}
+
+fn is_array(s: &Json) -> bool {
+    has_type(s, "array") || s.get("items").is_some()
+}
+
+/// What the schema knows about a value in the template
+#[derive(Clone, Debug)]
+enum Node<'a> {
+    Any,
+    Schemas(Vec<&'a Json>),
+}
+
+impl<'a> Node<'a> {
+    fn of(schema: &'a Schema, s: &'a Json) -> Node<'a> {
+        let mut schemas = Vec::new();
+        schema.expand(s, &mut schemas, 0);
+        Node::from_schemas(schema, schemas)
+    }
+
+    fn from_schemas(schema: &'a Schema, schemas: Vec<&'a Json>) -> Node<'a> {
+        let mut expanded = Vec::new();
+        for s in schemas {
+            schema.expand(s, &mut expanded, 0);
+        }
+        if expanded.iter().any(|s| is_any(s)) {
+            Node::Any
+        } else {
+            Node::Schemas(expanded)
+        }
+    }
+
+    fn child(&self, schema: &'a Schema, seg: &str) -> Option<Node<'a>> {
+        let schemas = match *self {
+            Node::Any => return Some(Node::Any),
+            Node::Schemas(ref schemas) => schemas,
+        };
+
+        let mut children = Vec::new();
+        for s in schemas {
+            if is_object(s) {
+                if let Some(p) = s.get("properties").and_then(|p| p.get(seg)) {
+                    children.push(p);
+                    continue;
+                }
+                match s.get("additionalProperties") {
+                    Some(Json::Bool(false)) => {}
+                    Some(additional) => children.push(additional),
+                    // a map without declared properties
+                    None if s.get("properties").is_none() => return Some(Node::Any),
+                    None => {}
+                }
+            }
+            if is_array(s) {
+                if let Ok(idx) = seg.parse::<usize>() {
+                    match s.get("items") {
+                        Some(Json::Array(ref items)) => children.extend(items.get(idx)),
+                        Some(items) => children.push(items),
+                        None => return Some(Node::Any),
+                    }
+                }
+            }
+        }
+
+        if children.is_empty() {
+            None
+        } else {
+            Some(Node::from_schemas(schema, children))
+        }
+    }
+
+    // the value of the items of an array or object, as iterated by `each`
+    fn item(&self, schema: &'a Schema) -> Node<'a> {
+        let schemas = match *self {
+            Node::Any => return Node::Any,
+            Node::Schemas(ref schemas) => schemas,
+        };
+
+        let mut items = Vec::new();
+        for s in schemas {
+            if is_array(s) {
+                match s.get("items") {
+                    Some(Json::Array(ref tuple)) => items.extend(tuple.iter()),
+                    Some(item) => items.push(item),
+                    None => return Node::Any,
+                }
+            }
+            if is_object(s) {
+                if let Some(Json::Object(ref properties)) = s.get("properties") {
+                    items.extend(properties.values());
+                }
+                match s.get("additionalProperties") {
+                    Some(Json::Bool(false)) => {}
+                    Some(additional) => items.push(additional),
+                    None if s.get("properties").is_none() => return Node::Any,
+                    None => {}
+                }
+            }
+        }
+        if items.is_empty() {
+            Node::Any
+        } else {
+            Node::from_schemas(schema, items)
+        }
+    }
+}
+
+struct Checker<'a, 'h> {
+    schema: &'a Schema,
+    // the context of each enclosing block, from the root
+    scopes: Vec<Node<'a>>,
+    block_params: Vec<(String, Node<'a>)>,
+    is_helper: &'h dyn Fn(&str) -> bool,
+    template_name: Option<String>,
+    errors: Vec<UnresolvedPath>,
+}
+
+impl<'a, 'h> Checker<'a, 'h> {
+    fn resolve(&self, path: &str) -> Option<Node<'a>> {
+        // local variables like `@index` are set by helpers
+        if path.starts_with('@') && !path.starts_with("@root") {
+            return Some(Node::Any);
+        }
+
+        let segs = match HandlebarsParser::parse(Rule::path, path) {
+            Ok(pairs) => pairs.flatten(),
+            Err(_) => return None,
+        };
+
+        let mut node: Option<Node<'a>> = None;
+        let mut ups = 0;
+        for seg in segs {
+            let s = seg.as_str();
+            match (node.take(), seg.as_rule()) {
+                (None, Rule::path_up) => ups += 1,
+                (None, Rule::path_current) => {
+                    node = Some(self.scope(ups));
+                }
+                (None, Rule::path_id) if s == "this" => {
+                    node = Some(self.scope(ups));
+                }
+                (None, Rule::path_id) if s == "@root" => {
+                    node = Some(self.scopes[0].clone());
+                }
+                (None, Rule::path_id) | (None, Rule::path_raw_id) => {
+                    let bp = if ups == 0 {
+                        self.block_params.iter().rev().find(|(name, _)| name == s)
+                    } else {
+                        None
+                    };
+                    node = match bp {
+                        Some((_, n)) => Some(n.clone()),
+                        None => self.scope(ups).child(self.schema, s),
+                    };
+                    node.as_ref()?;
+                }
+                (Some(n), Rule::path_id) if s == "this" => node = Some(n),
+                (Some(n), Rule::path_id) | (Some(n), Rule::path_raw_id) => {
+                    node = Some(n.child(self.schema, s)?);
+                }
+                (Some(n), _) => node = Some(n),
+                (None, _) => {}
+            }
+        }
+        Some(node.unwrap_or_else(|| self.scope(ups)))
+    }
+
+    fn scope(&self, ups: usize) -> Node<'a> {
+        let idx = self.scopes.len().saturating_sub(ups + 1);
+        self.scopes[idx].clone()
+    }
+
+    fn template(&mut self, t: &Template) {
+        for (idx, e) in t.elements.iter().enumerate() {
+            let position = t
+                .mapping
+                .as_ref()
+                .and_then(|m| m.get(idx))
+                .map(|&TemplateMapping(line, col)| (line, col));
+            self.element(e, position);
+        }
+    }
+
+    fn element(&mut self, e: &TemplateElement, position: Option<(usize, usize)>) {
+        match *e {
+            TemplateElement::HTMLExpression(ref p) => {
+                self.param(p, position);
+            }
+            TemplateElement::Expression(ref ht) | TemplateElement::HelperBlock(ref ht) => {
+                self.helper(ht, position);
+            }
+            TemplateElement::DirectiveExpression(ref dt)
+            | TemplateElement::DirectiveBlock(ref dt)
+            | TemplateElement::PartialExpression(ref dt)
+            | TemplateElement::PartialBlock(ref dt) => self.directive(dt, position),
+            TemplateElement::RawString(_) | TemplateElement::Comment(_) => {}
+        }
+    }
+
+    fn param(&mut self, p: &Parameter, position: Option<(usize, usize)>) -> Node<'a> {
+        match *p {
+            Parameter::Name(ref path) => match self.resolve(path) {
+                Some(node) => node,
+                None => {
+                    self.errors.push(UnresolvedPath {
+                        template: self.template_name.clone(),
+                        path: path.clone(),
+                        position,
+                    });
+                    // avoid reporting paths below this one again
+                    Node::Any
+                }
+            },
+            Parameter::Subexpression(ref s) => {
+                if let TemplateElement::Expression(ref ht) = *s.as_element() {
+                    self.helper(ht, position)
+                } else {
+                    Node::Any
+                }
+            }
+            Parameter::Literal(_) => Node::Any,
+        }
+    }
+
+    fn helper(&mut self, ht: &HelperTemplate, position: Option<(usize, usize)>) -> Node<'a> {
+        if ht.is_name_only() {
+            if let Some(name) = ht.name.as_name() {
+                if (self.is_helper)(name) {
+                    return Node::Any;
+                }
+            }
+            return self.param(&ht.name, position);
+        }
+
+        if let Parameter::Subexpression(_) = ht.name {
+            self.param(&ht.name, position);
+        }
+        let params: Vec<Node<'a>> = ht.params.iter().map(|p| self.param(p, position)).collect();
+        for (_, p) in sorted_hash(&ht.hash) {
+            self.param(p, position);
+        }
+
+        if let Some(ref t) = ht.template {
+            let (context, item) = match ht.name.as_name() {
+                Some("each") => {
+                    let item = params
+                        .first()
+                        .map(|n| n.item(self.schema))
+                        .unwrap_or(Node::Any);
+                    (Some(item.clone()), item)
+                }
+                Some("with") => {
+                    let value = params.first().cloned().unwrap_or(Node::Any);
+                    (Some(value.clone()), value)
+                }
+                _ => (None, Node::Any),
+            };
+
+            let block_params = self.block_params.len();
+            match ht.block_param {
+                Some(BlockParam::Single(ref p)) => self.add_block_param(p, item),
+                Some(BlockParam::Pair((ref p1, ref p2))) => {
+                    self.add_block_param(p1, item);
+                    self.add_block_param(p2, Node::Any);
+                }
+                None => {}
+            }
+            if let Some(ref context) = context {
+                self.scopes.push(context.clone());
+            }
+
+            self.template(t);
+
+            if context.is_some() {
+                self.scopes.pop();
+            }
+            self.block_params.truncate(block_params);
+        }
+        if let Some(ref t) = ht.inverse {
+            self.template(t);
+        }
+        Node::Any
+    }
+
+    fn add_block_param(&mut self, p: &Parameter, node: Node<'a>) {
+        if let Some(name) = p.as_name() {
+            self.block_params.push((name.to_owned(), node));
+        }
+    }
+
+    fn directive(&mut self, dt: &DirectiveTemplate, position: Option<(usize, usize)>) {
+        if let Parameter::Subexpression(_) = dt.name {
+            self.param(&dt.name, position);
+        }
+        for p in &dt.params {
+            self.param(p, position);
+        }
+        for (_, p) in sorted_hash(&dt.hash) {
+            self.param(p, position);
+        }
+        if let Some(ref t) = dt.template {
+            self.template(t);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::registry::Registry;
+    use crate::schema::Schema;
+    use crate::template::Template;
+
+    #[derive(Serialize)]
+    struct Item {
+        name: String,
+        tags: Vec<String>,
+    }
+
+    #[derive(Serialize)]
+    struct Page {
+        title: String,
+        items: Vec<Item>,
+        owner: Option<Item>,
+    }
+
+    fn paths(schema: &Schema, source: &str) -> Vec<(String, Option<(usize, usize)>)> {
+        let t = Template::compile2(source, true).unwrap();
+        schema
+            .check(&t)
+            .into_iter()
+            .map(|e| (e.path, e.position))
+            .collect()
+    }
+
+    #[test]
+    fn test_infer_from_sample() {
+        let schema = Schema::infer_from_sample(&Page {
+            title: "t".to_owned(),
+            items: vec![Item {
+                name: "a".to_owned(),
+                tags: vec![],
+            }],
+            owner: None,
+        })
+        .unwrap();
+
+        assert!(paths(
+            &schema,
+            "{{title}}{{#each items}}{{name}}{{@index}}{{../title}}{{#each tags}}{{this}}{{/each}}{{/each}}\
+             {{owner.anything}}{{items.[0].name}}{{items.0.tags}}{{@root.title}}"
+        )
+        .is_empty());
+
+        assert_eq!(
+            paths(
+                &schema,
+                "{{titel}}\n{{#each items as |item i|}}{{item.nmae}}{{i}}{{name}}{{title}}\
+                 {{else}}{{name}}{{/each}}{{title.length}}"
+            ),
+            vec![
+                ("titel".to_owned(), Some((1, 1))),
+                ("item.nmae".to_owned(), Some((2, 28))),
+                ("title".to_owned(), Some((2, 54))),
+                ("name".to_owned(), Some((2, 71))),
+                ("title.length".to_owned(), Some((2, 88))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_infer_from_samples() {
+        let item = Item {
+            name: "a".to_owned(),
+            tags: vec!["t".to_owned()],
+        };
+        let schema = Schema::infer_from_samples(&[
+            Page {
+                title: "t".to_owned(),
+                items: vec![],
+                owner: None,
+            },
+            Page {
+                title: "t".to_owned(),
+                items: vec![],
+                owner: Some(item),
+            },
+        ])
+        .unwrap();
+
+        assert!(paths(&schema, "{{title}}{{owner.name}}{{owner.tags.0}}").is_empty());
+        assert_eq!(
+            paths(&schema, "{{owner.nmae}}{{owner.tags.0.x}}"),
+            vec![
+                ("owner.nmae".to_owned(), Some((1, 1))),
+                ("owner.tags.0.x".to_owned(), Some((1, 15))),
+            ]
+        );
+        assert!(paths(&Schema::infer_from_samples::<Page>(&[]).unwrap(), "{{x}}").is_empty());
+    }
+
+    #[test]
+    fn test_hash_order() {
+        let schema = Schema::new(json!({"type": "object", "properties": {}}));
+        assert_eq!(
+            paths(&schema, "{{f z=z b=b a=a}}{{> p y=y x=x}}"),
+            vec![
+                ("a".to_owned(), Some((1, 1))),
+                ("b".to_owned(), Some((1, 1))),
+                ("z".to_owned(), Some((1, 1))),
+                ("x".to_owned(), Some((1, 18))),
+                ("y".to_owned(), Some((1, 18))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let schema = Schema::new(json!({
+            "type": "object",
+            "properties": {
+                "user": {"$ref": "#/definitions/user"},
+                "meta": {"type": "object"},
+                "scores": {"type": "object", "additionalProperties": {"type": "number"}},
+                "either": {"anyOf": [
+                    {"type": "object", "properties": {"a": {"type": "string"}}},
+                    {"type": "object", "properties": {"b": {"type": "string"}}}
+                ]}
+            },
+            "definitions": {
+                "user": {
+                    "type": "object",
+                    "properties": {
+                        "name": {"type": "string"},
+                        "friends": {"type": "array", "items": {"$ref": "#/definitions/user"}}
+                    }
+                }
+            }
+        }));
+
+        assert!(paths(
+            &schema,
+            "{{#with user as |u|}}{{name}}{{#each friends}}{{name}}{{u.name}}{{/each}}{{/with}}\
+             {{meta.whatever.deep}}{{scores.math}}{{either.a}}{{either.b}}\
+             {{#if (eq user.name \"x\")}}{{user.friends.[1].friends.0.name}}{{/if}}"
+        )
+        .is_empty());
+
+        assert_eq!(
+            paths(
+                &schema,
+                "{{#with user}}{{friend}}{{/with}}{{either.c}}{{scores.math.x}}\
+                 {{lookup user.age 1}}{{> p ctx=user.mail}}"
+            ),
+            vec![
+                ("friend".to_owned(), Some((1, 15))),
+                ("either.c".to_owned(), Some((1, 34))),
+                ("scores.math.x".to_owned(), Some((1, 46))),
+                ("user.age".to_owned(), Some((1, 63))),
+                ("user.mail".to_owned(), Some((1, 84))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_registry_check() {
+        let mut r = Registry::new();
+        r.register_template_string("t0", "{{name}}{{hello}}{{log name}}")
+            .unwrap();
+        r.register_helper(
+            "hello",
+            Box::new(
+                |_: &crate::render::Helper,
+                 _: &Registry,
+                 _: &crate::context::Context,
+                 _: &mut crate::render::RenderContext,
+                 _: &mut dyn crate::output::Output| Ok(()),
+            ),
+        );
+
+        let schema = Schema::new(json!({"type": "object", "properties": {"title": {}}}));
+        let errors = r.check_template_schema("t0", &schema).unwrap();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].template, Some("t0".to_owned()));
+        assert_eq!(errors[0].path, "name");
+        assert!(r.check_template_schema("missing", &schema).is_err());
+    }
+}
@@ -0,0 +1,281 @@
+use std::fmt::{self, Debug, Formatter};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+use serde::Serialize;
+
+use crate::directives::DirectiveDef;
+use crate::error::{RenderError, TemplateError, TemplateFileError, TemplateRenderError};
+use crate::helpers::HelperDef;
+use crate::registry::Registry;
+
+/// A registry that can be shared between threads and changed through `&self`
+///
+/// It holds an immutable snapshot of a `Registry`. Every change copies the
+/// current snapshot, applies the change and publishes the result, while
+/// renders that already started keep using the snapshot they took. So a
+/// render always sees a consistent set of templates, helpers and decorators,
+/// and never waits for a registration to finish.
+///
+/// Since each change copies the registry, do bulk setup on a `Registry`
+/// and turn it into a `SharedHandlebars` afterwards.
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+/// use handlebars::*;
+///
+/// let hbs = Arc::new(SharedHandlebars::new());
+/// hbs.register_template_string("t0", "Hello {{this}}").unwrap();
+///
+/// let shared = hbs.clone();
+/// thread::spawn(move || {
+///     shared.register_template_string("t1", "Bye {{this}}").unwrap();
+/// })
+/// .join()
+/// .unwrap();
+///
+/// assert_eq!(hbs.render("t1", &"world").unwrap(), "Bye world");
+/// ```
+pub struct SharedRegistry {
+    current: RwLock<Arc<Registry>>,
+    // serializes changes so that none of them is lost
+    update_lock: Mutex<()>,
+}
+
+impl Debug for SharedRegistry {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("SharedHandlebars")
+            .field("current", &self.snapshot())
+            .finish()
+    }
+}
+
+impl Default for SharedRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<Registry> for SharedRegistry {
+    fn from(registry: Registry) -> SharedRegistry {
+        SharedRegistry {
+            current: RwLock::new(Arc::new(registry)),
+            update_lock: Mutex::new(()),
+        }
+    }
+}
+
+impl SharedRegistry {
+    pub fn new() -> SharedRegistry {
+        SharedRegistry::from(Registry::new())
+    }
+
+    /// Return the current registry
+    ///
+    /// Changes made after this call are not visible in the returned registry.
+    pub fn snapshot(&self) -> Arc<Registry> {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Change the registry with a function
+    ///
+    /// The function works on a copy of the current registry, which replaces
+    /// the current one when the function returns. Changes are applied one
+    /// at a time.
+    pub fn update<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Registry) -> R,
+    {
+        let _guard = self
+            .update_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let mut registry = Registry::clone(&self.snapshot());
+        let result = f(&mut registry);
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(registry);
+        result
+    }
+
+    /// Register a template string, see `Registry::register_template_string`
+    pub fn register_template_string<S>(&self, name: &str, tpl_str: S) -> Result<(), TemplateError>
+    where
+        S: AsRef<str>,
+    {
+        self.update(|r| r.register_template_string(name, tpl_str))
+    }
+
+    /// Register a partial string, see `Registry::register_partial`
+    pub fn register_partial<S>(&self, name: &str, partial_str: S) -> Result<(), TemplateError>
+    where
+        S: AsRef<str>,
+    {
+        self.update(|r| r.register_partial(name, partial_str))
+    }
+
+    /// Register a template from a path, see `Registry::register_template_file`
+    pub fn register_template_file<P>(
+        &self,
+        name: &str,
+        tpl_path: P,
+    ) -> Result<(), TemplateFileError>
+    where
+        P: AsRef<Path>,
+    {
+        self.update(|r| r.register_template_file(name, tpl_path))
+    }
+
+    /// remove a template from the registry
+    pub fn unregister_template(&self, name: &str) {
+        self.update(|r| r.unregister_template(name))
+    }
+
+    /// register a helper
+    pub fn register_helper(
+        &self,
+        name: &str,
+        def: Box<dyn HelperDef + 'static>,
+    ) -> Option<Arc<dyn HelperDef + 'static>> {
+        self.update(|r| r.register_helper(name, def))
+    }
+
+    /// remove a helper from the registry
+    pub fn unregister_helper(&self, name: &str) -> Option<Arc<dyn HelperDef + 'static>> {
+        self.update(|r| r.unregister_helper(name))
+    }
+
+    /// register a decorator
+    pub fn register_decorator(
+        &self,
+        name: &str,
+        def: Box<dyn DirectiveDef + 'static>,
+    ) -> Option<Arc<dyn DirectiveDef + 'static>> {
+        self.update(|r| r.register_decorator(name, def))
+    }
+
+    /// remove a decorator from the registry
+    pub fn unregister_decorator(&self, name: &str) -> Option<Arc<dyn DirectiveDef + 'static>> {
+        self.update(|r| r.unregister_decorator(name))
+    }
+
+    /// Render a registered template with some data into a string,
+    /// see `Registry::render`
+    pub fn render<T>(&self, name: &str, data: &T) -> Result<String, RenderError>
+    where
+        T: Serialize,
+    {
+        self.snapshot().render(name, data)
+    }
+
+    /// Render a registered template and write some data to the `std::io::Write`
+    pub fn render_to_write<T, W>(&self, name: &str, data: &T, writer: W) -> Result<(), RenderError>
+    where
+        T: Serialize,
+        W: Write,
+    {
+        self.snapshot().render_to_write(name, data, writer)
+    }
+
+    /// render a template string using current registry without register it
+    pub fn render_template<T>(
+        &self,
+        template_string: &str,
+        data: &T,
+    ) -> Result<String, TemplateRenderError>
+    where
+        T: Serialize,
+    {
+        self.snapshot().render_template(template_string, data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::context::Context;
+    use crate::error::RenderError;
+    use crate::output::Output;
+    use crate::registry::Registry;
+    use crate::render::{Helper, RenderContext};
+    use crate::shared::SharedRegistry;
+
+    #[test]
+    fn test_snapshot_consistency() {
+        let hbs = SharedRegistry::new();
+        hbs.register_template_string("t0", "v1").unwrap();
+
+        let before = hbs.snapshot();
+        hbs.register_template_string("t0", "v2").unwrap();
+        hbs.register_template_string("t1", "new").unwrap();
+
+        assert_eq!(before.render("t0", &()).unwrap(), "v1");
+        assert!(before.render("t1", &()).is_err());
+        assert_eq!(hbs.render("t0", &()).unwrap(), "v2");
+        assert_eq!(hbs.render("t1", &()).unwrap(), "new");
+
+        hbs.unregister_template("t1");
+        assert!(hbs.render("t1", &()).is_err());
+        assert!(hbs.register_template_string("t2", "{{#if}}").is_err());
+    }
+
+    #[test]
+    fn test_helpers_and_decorators() {
+        let mut registry = Registry::new();
+        registry.set_strict_mode(true);
+        let hbs = SharedRegistry::from(registry);
+
+        hbs.register_helper(
+            "hello",
+            Box::new(
+                |_: &Helper,
+                 _: &Registry,
+                 _: &Context,
+                 _: &mut RenderContext,
+                 out: &mut dyn Output|
+                 -> Result<(), RenderError> {
+                    out.write("hello")?;
+                    Ok(())
+                },
+            ),
+        );
+        assert!(hbs.snapshot().strict_mode());
+        assert_eq!(hbs.render_template("{{hello}}", &()).unwrap(), "hello");
+
+        assert!(hbs.unregister_helper("hello").is_some());
+        assert!(hbs.render_template("{{hello}}", &()).is_err());
+
+        assert!(hbs.unregister_decorator("inline").is_some());
+        assert!(hbs
+            .render_template("{{#*inline \"p\"}}{{/inline}}", &())
+            .is_err());
+    }
+
+    #[test]
+    fn test_concurrent_registration() {
+        let hbs = Arc::new(SharedRegistry::new());
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let hbs = hbs.clone();
+                thread::spawn(move || {
+                    let name = format!("t{}", i);
+                    hbs.register_template_string(&name, format!("{}:{{{{this}}}}", i))
+                        .unwrap();
+                    hbs.render(&name, &i).unwrap()
+                })
+            })
+            .collect();
+
+        for (i, h) in handles.into_iter().enumerate() {
+            assert_eq!(h.join().unwrap(), format!("{}:{}", i, i));
+        }
+        assert_eq!(hbs.snapshot().get_templates().len(), 8);
+    }
+}
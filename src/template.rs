@@ -5,7 +5,7 @@ use std::iter::Peekable;
 use crate::grammar::{HandlebarsParser, Rule};
 use pest::error::LineColLocation;
 use pest::iterators::Pair;
-use pest::{Parser, Position};
+use pest::{Parser, Position, Span};
 
 use hashbrown::HashMap;
 use serde_json::value::Value as Json;
@@ -13,13 +13,18 @@ use std::str::FromStr;
 
 use crate::error::{TemplateError, TemplateErrorReason};
 
+#[cfg(feature = "ast_serde")]
+use serde::{Deserialize, Serialize};
+
 use self::TemplateElement::*;
 
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "ast_serde", derive(Serialize, Deserialize))]
 pub struct TemplateMapping(pub usize, pub usize);
 
 /// A handlebars template
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "ast_serde", derive(Serialize, Deserialize))]
 pub struct Template {
     pub name: Option<String>,
     pub elements: Vec<TemplateElement>,
@@ -27,9 +32,11 @@ pub struct Template {
 }
 
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "ast_serde", derive(Serialize, Deserialize))]
 pub struct Subexpression {
     // we use box here avoid resursive struct definition
     pub element: Box<TemplateElement>,
+    mapping: Option<TemplateMapping>,
 }
 
 impl Subexpression {
@@ -37,10 +44,28 @@ impl Subexpression {
         name: String,
         params: &[Parameter],
         hash: &HashMap<String, Parameter>,
+    ) -> Subexpression {
+        Subexpression::from_spec(Parameter::Name(name), params, hash, None)
+    }
+
+    /// Create a subexpression calling a helper, at line and column
+    /// `mapping` of the template
+    pub fn from_helper(ht: HelperTemplate, mapping: Option<TemplateMapping>) -> Subexpression {
+        Subexpression {
+            element: Box::new(Expression(Box::new(ht))),
+            mapping,
+        }
+    }
+
+    fn from_spec(
+        name: Parameter,
+        params: &[Parameter],
+        hash: &HashMap<String, Parameter>,
+        mapping: Option<TemplateMapping>,
     ) -> Subexpression {
         Subexpression {
             element: Box::new(Expression(Box::new(HelperTemplate {
-                name: Parameter::Name(name),
+                name,
                 params: params.to_owned(),
                 hash: hash.clone(),
                 template: None,
@@ -48,6 +73,7 @@ impl Subexpression {
                 block_param: None,
                 block: false,
             }))),
+            mapping,
         }
     }
 
@@ -62,14 +88,27 @@ impl Subexpression {
         self.element.as_ref()
     }
 
+    /// The name of the helper, empty when the helper is picked by another
+    /// subexpression, see `helper_name`
     pub fn name(&self) -> &str {
+        self.helper_name().unwrap_or("")
+    }
+
+    /// The name of the helper, `None` when the helper is picked by another
+    /// subexpression, like `((lookup helpers "format") value)`
+    pub fn helper_name(&self) -> Option<&str> {
         match *self.as_element() {
-            // FIXME: avoid unwrap here
-            Expression(ref ht) => ht.name.as_name().unwrap(),
-            _ => unreachable!(),
+            Expression(ref ht) => ht.name.as_name(),
+            _ => None,
         }
     }
 
+    /// Line and column of the subexpression, when the template was
+    /// compiled with source map
+    pub fn mapping(&self) -> Option<&TemplateMapping> {
+        self.mapping.as_ref()
+    }
+
     pub fn params(&self) -> Option<&Vec<Parameter>> {
         match *self.as_element() {
             Expression(ref ht) => Some(&ht.params),
@@ -86,6 +125,7 @@ impl Subexpression {
 }
 
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "ast_serde", derive(Serialize, Deserialize))]
 pub enum BlockParam {
     Single(Parameter),
     Pair((Parameter, Parameter)),
@@ -102,6 +142,7 @@ pub struct ExpressionSpec {
 }
 
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "ast_serde", derive(Serialize, Deserialize))]
 pub enum Parameter {
     Name(String),
     Literal(Json),
@@ -109,6 +150,7 @@ pub enum Parameter {
 }
 
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "ast_serde", derive(Serialize, Deserialize))]
 pub struct HelperTemplate {
     pub name: Parameter,
     pub params: Vec<Parameter>,
@@ -120,6 +162,44 @@ pub struct HelperTemplate {
 }
 
 impl HelperTemplate {
+    /// Create a helper expression, like `{{name param key=value}}`
+    pub fn new(
+        name: Parameter,
+        params: Vec<Parameter>,
+        hash: HashMap<String, Parameter>,
+    ) -> HelperTemplate {
+        HelperTemplate {
+            name,
+            params,
+            hash,
+            block_param: None,
+            template: None,
+            inverse: None,
+            block: false,
+        }
+    }
+
+    /// Create a helper block, like
+    /// `{{#name param as |item|}}template{{else}}inverse{{/name}}`
+    pub fn new_block(
+        name: Parameter,
+        params: Vec<Parameter>,
+        hash: HashMap<String, Parameter>,
+        block_param: Option<BlockParam>,
+        template: Option<Template>,
+        inverse: Option<Template>,
+    ) -> HelperTemplate {
+        HelperTemplate {
+            name,
+            params,
+            hash,
+            block_param,
+            template,
+            inverse,
+            block: true,
+        }
+    }
+
     pub(crate) fn with_name(name: String) -> HelperTemplate {
         HelperTemplate {
             name: Parameter::Name(name),
@@ -138,11 +218,47 @@ impl HelperTemplate {
 }
 
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "ast_serde", derive(Serialize, Deserialize))]
 pub struct DirectiveTemplate {
     pub name: Parameter,
     pub params: Vec<Parameter>,
     pub hash: HashMap<String, Parameter>,
     pub template: Option<Template>,
+    /// Indentation of a partial alone on its line, given to each line of
+    /// its output
+    pub indent: Option<String>,
+}
+
+/// The params of a hash sorted by key, to visit them in a stable order
+pub(crate) fn sorted_hash(hash: &HashMap<String, Parameter>) -> Vec<(&String, &Parameter)> {
+    let mut sorted: Vec<_> = hash.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(b.0));
+    sorted
+}
+
+impl DirectiveTemplate {
+    /// Create a decorator or a partial, with the block `template` for
+    /// decorator and partial blocks
+    pub fn new(
+        name: Parameter,
+        params: Vec<Parameter>,
+        hash: HashMap<String, Parameter>,
+        template: Option<Template>,
+    ) -> DirectiveTemplate {
+        DirectiveTemplate {
+            name,
+            params,
+            hash,
+            template,
+            indent: None,
+        }
+    }
+
+    /// Set the indentation of a partial alone on its line
+    pub fn with_indent(mut self, indent: String) -> DirectiveTemplate {
+        self.indent = Some(indent);
+        self
+    }
 }
 
 impl Parameter {
@@ -162,15 +278,53 @@ impl Parameter {
         }
     }
 
+    fn shift_first_line(&mut self, cols: usize) {
+        if let Parameter::Subexpression(ref mut s) = *self {
+            if let Some(TemplateMapping(1, ref mut col)) = s.mapping {
+                *col -= cols;
+            }
+            s.element.shift_first_line(cols);
+        }
+    }
+
     pub fn parse(s: &str) -> Result<Parameter, TemplateError> {
         let parser = HandlebarsParser::parse(Rule::parameter, s)
             .map_err(|_| TemplateError::of(TemplateErrorReason::InvalidParam(s.to_owned())))?;
 
         let mut it = parser.flatten().peekable();
-        Template::parse_param(s, &mut it, s.len() - 1)
+        Template::parse_param(s, &mut it, s.len() - 1, false)
+    }
+}
+
+/// Options of the template parser
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateOptions {
+    /// Keep the line and column of elements, for error reporting
+    pub source_map: bool,
+    /// Accept Mustache set delimiter tags, like `{{=<% %>=}}`
+    pub mustache: bool,
+    /// Open and close delimiters of tags, instead of `{{` and `}}`
+    pub delimiters: Option<(String, String)>,
+    /// Keep the lines of block tags, comments and partials that stand alone
+    /// on their line, instead of removing them
+    pub ignore_standalone: bool,
+}
+
+impl Default for TemplateOptions {
+    fn default() -> TemplateOptions {
+        TemplateOptions {
+            source_map: true,
+            mustache: false,
+            delimiters: None,
+            ignore_standalone: false,
+        }
     }
 }
 
+fn is_valid_delimiter(d: &str) -> bool {
+    !d.is_empty() && !d.contains(|c: char| c.is_whitespace() || c == '=')
+}
+
 impl Template {
     pub fn new(mapping: bool) -> Template {
         Template {
@@ -180,6 +334,19 @@ impl Template {
         }
     }
 
+    /// Create a template from its elements, and the line and column of
+    /// each element when `mapping` is given
+    pub fn from_elements(
+        elements: Vec<TemplateElement>,
+        mapping: Option<Vec<TemplateMapping>>,
+    ) -> Template {
+        Template {
+            name: None,
+            elements,
+            mapping,
+        }
+    }
+
     fn push_element(&mut self, e: TemplateElement, line: usize, col: usize) {
         self.elements.push(e);
         if let Some(ref mut maps) = self.mapping {
@@ -191,24 +358,39 @@ impl Template {
         Template::compile2(source, false)
     }
 
+    pub fn compile2<S: AsRef<str>>(source: S, mapping: bool) -> Result<Template, TemplateError> {
+        let options = TemplateOptions {
+            source_map: mapping,
+            ..TemplateOptions::default()
+        };
+        Template::compile_with_options(source, &options)
+    }
+
     fn parse_subexpression<'a, I>(
         source: &'a str,
         it: &mut Peekable<I>,
         limit: usize,
+        mapping: Option<TemplateMapping>,
     ) -> Result<Parameter, TemplateError>
     where
         I: Iterator<Item = Pair<'a, Rule>>,
     {
-        let espec = Template::parse_expression(source, it.by_ref(), limit)?;
-        if let Parameter::Name(name) = espec.name {
-            Ok(Parameter::Subexpression(Subexpression::new(
-                name,
-                &espec.params,
-                &espec.hash,
-            )))
+        let with_mapping = mapping.is_some();
+        let espec = Template::parse_expression(source, it.by_ref(), limit, with_mapping)?;
+        Ok(Parameter::Subexpression(Subexpression::from_spec(
+            espec.name,
+            &espec.params,
+            &espec.hash,
+            mapping,
+        )))
+    }
+
+    fn subexpression_mapping(pair: &Pair<Rule>, mapping: bool) -> Option<TemplateMapping> {
+        if mapping {
+            let (line, col) = pair.as_span().start_pos().line_col();
+            Some(TemplateMapping(line, col))
         } else {
-            // line/col no
-            Err(TemplateError::of(TemplateErrorReason::NestedSubexpression))
+            None
         }
     }
 
@@ -216,6 +398,7 @@ impl Template {
         source: &'a str,
         it: &mut Peekable<I>,
         _: usize,
+        mapping: bool,
     ) -> Result<Parameter, TemplateError>
     where
         I: Iterator<Item = Pair<'a, Rule>>,
@@ -224,20 +407,80 @@ impl Template {
         let rule = name_node.as_rule();
         let name_span = name_node.as_span();
         match rule {
+            // a number name, like `{{12.34}}`, is a key, not a path
+            Rule::reference
+                if Json::from_str(name_span.as_str())
+                    .ok()
+                    .map_or(false, |j| j.is_number()) =>
+            {
+                Ok(Parameter::Name(format!("[{}]", name_span.as_str())))
+            }
             Rule::identifier | Rule::reference | Rule::invert_tag_item => {
+                Template::check_path(source, &name_span)?;
                 Ok(Parameter::Name(name_span.as_str().to_owned()))
             }
             Rule::subexpression => {
-                Template::parse_subexpression(source, it.by_ref(), name_span.end())
+                let mapping = Template::subexpression_mapping(&name_node, mapping);
+                Template::parse_subexpression(source, it.by_ref(), name_span.end(), mapping)
+            }
+            Rule::string_literal => {
+                let s = name_span.as_str();
+                let name = Json::from_str(s)
+                    .ok()
+                    .or_else(|| Template::single_quoted_string(s))
+                    .and_then(|json| json.as_str().map(|n| format!("[{}]", n)))
+                    .unwrap_or_else(|| s.to_owned());
+                Ok(Parameter::Name(name))
             }
             _ => unreachable!(),
         }
     }
 
+    // `..` and `this` only start a path, like in Handlebars.js
+    fn check_path(source: &str, span: &Span) -> Result<(), TemplateError> {
+        let path = span.as_str();
+        let path = if path.starts_with('@') {
+            &path[1..]
+        } else {
+            path
+        };
+        let pairs = match HandlebarsParser::parse(Rule::path, path) {
+            Ok(pairs) => pairs,
+            Err(_) => return Ok(()),
+        };
+
+        let mut named = false;
+        for pair in pairs.flatten() {
+            match pair.as_rule() {
+                Rule::path_up | Rule::path_current if named => {
+                    let (line_no, col_no) = span.start_pos().line_col();
+                    return Err(TemplateError::of(TemplateErrorReason::InvalidPath(
+                        span.as_str().to_owned(),
+                    ))
+                    .at(source, line_no, col_no));
+                }
+                Rule::path_id | Rule::path_raw_id => named = true,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // `'text'` is not JSON, but is the string `"text"` like in Handlebars.js
+    fn single_quoted_string(s: &str) -> Option<Json> {
+        if s.len() < 2 || !s.starts_with('\'') || !s.ends_with('\'') {
+            return None;
+        }
+        let text = &s[1..s.len() - 1];
+        let quoted = format!("\"{}\"", text.replace("\\'", "'").replace('"', "\\\""));
+        Json::from_str(&quoted).ok()
+    }
+
     fn parse_param<'a, I>(
         source: &'a str,
         it: &mut Peekable<I>,
         _: usize,
+        mapping: bool,
     ) -> Result<Parameter, TemplateError>
     where
         I: Iterator<Item = Pair<'a, Rule>>,
@@ -249,17 +492,23 @@ impl Template {
         let param_rule = param.as_rule();
         let param_span = param.as_span();
         let result = match param_rule {
-            Rule::reference => Parameter::Name(param_span.as_str().to_owned()),
+            Rule::reference => {
+                Template::check_path(source, &param_span)?;
+                Parameter::Name(param_span.as_str().to_owned())
+            }
             Rule::literal => {
                 let s = param_span.as_str();
                 if let Ok(json) = Json::from_str(s) {
                     Parameter::Literal(json)
+                } else if let Some(json) = Template::single_quoted_string(s) {
+                    Parameter::Literal(json)
                 } else {
                     Parameter::Name(s.to_owned())
                 }
             }
             Rule::subexpression => {
-                Template::parse_subexpression(source, it.by_ref(), param_span.end())?
+                let mapping = Template::subexpression_mapping(&param, mapping);
+                Template::parse_subexpression(source, it.by_ref(), param_span.end(), mapping)?
             }
             _ => unreachable!(),
         };
@@ -279,6 +528,7 @@ impl Template {
         source: &'a str,
         it: &mut Peekable<I>,
         limit: usize,
+        mapping: bool,
     ) -> Result<(String, Parameter), TemplateError>
     where
         I: Iterator<Item = Pair<'a, Rule>>,
@@ -288,7 +538,7 @@ impl Template {
         // identifier
         let key = name_node.as_str().to_owned();
 
-        let value = Template::parse_param(source, it.by_ref(), limit)?;
+        let value = Template::parse_param(source, it.by_ref(), limit, mapping)?;
         Ok((key, value))
     }
 
@@ -326,6 +576,7 @@ impl Template {
         source: &'a str,
         it: &mut Peekable<I>,
         limit: usize,
+        mapping: bool,
     ) -> Result<ExpressionSpec, TemplateError>
     where
         I: Iterator<Item = Pair<'a, Rule>>,
@@ -341,7 +592,7 @@ impl Template {
             it.next();
         }
 
-        let name = Template::parse_name(source, it.by_ref(), limit)?;
+        let name = Template::parse_name(source, it.by_ref(), limit, mapping)?;
 
         loop {
             let rule;
@@ -362,10 +613,10 @@ impl Template {
 
             match rule {
                 Rule::param => {
-                    params.push(Template::parse_param(source, it.by_ref(), end)?);
+                    params.push(Template::parse_param(source, it.by_ref(), end, mapping)?);
                 }
                 Rule::hash => {
-                    let (key, value) = Template::parse_hash(source, it.by_ref(), end)?;
+                    let (key, value) = Template::parse_hash(source, it.by_ref(), end, mapping)?;
                     hashes.insert(key, value);
                 }
                 Rule::block_param => {
@@ -387,6 +638,87 @@ impl Template {
         })
     }
 
+    // ends the section before `{{else}}`, the main one or the one given by
+    // `{{^name}}`
+    fn end_section(h: &mut HelperTemplate, t: Template) {
+        if h.template.is_some() {
+            h.inverse = Some(t);
+            h.template = None;
+        } else {
+            h.template = Some(t);
+        }
+    }
+
+    // tags that are removed with their line when they stand alone on it
+    fn is_standalone_tag(rule: Rule) -> bool {
+        match rule {
+            Rule::helper_block_start
+            | Rule::inverted_block_start
+            | Rule::helper_block_end
+            | Rule::invert_tag
+            | Rule::invert_chain_tag
+            | Rule::directive_block_start
+            | Rule::directive_block_end
+            | Rule::partial_block_start
+            | Rule::partial_block_end
+            | Rule::partial_expression
+            | Rule::hbs_comment
+            | Rule::hbs_comment_compact
+            | Rule::set_delimiters => true,
+            _ => false,
+        }
+    }
+
+    // returns the indentation of the tag and the position after its line
+    // when there is only whitespace around the tag on its line, the line
+    // of the tag starts at `line_start`
+    fn standalone_line(
+        source: &str,
+        rule: Rule,
+        span: &Span,
+        line_start: usize,
+    ) -> Option<(String, usize)> {
+        if !Template::is_standalone_tag(rule) {
+            return None;
+        }
+
+        let indent = &source[line_start..span.start()];
+        if !indent.chars().all(|c| c == ' ' || c == '\t') {
+            return None;
+        }
+
+        for (i, c) in source[span.end()..].char_indices() {
+            match c {
+                '\n' => return Some((indent.to_owned(), span.end() + i + 1)),
+                ' ' | '\t' | '\r' => {}
+                _ => return None,
+            }
+        }
+        Some((indent.to_owned(), source.len()))
+    }
+
+    // removes the indentation of a standalone tag from the text before it
+    fn remove_indentation(template_stack: &mut VecDeque<Template>, indent: &str) {
+        if indent.is_empty() {
+            return;
+        }
+        let t = template_stack.front_mut().unwrap();
+        let is_empty = match t.elements.last_mut() {
+            Some(RawString(ref mut text)) if text.ends_with(indent) => {
+                let len = text.len() - indent.len();
+                text.truncate(len);
+                text.is_empty()
+            }
+            _ => false,
+        };
+        if is_empty {
+            t.elements.pop();
+            if let Some(ref mut maps) = t.mapping {
+                maps.pop();
+            }
+        }
+    }
+
     fn remove_previous_whitespace(template_stack: &mut VecDeque<Template>) {
         let t = template_stack.front_mut().unwrap();
         if let Some(el) = t.elements.pop() {
@@ -437,9 +769,50 @@ impl Template {
         }
     }
 
-    pub fn compile2<S: AsRef<str>>(source: S, mapping: bool) -> Result<Template, TemplateError> {
+    pub fn compile_with_options<S: AsRef<str>>(
+        source: S,
+        options: &TemplateOptions,
+    ) -> Result<Template, TemplateError> {
         let source = source.as_ref();
-        let mut helper_stack: VecDeque<HelperTemplate> = VecDeque::new();
+        let (open, close) = match options.delimiters {
+            Some((ref open, ref close)) => (open, close),
+            None => return Template::compile_source(source, options, 0),
+        };
+        if !is_valid_delimiter(open) || !is_valid_delimiter(close) {
+            return Err(TemplateError::of(TemplateErrorReason::InvalidDelimiters(
+                open.clone(),
+                close.clone(),
+            )));
+        }
+
+        // the delimiters are set by a set delimiter tag put before the
+        // source, positions on the first line are moved back
+        let prefix = format!("{{{{={} {}=}}}}", open, close);
+        let prefix_len = prefix.chars().count();
+        let prefixed = format!("{}{}", prefix, source);
+        let mut t =
+            Template::compile_source(&prefixed, options, prefix.len()).map_err(|e| {
+                match (e.line_no, e.column_no) {
+                    (Some(1), Some(col)) => e.at(source, 1, col.saturating_sub(prefix_len).max(1)),
+                    (Some(line), Some(col)) => e.at(source, line, col),
+                    _ => e,
+                }
+            })?;
+        t.shift_first_line(prefix_len);
+        Ok(t)
+    }
+
+    // `prefix_len` is the length of the set delimiter tag before the source,
+    // if any
+    fn compile_source(
+        source: &str,
+        options: &TemplateOptions,
+        prefix_len: usize,
+    ) -> Result<Template, TemplateError> {
+        let mapping = options.source_map;
+        // helpers of open blocks, with the position of the `{{else name}}` tag
+        // for helpers chained to the inverse of the previous one
+        let mut helper_stack: VecDeque<(HelperTemplate, Option<TemplateMapping>)> = VecDeque::new();
         let mut directive_stack: VecDeque<DirectiveTemplate> = VecDeque::new();
         let mut template_stack: VecDeque<Template> = VecDeque::new();
 
@@ -461,6 +834,10 @@ impl Template {
             .filter(|p| p.as_rule() != Rule::escape)
             .peekable();
         let mut end_pos: Option<Position> = None;
+        // the start of the current line, the source before `scanned` was
+        // searched for newlines already
+        let mut line_start = prefix_len;
+        let mut scanned = prefix_len;
         loop {
             if let Some(pair) = it.next() {
                 let prev_end = end_pos.as_ref().map(|p| p.pos()).unwrap_or(0);
@@ -494,6 +871,23 @@ impl Template {
                     }
                 }
 
+                let is_prefix = prefix_len > 0 && span.start() == 0;
+                // indentation and end of line of a tag alone on its line
+                let standalone = if options.ignore_standalone || is_prefix {
+                    None
+                } else {
+                    if span.start() > scanned {
+                        if let Some(i) = source[scanned..span.start()].rfind('\n') {
+                            line_start = scanned + i + 1;
+                        }
+                        scanned = span.start();
+                    }
+                    Template::standalone_line(source, rule, &span, line_start)
+                };
+                if let Some((ref indent, _)) = standalone {
+                    Template::remove_indentation(&mut template_stack, indent);
+                }
+
                 let (line_no, col_no) = span.start_pos().line_col();
                 match rule {
                     Rule::template => {
@@ -519,23 +913,34 @@ impl Template {
                         );
                     }
                     Rule::helper_block_start
+                    | Rule::inverted_block_start
                     | Rule::raw_block_start
                     | Rule::directive_block_start
                     | Rule::partial_block_start => {
-                        let exp = Template::parse_expression(source, it.by_ref(), span.end())?;
+                        let exp =
+                            Template::parse_expression(source, it.by_ref(), span.end(), mapping)?;
 
                         match rule {
-                            Rule::helper_block_start | Rule::raw_block_start => {
+                            Rule::helper_block_start
+                            | Rule::inverted_block_start
+                            | Rule::raw_block_start => {
+                                // the content of `{{^name}}` is the inverse, an
+                                // empty template is given as the main section
+                                let template = if rule == Rule::inverted_block_start {
+                                    Some(Template::new(mapping))
+                                } else {
+                                    None
+                                };
                                 let helper_template = HelperTemplate {
                                     name: exp.name,
                                     params: exp.params,
                                     hash: exp.hash,
                                     block_param: exp.block_param,
                                     block: true,
-                                    template: None,
+                                    template,
                                     inverse: None,
                                 };
-                                helper_stack.push_front(helper_template);
+                                helper_stack.push_front((helper_template, None));
                             }
                             Rule::directive_block_start | Rule::partial_block_start => {
                                 let directive = DirectiveTemplate {
@@ -543,6 +948,7 @@ impl Template {
                                     params: exp.params,
                                     hash: exp.hash,
                                     template: None,
+                                    indent: None,
                                 };
                                 directive_stack.push_front(directive);
                             }
@@ -562,7 +968,23 @@ impl Template {
                     Rule::invert_tag => {
                         // hack: invert_tag structure is similar to ExpressionSpec, so I
                         // use it here to represent the data
-                        let exp = Template::parse_expression(source, it.by_ref(), span.end())?;
+                        let exp =
+                            Template::parse_expression(source, it.by_ref(), span.end(), mapping)?;
+
+                        if exp.omit_pre_ws {
+                            Template::remove_previous_whitespace(&mut template_stack);
+                        }
+                        omit_pro_ws = exp.omit_pro_ws;
+
+                        let t = template_stack.pop_front().unwrap();
+                        let h = &mut helper_stack.front_mut().unwrap().0;
+                        Template::end_section(h, t);
+                    }
+                    Rule::invert_chain_tag => {
+                        // `{{else name params}}` ends the current section and opens
+                        // a block of `name` as the inverse
+                        let exp =
+                            Template::parse_expression(source, it.by_ref(), span.end(), mapping)?;
 
                         if exp.omit_pre_ws {
                             Template::remove_previous_whitespace(&mut template_stack);
@@ -570,8 +992,20 @@ impl Template {
                         omit_pro_ws = exp.omit_pro_ws;
 
                         let t = template_stack.pop_front().unwrap();
-                        let h = helper_stack.front_mut().unwrap();
-                        h.template = Some(t);
+                        let h = &mut helper_stack.front_mut().unwrap().0;
+                        Template::end_section(h, t);
+
+                        let helper_template = HelperTemplate {
+                            name: exp.name,
+                            params: exp.params,
+                            hash: exp.hash,
+                            block_param: exp.block_param,
+                            block: true,
+                            template: None,
+                            inverse: None,
+                        };
+                        helper_stack
+                            .push_front((helper_template, Some(TemplateMapping(line_no, col_no))));
                     }
                     Rule::raw_block_text => {
                         let mut t = Template::new(mapping);
@@ -590,7 +1024,8 @@ impl Template {
                     | Rule::raw_block_end
                     | Rule::directive_block_end
                     | Rule::partial_block_end => {
-                        let exp = Template::parse_expression(source, it.by_ref(), span.end())?;
+                        let exp =
+                            Template::parse_expression(source, it.by_ref(), span.end(), mapping)?;
                         if exp.omit_pre_ws {
                             Template::remove_previous_whitespace(&mut template_stack);
                         }
@@ -618,11 +1053,17 @@ impl Template {
                                 t.push_element(el, line_no, col_no);
                             }
                             Rule::directive_expression | Rule::partial_expression => {
+                                let omit_pre_ws = exp.omit_pre_ws;
                                 let directive = DirectiveTemplate {
                                     name: exp.name,
                                     params: exp.params,
                                     hash: exp.hash,
                                     template: None,
+                                    // `{{~> name}}` removes the indentation
+                                    indent: standalone
+                                        .as_ref()
+                                        .map(|(indent, _)| indent.clone())
+                                        .filter(|indent| !indent.is_empty() && !omit_pre_ws),
                                 };
                                 let el = if rule == Rule::directive_expression {
                                     DirectiveExpression(directive)
@@ -633,10 +1074,27 @@ impl Template {
                                 t.push_element(el, line_no, col_no);
                             }
                             Rule::helper_block_end | Rule::raw_block_end => {
-                                let mut h = helper_stack.pop_front().unwrap();
+                                let mut prev_t = template_stack.pop_front().unwrap();
+                                let (mut h, mut chained) = helper_stack.pop_front().unwrap();
+                                // chained helpers end with the block they are chained to,
+                                // each one becomes the inverse of the previous helper
+                                while let Some(TemplateMapping(line, col)) = chained {
+                                    if h.template.is_some() {
+                                        h.inverse = Some(prev_t);
+                                    } else {
+                                        h.template = Some(prev_t);
+                                    }
+                                    prev_t = Template::new(mapping);
+                                    prev_t.push_element(HelperBlock(Box::new(h)), line, col);
+
+                                    let (parent, parent_chained) =
+                                        helper_stack.pop_front().unwrap();
+                                    h = parent;
+                                    chained = parent_chained;
+                                }
+
                                 let close_tag_name = exp.name;
                                 if h.name == close_tag_name {
-                                    let prev_t = template_stack.pop_front().unwrap();
                                     if h.template.is_some() {
                                         h.inverse = Some(prev_t);
                                     } else {
@@ -679,26 +1137,37 @@ impl Template {
                             _ => unreachable!(),
                         }
                     }
-                    Rule::hbs_comment_compact => {
-                        let text = span
-                            .as_str()
-                            .trim_start_matches("{{!")
-                            .trim_end_matches("}}");
+                    Rule::hbs_comment_compact | Rule::hbs_comment => {
+                        let mut text = it.next().unwrap();
+                        if text.as_rule() == Rule::pre_whitespace_omitter {
+                            Template::remove_previous_whitespace(&mut template_stack);
+                            text = it.next().unwrap();
+                        }
+                        omit_pro_ws = it
+                            .peek()
+                            .map_or(false, |p| p.as_rule() == Rule::pro_whitespace_omitter);
+                        if omit_pro_ws {
+                            it.next();
+                        }
+
                         let t = template_stack.front_mut().unwrap();
-                        t.push_element(Comment(text.to_owned()), line_no, col_no);
+                        t.push_element(Comment(text.as_str().to_owned()), line_no, col_no);
                     }
-                    Rule::hbs_comment => {
-                        let text = span
-                            .as_str()
-                            .trim_start_matches("{{!--")
-                            .trim_end_matches("--}}");
-                        let t = template_stack.front_mut().unwrap();
-                        t.push_element(Comment(text.to_owned()), line_no, col_no);
+                    Rule::set_delimiters => {
+                        if !options.mustache && !is_prefix {
+                            return Err(TemplateError::of(TemplateErrorReason::InvalidSyntax)
+                                .at(source, line_no, col_no));
+                        }
+                        // the new delimiters
+                        it.next();
+                        it.next();
                     }
                     _ => {}
                 }
 
-                if rule != Rule::template {
+                if let Some((_, line_end)) = standalone {
+                    end_pos = Position::new(source, line_end);
+                } else if rule != Rule::template {
                     end_pos = Some(span.end_pos());
                 }
             } else {
@@ -715,12 +1184,36 @@ impl Template {
         }
     }
 
+    // moves the mappings on the first line `cols` columns to the left
+    fn shift_first_line(&mut self, cols: usize) {
+        if let Some(ref mut maps) = self.mapping {
+            for m in maps.iter_mut().filter(|m| m.0 == 1) {
+                m.1 -= cols;
+            }
+        }
+        for e in &mut self.elements {
+            e.shift_first_line(cols);
+        }
+    }
+
     pub fn compile_with_name<S: AsRef<str>>(
         source: S,
         name: String,
         mapping: bool,
     ) -> Result<Template, TemplateError> {
-        match Template::compile2(source, mapping) {
+        let options = TemplateOptions {
+            source_map: mapping,
+            ..TemplateOptions::default()
+        };
+        Template::compile_named(source, name, &options)
+    }
+
+    pub(crate) fn compile_named<S: AsRef<str>>(
+        source: S,
+        name: String,
+        options: &TemplateOptions,
+    ) -> Result<Template, TemplateError> {
+        match Template::compile_with_options(source, options) {
             Ok(mut t) => {
                 t.name = Some(name);
                 Ok(t)
@@ -731,6 +1224,7 @@ impl Template {
 }
 
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "ast_serde", derive(Serialize, Deserialize))]
 pub enum TemplateElement {
     RawString(String),
     HTMLExpression(Parameter),
@@ -743,6 +1237,36 @@ pub enum TemplateElement {
     Comment(String),
 }
 
+impl TemplateElement {
+    fn shift_first_line(&mut self, cols: usize) {
+        match *self {
+            HTMLExpression(ref mut p) => p.shift_first_line(cols),
+            Expression(ref mut ht) | HelperBlock(ref mut ht) => {
+                let params = ht.params.iter_mut().chain(ht.hash.values_mut());
+                for p in Some(&mut ht.name).into_iter().chain(params) {
+                    p.shift_first_line(cols);
+                }
+                for t in ht.template.iter_mut().chain(ht.inverse.iter_mut()) {
+                    t.shift_first_line(cols);
+                }
+            }
+            DirectiveExpression(ref mut dt)
+            | DirectiveBlock(ref mut dt)
+            | PartialExpression(ref mut dt)
+            | PartialBlock(ref mut dt) => {
+                let params = dt.params.iter_mut().chain(dt.hash.values_mut());
+                for p in Some(&mut dt.name).into_iter().chain(params) {
+                    p.shift_first_line(cols);
+                }
+                if let Some(ref mut t) = dt.template {
+                    t.shift_first_line(cols);
+                }
+            }
+            RawString(_) | Comment(_) => {}
+        }
+    }
+}
+
 #[test]
 fn test_parse_escaped_tag_raw_string() {
     let source = r"foo \{{bar}}";
@@ -839,6 +1363,50 @@ fn test_parse_error() {
     );
 }
 
+#[test]
+fn test_invalid_path() {
+    for &(source, path, col) in &[
+        ("{{foo/../bar}}", "foo/../bar", 3),
+        (
+            "{{#each a}}{{x text/this/foo}}{{/each}}",
+            "text/this/foo",
+            16,
+        ),
+        ("{{#foo.. a}}{{/foo..}}", "foo..", 4),
+    ] {
+        assert_eq!(
+            Template::compile(source).unwrap_err(),
+            TemplateError::of(TemplateErrorReason::InvalidPath(path.to_owned())).at(source, 1, col)
+        );
+    }
+
+    for source in &[
+        "{{../foo}}",
+        "{{this/foo}}",
+        "{{./foo}}",
+        "{{@../index}}",
+        "{{a.[this]}}",
+    ] {
+        assert!(Template::compile(*source).is_ok(), "{}", source);
+    }
+}
+
+#[test]
+fn test_literal_names() {
+    let t = Template::compile("{{\"foo bar\"}}{{#'list'}}{{/'list'}}{{> \"a/b?\"}}{{12.34}}{{1}}")
+        .unwrap();
+    let names: Vec<&str> = t
+        .elements
+        .iter()
+        .map(|e| match *e {
+            Expression(ref h) | HelperBlock(ref h) => h.name.as_name().unwrap(),
+            PartialExpression(ref d) => d.name.as_name().unwrap(),
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(names, ["[foo bar]", "[list]", "[a/b?]", "[12.34]", "[1]"]);
+}
+
 #[test]
 fn test_subexpression() {
     let source = "{{foo (bar)}}{{foo (bar baz)}} hello {{#if (baz bar) then=(bar)}}world{{/if}}";
@@ -850,7 +1418,7 @@ fn test_subexpression() {
             assert_eq!(h.name.as_name().unwrap(), "foo".to_owned());
             assert_eq!(h.params.len(), 1);
             if let &Parameter::Subexpression(ref t) = h.params.get(0).unwrap() {
-                assert_eq!(t.name(), "bar".to_owned());
+                assert_eq!(t.name(), "bar");
             } else {
                 panic!("Subexpression expected");
             }
@@ -865,7 +1433,7 @@ fn test_subexpression() {
             assert_eq!(h.name.as_name().unwrap(), "foo".to_string());
             assert_eq!(h.params.len(), 1);
             if let &Parameter::Subexpression(ref t) = h.params.get(0).unwrap() {
-                assert_eq!(t.name(), "bar".to_owned());
+                assert_eq!(t.name(), "bar");
                 if let Some(&Parameter::Name(ref n)) = t.params().unwrap().get(0) {
                     assert_eq!(n, "baz");
                 } else {
@@ -887,7 +1455,7 @@ fn test_subexpression() {
             assert_eq!(h.hash.len(), 1);
 
             if let &Parameter::Subexpression(ref t) = h.params.get(0).unwrap() {
-                assert_eq!(t.name(), "baz".to_owned());
+                assert_eq!(t.name(), "baz");
                 if let Some(&Parameter::Name(ref n)) = t.params().unwrap().get(0) {
                     assert_eq!(n, "bar");
                 } else {
@@ -898,7 +1466,7 @@ fn test_subexpression() {
             }
 
             if let &Parameter::Subexpression(ref t) = h.hash.get("then").unwrap() {
-                assert_eq!(t.name(), "bar".to_owned());
+                assert_eq!(t.name(), "bar");
             } else {
                 panic!("Subexpression expected (bar)");
             }
@@ -909,6 +1477,247 @@ fn test_subexpression() {
     }
 }
 
+#[test]
+fn test_parse_else_chain() {
+    let source = "{{#if a}}a{{else if b}}b{{else with c as |x|}}{{x}}{{else}}d{{/if}}";
+    let t = Template::compile2(source, true).unwrap();
+    assert_eq!(t.elements.len(), 1);
+
+    let h = match t.elements[0] {
+        HelperBlock(ref h) => h,
+        _ => panic!("HelperBlock expected"),
+    };
+    assert_eq!(h.name.as_name(), Some("if"));
+    let inverse = h.inverse.as_ref().unwrap();
+    assert_eq!(inverse.mapping, Some(vec![TemplateMapping(1, 11)]));
+
+    let h = match inverse.elements[0] {
+        HelperBlock(ref h) => h,
+        _ => panic!("chained HelperBlock expected"),
+    };
+    assert_eq!(h.name.as_name(), Some("if"));
+    assert_eq!(h.params, vec![Parameter::Name("b".to_owned())]);
+    assert_eq!(
+        h.template.as_ref().unwrap().elements,
+        vec![RawString("b".to_owned())]
+    );
+
+    let h = match h.inverse.as_ref().unwrap().elements[0] {
+        HelperBlock(ref h) => h,
+        _ => panic!("chained HelperBlock expected"),
+    };
+    assert_eq!(h.name.as_name(), Some("with"));
+    assert_eq!(
+        h.block_param,
+        Some(BlockParam::Single(Parameter::Name("x".to_owned())))
+    );
+    assert_eq!(
+        h.inverse.as_ref().unwrap().elements,
+        vec![RawString("d".to_owned())]
+    );
+
+    // `else` starting a longer name is still an expression
+    let t = Template::compile("{{#if a}}{{elsewhere}}{{/if}}").unwrap();
+    match t.elements[0] {
+        HelperBlock(ref h) => assert!(h.inverse.is_none()),
+        _ => panic!("HelperBlock expected"),
+    }
+}
+
+#[test]
+fn test_parse_inverted_block() {
+    let t = Template::compile("{{^list}}empty{{else}}{{.}}{{/list}}").unwrap();
+    match t.elements[0] {
+        HelperBlock(ref h) => {
+            assert_eq!(h.name.as_name(), Some("list"));
+            assert_eq!(
+                h.inverse.as_ref().unwrap().elements,
+                vec![RawString("empty".to_owned())]
+            );
+            assert_eq!(h.template.as_ref().unwrap().elements.len(), 1);
+        }
+        _ => panic!("HelperBlock expected"),
+    }
+
+    let t = Template::compile("{{^list}}empty{{/list}}").unwrap();
+    match t.elements[0] {
+        HelperBlock(ref h) => {
+            assert!(h.template.as_ref().unwrap().elements.is_empty());
+            assert_eq!(
+                h.inverse.as_ref().unwrap().elements,
+                vec![RawString("empty".to_owned())]
+            );
+        }
+        _ => panic!("HelperBlock expected"),
+    }
+}
+
+#[test]
+fn test_parse_set_delimiters() {
+    let source = "{{=<% %>=}}<%! note %><%name%> {{raw}}<%={{ }}=%>{{name}}";
+    assert!(Template::compile(source).is_err());
+
+    let options = TemplateOptions {
+        mustache: true,
+        ..TemplateOptions::default()
+    };
+    let t = Template::compile_with_options(source, &options).unwrap();
+    assert_eq!(
+        t.elements,
+        vec![
+            Comment(" note ".to_owned()),
+            Expression(Box::new(HelperTemplate::with_name("name".to_owned()))),
+            RawString(" {{raw}}".to_owned()),
+            Expression(Box::new(HelperTemplate::with_name("name".to_owned()))),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_with_delimiters() {
+    let options = TemplateOptions {
+        delimiters: Some(("<%".to_owned(), "%>".to_owned())),
+        ..TemplateOptions::default()
+    };
+    let source = "{{a}} <%b%>\n<%#if (c d)%><%e%><%/if%>";
+    let t = Template::compile_with_options(source, &options).unwrap();
+    assert_eq!(t.elements.len(), 4);
+    assert_eq!(t.elements[0], RawString("{{a}} ".to_owned()));
+    assert_eq!(
+        t.mapping,
+        Some(vec![
+            TemplateMapping(1, 1),
+            TemplateMapping(1, 7),
+            TemplateMapping(2, 1),
+            TemplateMapping(2, 1),
+        ])
+    );
+    match t.elements[3] {
+        HelperBlock(ref h) => match h.params[0] {
+            Parameter::Subexpression(ref s) => {
+                assert_eq!(s.mapping, Some(TemplateMapping(2, 7)));
+            }
+            _ => panic!("Subexpression expected"),
+        },
+        _ => panic!("HelperBlock expected"),
+    }
+
+    let err = Template::compile_with_options("<%a%> <%#if%>", &options).unwrap_err();
+    assert_eq!(err.line_no, Some(1));
+    assert_eq!(err.column_no, Some(14));
+
+    for &(open, close) in &[("", "%>"), ("< %", "%>"), ("<%", "=>")] {
+        let options = TemplateOptions {
+            delimiters: Some((open.to_owned(), close.to_owned())),
+            ..TemplateOptions::default()
+        };
+        assert_eq!(
+            Template::compile_with_options("", &options)
+                .unwrap_err()
+                .reason,
+            TemplateErrorReason::InvalidDelimiters(open.to_owned(), close.to_owned())
+        );
+    }
+}
+
+#[test]
+fn test_parse_standalone_lines() {
+    let source =
+        "a\n  {{#if b}}  \n  c\n  {{else}}\n{{! d }}\n  {{> e}}\n{{/if}}\r\nf {{#if g}}\n{{/if}}";
+    let t = Template::compile(source).unwrap();
+    assert_eq!(t.elements.len(), 4);
+    assert_eq!(t.elements[0], RawString("a\n".to_owned()));
+    assert_eq!(t.elements[2], RawString("f ".to_owned()));
+    match t.elements[1] {
+        HelperBlock(ref h) => {
+            assert_eq!(
+                h.template.as_ref().unwrap().elements,
+                vec![RawString("  c\n".to_owned())]
+            );
+            let inverse = &h.inverse.as_ref().unwrap().elements;
+            assert_eq!(inverse.len(), 2);
+            match inverse[1] {
+                PartialExpression(ref d) => assert_eq!(d.indent, Some("  ".to_owned())),
+                _ => panic!("PartialExpression expected"),
+            }
+        }
+        _ => panic!("HelperBlock expected"),
+    }
+    // the block tag is not alone on its line
+    match t.elements[3] {
+        HelperBlock(ref h) => assert_eq!(
+            h.template.as_ref().unwrap().elements,
+            vec![RawString("\n".to_owned())]
+        ),
+        _ => panic!("HelperBlock expected"),
+    }
+
+    let options = TemplateOptions {
+        ignore_standalone: true,
+        ..TemplateOptions::default()
+    };
+    let t = Template::compile_with_options(source, &options).unwrap();
+    assert_eq!(t.elements[0], RawString("a\n  ".to_owned()));
+}
+
+#[test]
+fn test_nested_subexpression() {
+    let source = "{{foo ((bar) (baz (qux)))\n  k=(a (b (c)))}}";
+    let t = Template::compile2(source, true).unwrap();
+
+    let h = match t.elements[0] {
+        Expression(ref h) => h,
+        _ => panic!("Helper expression expected"),
+    };
+    let s = match h.params[0] {
+        Parameter::Subexpression(ref s) => s,
+        _ => panic!("Subexpression expected"),
+    };
+    // helper name given by a subexpression
+    assert_eq!(s.name(), "");
+    assert_eq!(s.helper_name(), None);
+    assert_eq!(s.mapping, Some(TemplateMapping(1, 7)));
+    match *s.as_element() {
+        Expression(ref ht) => match ht.name {
+            Parameter::Subexpression(ref n) => {
+                assert_eq!(n.name(), "bar");
+                assert_eq!(n.mapping, Some(TemplateMapping(1, 8)));
+            }
+            _ => panic!("Subexpression expected as name"),
+        },
+        _ => unreachable!(),
+    }
+    match s.params().unwrap()[0] {
+        Parameter::Subexpression(ref p) => {
+            assert_eq!(p.name(), "baz");
+            assert_eq!(p.mapping, Some(TemplateMapping(1, 14)));
+        }
+        _ => panic!("Subexpression expected"),
+    }
+
+    let mut k = match h.hash["k"] {
+        Parameter::Subexpression(ref s) => s,
+        _ => panic!("Subexpression expected"),
+    };
+    for &(name, col) in &[("a", 5), ("b", 8), ("c", 11)] {
+        assert_eq!(k.name(), name);
+        assert_eq!(k.mapping, Some(TemplateMapping(2, col)));
+        if let Some(&Parameter::Subexpression(ref s)) = k.params().unwrap().get(0) {
+            k = s;
+        }
+    }
+
+    // positions are only kept with source map
+    let t = Template::compile(source).unwrap();
+    match t.elements[0] {
+        Expression(ref h) => match h.params[0] {
+            Parameter::Subexpression(ref s) => assert_eq!(s.mapping, None),
+            _ => panic!("Subexpression expected"),
+        },
+        _ => panic!("Helper expression expected"),
+    }
+}
+
 #[test]
 fn test_white_space_omitter() {
     let source = "hello~     {{~world~}} \n  !{{~#if true}}else{{/if~}}".to_string();
@@ -940,6 +1749,16 @@ fn test_white_space_omitter() {
         }
         _ => unreachable!(),
     }
+
+    let t3 = Template::compile("a {{~! one ~}} b {{~!-- two --~}} c {{~{d}~}} e".to_string())
+        .ok()
+        .unwrap();
+    assert_eq!(t3.elements.len(), 7);
+    assert_eq!(t3.elements[0], RawString("a".to_string()));
+    assert_eq!(t3.elements[1], Comment(" one ".to_string()));
+    assert_eq!(t3.elements[2], RawString("b".to_string()));
+    assert_eq!(t3.elements[4], RawString("c".to_string()));
+    assert_eq!(t3.elements[6], RawString("e".to_string()));
 }
 
 #[test]
@@ -1128,3 +1947,24 @@ fn test_directive() {
         }
     }
 }
+
+#[test]
+#[cfg(feature = "ast_serde")]
+fn test_serde_roundtrip() {
+    let source = "{{#each items as |item i|}}{{i}} {{{item.name}}}\
+                  {{else}}{{lookup (lookup this \"x\") 1 k=[1, {\"a\": null}]}}{{/each}}\
+                  {{!-- comment --}}{{#*inline \"p\"}}inline{{/inline}}{{#> p}}x{{/p}}";
+    let t = Template::compile_with_name(source, "t0".to_owned(), true).unwrap();
+
+    let s = serde_json::to_string(&t).unwrap();
+    let t2: Template = serde_json::from_str(&s).unwrap();
+    assert_eq!(t, t2);
+
+    let mut r = crate::registry::Registry::new();
+    r.register_template("t0", t2);
+    assert_eq!(
+        r.render("t0", &json!({"items": [{"name": "<a>"}]}))
+            .unwrap(),
+        "0 <a>inline"
+    );
+}
@@ -9,7 +9,7 @@ pub(crate) static DEFAULT_VALUE: Json = Json::Null;
 /// * Context:  the JSON value referenced in your provided data context
 /// * Derived:  the owned JSON value computed during rendering process
 ///
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ScopedJson<'reg: 'rc, 'rc> {
     Constant(&'reg Json),
     Derived(Json),
@@ -128,6 +128,10 @@ impl<'reg: 'rc, 'rc> PathAndJson<'reg, 'rc> {
         self.value.as_json()
     }
 
+    pub(crate) fn scoped_value(&self) -> &ScopedJson<'reg, 'rc> {
+        &self.value
+    }
+
     /// Test if value is missing
     pub fn is_value_missing(&self) -> bool {
         self.value.is_missing()
@@ -0,0 +1,65 @@
+extern crate handlebars;
+#[macro_use]
+extern crate serde_json;
+
+use handlebars::template::TemplateOptions;
+use handlebars::{Handlebars, Template};
+
+#[test]
+fn test_registry_delimiters() {
+    let mut hbs = Handlebars::new();
+    hbs.set_delimiters("<%", "%>");
+    assert_eq!(hbs.delimiters(), ("<%", "%>"));
+
+    hbs.register_partial("item", "<<%name%>>").unwrap();
+    let data = json!({"title": "<b>", "items": [{"name": "a"}, {"name": "b"}]});
+    let cases = vec![
+        ("{{ title }} <%title%>", "{{ title }} &lt;b&gt;"),
+        ("<%{title}%> <%&title%>", "<b> <b>"),
+        ("<%#each items%><%name%>,<%/each%>", "a,b,"),
+        ("<%#if missing%>yes<%else%>no<%/if%>", "no"),
+        ("<%#each items%><%> item%><%/each%>", "<a><b>"),
+        ("a<%! comment %>b<%!-- <%x%> --%>c", "abc"),
+        ("a  <%~title~%>  b", "a&lt;b&gt;b"),
+        ("<%<%raw%>%><%x%> {{y}}<%<%/raw%>%>", "<%x%> {{y}}"),
+        ("<%#if (not (not title))%>y<%/if%>", "y"),
+        ("\\<%title%>", "<%title%>"),
+    ];
+    for &(tpl, expected) in &cases {
+        hbs.register_template_string("t", tpl).unwrap();
+        assert_eq!(hbs.render("t", &data).unwrap(), expected, "{}", tpl);
+    }
+
+    hbs.reset_delimiters();
+    assert_eq!(hbs.delimiters(), ("{{", "}}"));
+    assert_eq!(
+        hbs.render_template("{{title}}<%title%>", &data).unwrap(),
+        "&lt;b&gt;<%title%>"
+    );
+}
+
+#[test]
+fn test_template_delimiters() {
+    let mut hbs = Handlebars::new();
+    let options = TemplateOptions {
+        delimiters: Some(("[[".to_owned(), "]]".to_owned())),
+        ..TemplateOptions::default()
+    };
+    let t = Template::compile_with_options("[[#each list]][[this]] {{ v }}[[/each]]", &options)
+        .unwrap();
+    hbs.register_template("vue", t);
+    hbs.register_template_string("hbs", "{{#each list}}{{this}}{{/each}}")
+        .unwrap();
+
+    let data = json!({"list": [1, 2]});
+    assert_eq!(hbs.render("vue", &data).unwrap(), "1 {{ v }}2 {{ v }}");
+    assert_eq!(hbs.render("hbs", &data).unwrap(), "12");
+}
+
+#[test]
+fn test_invalid_delimiters() {
+    let mut hbs = Handlebars::new();
+    hbs.set_delimiters("<%", "% >");
+    let err = hbs.register_template_string("t", "<%a%>").unwrap_err();
+    assert_eq!(err.to_string(), "invalid delimiters \"<%\" and \"% >\"");
+}
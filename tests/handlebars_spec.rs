@@ -0,0 +1,344 @@
+//! Runs the Handlebars.js spec fixtures in `tests/handlebars/specs`. Cases
+//! listed in `tests/handlebars/skip.json`, by file and name, are expected to
+//! fail; they are reported once they pass.
+//!
+//! The helpers of a case map a helper name to an entry of `helper_catalog`,
+//! the Rust counterparts of the JavaScript helpers used upstream, and its
+//! decorators to `decorator_catalog`. The `runtime_data` of a case, the
+//! `data` option of Handlebars.js, is set as `@` variables before rendering.
+//! Cases with `compat` set run in Mustache mode, the compat mode of
+//! Handlebars.js, and `SectionHelper` is the `blockHelperMissing` of all
+//! cases.
+extern crate handlebars;
+extern crate serde_json;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use std::io;
+
+use handlebars::{
+    Context, Decorator, DecoratorDef, Handlebars, Helper, HelperDef, HelperResult, Output,
+    RenderContext, RenderError, Renderable, ScopedJson, SectionHelper,
+};
+use serde_json::Value as Json;
+
+const SPEC_DIR: &str = "tests/handlebars/specs";
+const SKIP_LIST: &str = "tests/handlebars/skip.json";
+const CASE_TEMPLATE: &str = "spec";
+
+type CatalogFn = fn(&[Json], &BTreeMap<&str, Json>) -> Json;
+
+struct CatalogHelper(CatalogFn);
+
+impl HelperDef for CatalogHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg>,
+    ) -> Result<Option<ScopedJson<'reg, 'rc>>, RenderError> {
+        let params: Vec<Json> = h.params().iter().map(|p| p.value().clone()).collect();
+        let hash = h
+            .hash()
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.value().clone()))
+            .collect();
+        Ok(Some(ScopedJson::Derived((self.0)(&params, &hash))))
+    }
+}
+
+// `function() { return new Handlebars.SafeString("&'\\<>"); }`
+struct SafeStringHelper;
+
+impl HelperDef for SafeStringHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        _: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        out.write("&'\\<>")?;
+        Ok(())
+    }
+}
+
+// `function(options) { return options.data.adjective + " " + this.noun; }`
+struct AdjectiveNounHelper;
+
+impl HelperDef for AdjectiveNounHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        _: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let adjective = rc
+            .get_local_var("@adjective")
+            .map(render)
+            .unwrap_or_default();
+        let noun = render(&ctx.data()["noun"]);
+        out.write(&format!("{} {}", adjective, noun))?;
+        Ok(())
+    }
+}
+
+// renders its block with the hash set as `@` variables, like
+// `options.fn(this, {data: {world: options.hash.world}})`
+struct WithDataHelper;
+
+impl HelperDef for WithDataHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        r: &'reg Handlebars,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        for (name, value) in h.hash() {
+            rc.set_local_var(format!("@{}", name), value.value().clone());
+        }
+        match h.template() {
+            Some(t) => t.render(r, ctx, rc, out),
+            None => Ok(()),
+        }
+    }
+}
+
+// renders its block with `@depth` one more than the enclosing one, which
+// becomes `@../depth`
+struct DepthHelper;
+
+impl HelperDef for DepthHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        r: &'reg Handlebars,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let depth = rc
+            .get_local_var("@depth")
+            .and_then(Json::as_u64)
+            .unwrap_or(0);
+        rc.promote_local_vars();
+        rc.set_local_var("@depth".to_owned(), Json::from(depth + 1));
+        let result = match h.template() {
+            Some(t) => t.render(r, ctx, rc, out),
+            None => Ok(()),
+        };
+        rc.demote_local_vars();
+        result
+    }
+}
+
+struct StringOutput(String);
+
+impl Output for StringOutput {
+    fn write(&mut self, seg: &str) -> Result<(), io::Error> {
+        self.0.push_str(seg);
+        Ok(())
+    }
+}
+
+fn render(value: &Json) -> String {
+    match value {
+        Json::String(s) => s.clone(),
+        Json::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn helper_catalog(id: &str) -> Option<CatalogFn> {
+    let helper: CatalogFn = match id {
+        "awesome" => |_, _| Json::from("awesome"),
+        "awesome_capitalized" => |_, _| Json::from("Awesome"),
+        "undefined_null" => |params, _| {
+            Json::from(format!(
+                "{} {} object",
+                params[0].is_null(),
+                params[1].is_null()
+            ))
+        },
+        "hello" => |params, _| Json::from(format!("Hello {}", render(&params[0]))),
+        "hello_noun" => |_, hash| Json::from(format!("Hello {}", render(&hash["noun"]))),
+        "prefix_bar" => |params, _| Json::from(format!("bar {}", render(&params[0]))),
+        "double" => |params, _| Json::from(render(&params[0]).repeat(2)),
+        "lol" => |_, _| Json::from("LOL"),
+        "identity" => |params, _| params[0].clone(),
+        "equal" => |params, _| Json::from(params[0] == params[1]),
+        "dash" => |params, _| Json::from(format!("{}-{}", render(&params[0]), render(&params[1]))),
+        "concat" => |params, _| Json::from(format!("{}{}", render(&params[0]), render(&params[1]))),
+        "blog" => |params, hash| match (hash.get("fun"), params.len()) {
+            (Some(fun), _) => Json::from(format!("val is {}", render(fun))),
+            (None, 3) => Json::from(format!(
+                "val is {}, {} and {}",
+                render(&params[0]),
+                render(&params[1]),
+                render(&params[2])
+            )),
+            _ => Json::from(format!("val is {}", render(&params[0]))),
+        },
+        "input" => |_, hash| {
+            let attrs: Vec<String> = hash
+                .iter()
+                .map(|(k, v)| format!("{}={:?}", k, render(v)))
+                .collect();
+            Json::from(format!("<input {} />", attrs.join(" ")))
+        },
+        "dude" => |_, _| Json::from("dude"),
+        "missing" => |_, _| Json::from("missing"),
+        _ => return None,
+    };
+    Some(helper)
+}
+
+fn block_helper_catalog(id: &str) -> Option<Box<dyn HelperDef + 'static>> {
+    let helper: Box<dyn HelperDef + 'static> = match id {
+        "safe_string" => Box::new(SafeStringHelper),
+        "adjective_noun" => Box::new(AdjectiveNounHelper),
+        "with_data" => Box::new(WithDataHelper),
+        "depth" => Box::new(DepthHelper),
+        _ => match helper_catalog(id) {
+            Some(helper) => Box::new(CatalogHelper(helper)),
+            None => return None,
+        },
+    };
+    Some(helper)
+}
+
+fn noop_decorator(
+    _: &Decorator,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+) -> Result<(), RenderError> {
+    Ok(())
+}
+
+fn decorator_catalog(id: &str) -> Option<Box<dyn DecoratorDef + 'static>> {
+    match id {
+        "noop" => Some(Box::new(noop_decorator)),
+        _ => None,
+    }
+}
+
+fn load_json(path: &Path) -> Json {
+    let source = fs::read_to_string(path).unwrap();
+    serde_json::from_str(&source).unwrap()
+}
+
+fn render_case(case: &Json) -> Result<String, String> {
+    let mut hbs = Handlebars::new();
+    hbs.register_escape_fn(escape_expression);
+    hbs.set_mustache_mode(case["compat"].as_bool().unwrap_or(false));
+    // blocks that are not helpers are sections in Handlebars.js
+    hbs.register_helper("blockHelperMissing", Box::new(SectionHelper));
+    if let Some(helpers) = case["helpers"].as_object() {
+        for (name, id) in helpers {
+            let id = id.as_str().unwrap();
+            let helper = block_helper_catalog(id).ok_or_else(|| format!("no helper {:?}", id))?;
+            hbs.register_helper(name, helper);
+        }
+    }
+    if let Some(decorators) = case["decorators"].as_object() {
+        for (name, id) in decorators {
+            let id = id.as_str().unwrap();
+            let decorator =
+                decorator_catalog(id).ok_or_else(|| format!("no decorator {:?}", id))?;
+            hbs.register_decorator(name, decorator);
+        }
+    }
+    if let Some(partials) = case["partials"].as_object() {
+        for (name, partial) in partials {
+            hbs.register_partial(name, partial.as_str().unwrap())
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    hbs.register_template_string(CASE_TEMPLATE, case["template"].as_str().unwrap())
+        .map_err(|e| e.to_string())?;
+
+    let template = hbs.get_template(CASE_TEMPLATE).unwrap();
+    let ctx = Context::wraps(&case["data"]).map_err(|e| e.to_string())?;
+    let mut rc = RenderContext::new(template.name.as_ref());
+    if let Some(data) = case["runtime_data"].as_object() {
+        for (name, value) in data {
+            rc.set_local_var(format!("@{}", name), value.clone());
+        }
+    }
+    let mut output = StringOutput(String::new());
+    template
+        .render(&hbs, &ctx, &mut rc, &mut output)
+        .map_err(|e| e.to_string())?;
+    Ok(output.0)
+}
+
+// `Handlebars.escapeExpression`, which also escapes quotes, backticks and
+// equal signs
+fn escape_expression(data: &str) -> String {
+    let mut escaped = String::with_capacity(data.len());
+    for c in data.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            '`' => escaped.push_str("&#x60;"),
+            '=' => escaped.push_str("&#x3D;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[test]
+fn test_handlebars_spec() {
+    let skip: BTreeMap<String, BTreeMap<String, String>> =
+        serde_json::from_value(load_json(Path::new(SKIP_LIST))).unwrap();
+
+    let mut files: Vec<_> = fs::read_dir(SPEC_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    files.sort();
+
+    let mut failures = Vec::new();
+    let mut count = 0;
+    for file in &files {
+        let spec = file.file_stem().unwrap().to_str().unwrap();
+        let skipped = skip.get(spec);
+        for case in load_json(file)["tests"].as_array().unwrap() {
+            let name = case["name"].as_str().unwrap();
+            let result = render_case(case);
+            let passed = match case["expected"].as_str() {
+                Some(expected) => result.as_ref().map(|s| s.as_str()) == Ok(expected),
+                None => result.is_err(),
+            };
+            count += 1;
+
+            match (passed, skipped.map_or(false, |s| s.contains_key(name))) {
+                (false, false) => failures.push(format!(
+                    "{}: {}, expected {:?}, got {:?}",
+                    spec, name, case["expected"], result
+                )),
+                (true, true) => failures.push(format!(
+                    "{}: {} passes, remove it from the skip list",
+                    spec, name
+                )),
+                _ => {}
+            }
+        }
+    }
+
+    assert!(count > 0);
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
@@ -0,0 +1,76 @@
+//! Runs the Mustache spec fixtures in `tests/mustache/specs` in Mustache
+//! mode. Cases listed in `tests/mustache/skip.json`, by file and name, are
+//! expected to fail; they are reported once they pass.
+extern crate handlebars;
+extern crate serde_json;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use handlebars::Handlebars;
+use serde_json::Value as Json;
+
+const SPEC_DIR: &str = "tests/mustache/specs";
+const SKIP_LIST: &str = "tests/mustache/skip.json";
+
+fn load_json(path: &Path) -> Json {
+    let source = fs::read_to_string(path).unwrap();
+    serde_json::from_str(&source).unwrap()
+}
+
+fn render_case(case: &Json) -> Result<String, String> {
+    let mut hbs = Handlebars::new();
+    hbs.set_mustache_mode(true);
+    if let Some(partials) = case["partials"].as_object() {
+        for (name, partial) in partials {
+            hbs.register_partial(name, partial.as_str().unwrap())
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    hbs.render_template(case["template"].as_str().unwrap(), &case["data"])
+        .map_err(|e| e.to_string())
+}
+
+#[test]
+fn test_mustache_spec() {
+    let skip: BTreeMap<String, BTreeMap<String, String>> =
+        serde_json::from_value(load_json(Path::new(SKIP_LIST))).unwrap();
+
+    let mut files: Vec<_> = fs::read_dir(SPEC_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    files.sort();
+
+    let mut failures = Vec::new();
+    let mut count = 0;
+    for file in &files {
+        let spec = file.file_stem().unwrap().to_str().unwrap();
+        let skipped = skip.get(spec);
+        for case in load_json(file)["tests"].as_array().unwrap() {
+            let name = case["name"].as_str().unwrap();
+            let expected = case["expected"].as_str().unwrap();
+            let passed = render_case(case).as_ref().map(|s| s.as_str()) == Ok(expected);
+            count += 1;
+
+            match (passed, skipped.map_or(false, |s| s.contains_key(name))) {
+                (false, false) => failures.push(format!(
+                    "{}: {}, expected {:?}, got {:?}",
+                    spec,
+                    name,
+                    expected,
+                    render_case(case)
+                )),
+                (true, true) => failures.push(format!(
+                    "{}: {} passes, remove it from the skip list",
+                    spec, name
+                )),
+                _ => {}
+            }
+        }
+    }
+
+    assert!(count > 0);
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
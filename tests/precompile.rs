@@ -0,0 +1,67 @@
+extern crate handlebars;
+#[macro_use]
+extern crate serde_json;
+
+use handlebars::{Handlebars, Precompiler};
+
+mod templates {
+    include!("precompiled/templates.rs");
+}
+
+static TEMPLATES: &[(&str, &str)] = &[
+    (
+        "index",
+        "<h1>{{title}}</h1>{{! comment }}\n\
+         {{#each items as |item i|}}{{i}}: {{item.name}} {{/each}}\n\
+         {{#if (lookup flags \"on\")}}on{{else}}off{{/if}}\n\
+         {{> footer year=2019 tags=[\"a\", 1, -2, 0.5, null, true] meta={\"k\": \"v\"}}}",
+    ),
+    (
+        "footer",
+        "{{#*inline \"c\"}}(c){{/inline}}{{> c}} {{year}} {{{raw}}}",
+    ),
+];
+
+fn precompiler() -> Precompiler {
+    let mut precompiler = Precompiler::new();
+    for (name, source) in TEMPLATES {
+        precompiler.register_template_string(name, source).unwrap();
+    }
+    precompiler
+}
+
+#[test]
+fn test_precompiled_up_to_date() {
+    let expected = include_str!("precompiled/templates.rs");
+    assert_eq!(precompiler().generate(), expected);
+}
+
+#[test]
+fn test_precompiled_templates() {
+    let mut compiled = Handlebars::new();
+    for (name, source) in TEMPLATES {
+        compiled.register_template_string(name, source).unwrap();
+    }
+
+    let mut precompiled = Handlebars::new();
+    templates::register_templates(&mut precompiled);
+
+    for (name, _) in TEMPLATES {
+        assert_eq!(compiled.get_template(name), precompiled.get_template(name));
+    }
+
+    let data = json!({
+        "title": "hello",
+        "items": [{"name": "a"}, {"name": "b"}],
+        "flags": {"on": true},
+        "raw": "<b>"
+    });
+    assert_eq!(
+        precompiled.render("index", &data).unwrap(),
+        compiled.render("index", &data).unwrap()
+    );
+    assert_eq!(
+        precompiled.render("index", &data).unwrap(),
+        "<h1>hello</h1>\n0: a 1: b \non\n(c) 2019 <b>"
+    );
+}
@@ -0,0 +1,116 @@
+// Generated by handlebars::Precompiler, do not edit.
+
+#[allow(unused_imports)]
+use ::handlebars::template::{BlockParam, DirectiveTemplate, HelperTemplate, Parameter, Subexpression, TemplateElement, TemplateMapping};
+#[allow(unused_imports)]
+use ::handlebars::{Handlebars, JsonValue, Template};
+
+/// Register all precompiled templates
+pub fn register_templates(registry: &mut Handlebars) {
+    registry.register_template("footer", template_0());
+    registry.register_template("index", template_1());
+}
+
+fn template_0() -> Template {
+    Template::from_elements(
+        vec![
+            TemplateElement::DirectiveBlock(DirectiveTemplate::new(
+                Parameter::Name("inline".to_owned()),
+                vec![Parameter::Literal(JsonValue::String("c".to_owned()))],
+                vec![].into_iter().collect(),
+                Some(Template::from_elements(
+                    vec![
+                        TemplateElement::RawString("(c)".to_owned()),
+                    ],
+                    Some(vec![TemplateMapping(1, 17)]),
+                )),
+            )),
+            TemplateElement::PartialExpression(DirectiveTemplate::new(
+                Parameter::Name("c".to_owned()),
+                vec![],
+                vec![].into_iter().collect(),
+                None,
+            )),
+            TemplateElement::RawString(" ".to_owned()),
+            TemplateElement::Expression(Box::new(HelperTemplate::new(
+                Parameter::Name("year".to_owned()),
+                vec![],
+                vec![].into_iter().collect(),
+            ))),
+            TemplateElement::RawString(" ".to_owned()),
+            TemplateElement::HTMLExpression(Parameter::Name("raw".to_owned())),
+        ],
+        Some(vec![TemplateMapping(1, 1), TemplateMapping(1, 31), TemplateMapping(1, 39), TemplateMapping(1, 39), TemplateMapping(1, 48), TemplateMapping(1, 48)]),
+    )
+}
+
+fn template_1() -> Template {
+    Template::from_elements(
+        vec![
+            TemplateElement::RawString("<h1>".to_owned()),
+            TemplateElement::Expression(Box::new(HelperTemplate::new(
+                Parameter::Name("title".to_owned()),
+                vec![],
+                vec![].into_iter().collect(),
+            ))),
+            TemplateElement::RawString("</h1>".to_owned()),
+            TemplateElement::Comment(" comment ".to_owned()),
+            TemplateElement::RawString("\n".to_owned()),
+            TemplateElement::HelperBlock(Box::new(HelperTemplate::new_block(
+                Parameter::Name("each".to_owned()),
+                vec![Parameter::Name("items".to_owned())],
+                vec![].into_iter().collect(),
+                Some(BlockParam::Pair((Parameter::Name("item".to_owned()), Parameter::Name("i".to_owned())))),
+                Some(Template::from_elements(
+                    vec![
+                        TemplateElement::Expression(Box::new(HelperTemplate::new(
+                            Parameter::Name("i".to_owned()),
+                            vec![],
+                            vec![].into_iter().collect(),
+                        ))),
+                        TemplateElement::RawString(": ".to_owned()),
+                        TemplateElement::Expression(Box::new(HelperTemplate::new(
+                            Parameter::Name("item.name".to_owned()),
+                            vec![],
+                            vec![].into_iter().collect(),
+                        ))),
+                        TemplateElement::RawString(" ".to_owned()),
+                    ],
+                    Some(vec![TemplateMapping(2, 28), TemplateMapping(2, 33), TemplateMapping(2, 35), TemplateMapping(2, 49)]),
+                )),
+                None,
+            ))),
+            TemplateElement::RawString("\n".to_owned()),
+            TemplateElement::HelperBlock(Box::new(HelperTemplate::new_block(
+                Parameter::Name("if".to_owned()),
+                vec![Parameter::Subexpression(Subexpression::from_helper(HelperTemplate::new(
+                    Parameter::Name("lookup".to_owned()),
+                    vec![Parameter::Name("flags".to_owned()), Parameter::Literal(JsonValue::String("on".to_owned()))],
+                    vec![].into_iter().collect(),
+                ), Some(TemplateMapping(3, 7))))],
+                vec![].into_iter().collect(),
+                None,
+                Some(Template::from_elements(
+                    vec![
+                        TemplateElement::RawString("on".to_owned()),
+                    ],
+                    Some(vec![TemplateMapping(3, 28)]),
+                )),
+                Some(Template::from_elements(
+                    vec![
+                        TemplateElement::RawString("off".to_owned()),
+                    ],
+                    Some(vec![TemplateMapping(3, 38)]),
+                )),
+            ))),
+            TemplateElement::RawString("\n".to_owned()),
+            TemplateElement::PartialExpression(DirectiveTemplate::new(
+                Parameter::Name("footer".to_owned()),
+                vec![],
+                vec![("meta".to_owned(), Parameter::Literal(JsonValue::Object(vec![("k".to_owned(), JsonValue::String("v".to_owned()))].into_iter().collect()))), ("tags".to_owned(), Parameter::Literal(JsonValue::Array(vec![JsonValue::String("a".to_owned()), JsonValue::from(1u64), JsonValue::from(-2i64), JsonValue::from(0.5f64), JsonValue::Null, JsonValue::Bool(true)]))), ("year".to_owned(), Parameter::Literal(JsonValue::from(2019u64)))].into_iter().collect(),
+                None,
+            )),
+        ],
+        Some(vec![TemplateMapping(1, 1), TemplateMapping(1, 5), TemplateMapping(1, 14), TemplateMapping(1, 19), TemplateMapping(2, 1), TemplateMapping(2, 1), TemplateMapping(3, 1), TemplateMapping(3, 1), TemplateMapping(4, 1), TemplateMapping(4, 1)]),
+    )
+}
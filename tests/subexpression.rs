@@ -53,3 +53,56 @@ fn test_subexpression() {
         "Success"
     );
 }
+
+#[test]
+fn test_nested_subexpression() {
+    let hbs = Handlebars::new();
+
+    let data = json!({"a": 1, "b": 3, "c": 2, "op": "gt"});
+
+    assert_eq!(
+        hbs.render_template(
+            "{{#if (and (eq a 1) (not (or (gt a b) (and (eq c 2) (lt b a)))))}}Success{{else}}Failed{{/if}}",
+            &data
+        )
+        .unwrap(),
+        "Success"
+    );
+
+    // subexpressions as hash values
+    assert_eq!(
+        hbs.render_template(
+            "{{#*inline \"p\"}}{{v}}{{/inline}}{{> p v=(and (eq a 1) (not (and (gt a b) (eq c 2))))}}",
+            &data
+        )
+        .unwrap(),
+        "true"
+    );
+
+    // the helper is picked by a subexpression
+    assert_eq!(
+        hbs.render_template("{{#if ((lookup this \"op\") b a)}}Success{{/if}}", &data)
+            .unwrap(),
+        "Success"
+    );
+}
+
+#[test]
+fn test_nested_subexpression_error_position() {
+    let mut hbs = Handlebars::new();
+    hbs.register_template_string(
+        "t0",
+        "{{#if x}}\n  {{#if (and (eq a 1) (gt b (nope a)))}}yes{{/if}}{{/if}}",
+    )
+    .unwrap();
+    hbs.register_template_string("t1", "{{#if (and (eq a 1) (gt b \"x\"))}}yes{{/if}}")
+        .unwrap();
+
+    let data = json!({"x": true, "a": 1, "b": 3});
+    let e = hbs.render("t0", &data).unwrap_err();
+    assert_eq!(e.template_name, Some("t0".to_owned()));
+    assert_eq!((e.line_no, e.column_no), (Some(2), Some(29)));
+
+    let e = hbs.render("t1", &data).unwrap_err();
+    assert_eq!((e.line_no, e.column_no), (Some(1), Some(21)));
+}